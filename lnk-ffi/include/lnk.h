@@ -0,0 +1,173 @@
+#ifndef LNK_H
+#define LNK_H
+
+/* Generated by cbindgen from src/lib.rs, do not edit: cbindgen --config cbindgen.toml --output include/lnk.h */
+
+#include <stdint.h>
+#include <stddef.h>
+
+// The result of the functions that can fail.
+typedef enum LnkStatus {
+  LNK_STATUS_OK = 0,
+  // A pointer argument is NULL.
+  LNK_STATUS_NULL_ARGUMENT = 1,
+  // The path is not valid UTF-8.
+  LNK_STATUS_INVALID_PATH = 2,
+  // The file could not be read.
+  LNK_STATUS_IO = 3,
+  // The data is not a valid LNK file.
+  LNK_STATUS_PARSE = 4,
+  // The parser panicked, this is a bug.
+  LNK_STATUS_PANIC = 5,
+} LnkStatus;
+
+// A parsed LNK file.
+typedef struct LnkHandle LnkHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parse the LNK file in `data`. On success `*out` is a handle to free with `lnk_free`, otherwise it is NULL.
+//
+// # Safety
+// `data` must point to `len` readable bytes and `out` to a writable pointer.
+LnkStatus lnk_parse_buffer(const uint8_t *data, size_t len, LnkHandle **out);
+
+// Parse the LNK file at `path`, the `lnk_*` fields describe the file itself. On success `*out` is a handle to free
+// with `lnk_free`, otherwise it is NULL.
+//
+// # Safety
+// `path` must be a NUL terminated string and `out` a writable pointer.
+LnkStatus lnk_parse_path(const char *path, LnkHandle **out);
+
+// Release a handle, NULL is ignored.
+//
+// # Safety
+// `handle` must come from `lnk_parse_buffer` or `lnk_parse_path` and not be used afterwards.
+void lnk_free(LnkHandle *handle);
+
+// The message of the error of the last parse call of the calling thread, NULL when it succeeded. The string stays
+// valid until the next parse call on the same thread.
+const char *lnk_last_error(void);
+
+// A normalized field by name, NULL when the handle or the name is NULL, the field is unknown or empty.
+//
+// # Safety
+// `handle` must be a live handle or NULL and `name` a NUL terminated string or NULL.
+const char *lnk_get_field(const LnkHandle *handle, const char *name);
+
+// The path of the target, NULL when it is unknown.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_target_full_path(const LnkHandle *handle);
+
+// The arguments passed to the target, NULL when there are none.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_command_line_arguments(const LnkHandle *handle);
+
+// The command line run when the shortcut is opened, with the environment variables expanded.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_command_line(const LnkHandle *handle);
+
+// The working directory, NULL when it is not set.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_working_dir(const LnkHandle *handle);
+
+// The description of the shortcut, NULL when it is not set.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_name_string(const LnkHandle *handle);
+
+// The path of the target relative to the LNK file, NULL when it is not set.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_relative_path(const LnkHandle *handle);
+
+// The location of the icon, NULL when it is not set.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_icon_location(const LnkHandle *handle);
+
+// The NetBIOS name of the machine the target was on (TrackerDataBlock), NULL when it is unknown.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_target_hostname(const LnkHandle *handle);
+
+// The type of the volume of the target (`DRIVE_FIXED`, `DRIVE_REMOVABLE`, ...), NULL when it is unknown.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_drive_type(const LnkHandle *handle);
+
+// The serial number of the volume of the target (`XXXX-XXXX`), NULL when it is unknown.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_volume_serial_number(const LnkHandle *handle);
+
+// The label of the volume of the target, NULL when it is unknown.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_volume_label(const LnkHandle *handle);
+
+// The network share of the target (`\\server\share`), NULL when the target is local.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_net_name(const LnkHandle *handle);
+
+// The modification time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_target_modification_time(const LnkHandle *handle);
+
+// The access time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_target_access_time(const LnkHandle *handle);
+
+// The creation time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+const char *lnk_target_creation_time(const LnkHandle *handle);
+
+// The size of the target recorded in the header, 0 when the handle is NULL.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+uint32_t lnk_target_size(const LnkHandle *handle);
+
+// The full record as JSON, the same as the `json` output of `lnk_parser`. The string must be released with
+// `lnk_string_free`, NULL when the handle is NULL or the record can not be serialized.
+//
+// # Safety
+// `handle` must be a live handle or NULL.
+char *lnk_to_json(const LnkHandle *handle);
+
+// Release a string returned by `lnk_to_json`, NULL is ignored.
+//
+// # Safety
+// `s` must come from `lnk_to_json` and not be used afterwards.
+void lnk_string_free(char *s);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* LNK_H */
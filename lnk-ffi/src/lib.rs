@@ -0,0 +1,352 @@
+//! C ABI of the `lnk` crate.
+//!
+//! A LNK file is parsed into an opaque `LnkHandle` with `lnk_parse_buffer` or `lnk_parse_path` and released with
+//! `lnk_free`. The accessors return the normalized fields (see `lnk::NORMALIZED_FIELDS`) as NUL terminated UTF-8
+//! strings owned by the handle, they stay valid until the handle is freed. `lnk_to_json` returns the full record as
+//! a string the caller releases with `lnk_string_free`.
+//!
+//! Every function that can fail returns a `LnkStatus`, the message of the last error of the calling thread is
+//! returned by `lnk_last_error`. The C declarations are in `include/lnk.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/lnk.h`.
+
+use lnk::{schema::Versioned, Lnk, LnkFileMetaData};
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  ffi::{c_char, CStr, CString},
+  io::{Error, ErrorKind},
+  panic::{self, AssertUnwindSafe},
+  ptr, slice,
+};
+use winparsingtools::traits::Normalize;
+
+/// The result of the functions that can fail.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LnkStatus {
+  Ok = 0,
+  /// A pointer argument is NULL.
+  NullArgument = 1,
+  /// The path is not valid UTF-8.
+  InvalidPath = 2,
+  /// The file could not be read.
+  Io = 3,
+  /// The data is not a valid LNK file.
+  Parse = 4,
+  /// The parser panicked, this is a bug.
+  Panic = 5,
+}
+
+/// A parsed LNK file.
+pub struct LnkHandle {
+  lnk: Lnk,
+  /// The normalized fields, NUL terminated.
+  fields: HashMap<String, CString>,
+}
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+  let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+  LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+  LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
+
+fn to_c_string(s: &str) -> CString {
+  CString::new(s.replace('\0', "")).unwrap_or_default()
+}
+
+/// Parse a buffer, any error is reported as `InvalidData`.
+fn from_buffer(data: &[u8]) -> std::io::Result<Lnk> {
+  Lnk::from_buffer(data).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Run the parser and build the handle without letting a panic unwind into C, and store the handle in `out`.
+unsafe fn parse<F: FnOnce() -> std::io::Result<Lnk>>(out: *mut *mut LnkHandle, parse: F) -> LnkStatus {
+  if out.is_null() {
+    set_last_error(String::from("out is NULL"));
+    return LnkStatus::NullArgument;
+  }
+  *out = ptr::null_mut();
+  let handle = panic::catch_unwind(AssertUnwindSafe(|| -> std::io::Result<LnkHandle> {
+    let lnk = parse()?;
+    let fields = lnk
+      .normalize()
+      .into_iter()
+      .map(|(field, value)| (field, to_c_string(&value)))
+      .collect();
+    Ok(LnkHandle { lnk, fields })
+  }));
+  match handle {
+    Ok(Ok(handle)) => {
+      clear_last_error();
+      *out = Box::into_raw(Box::new(handle));
+      LnkStatus::Ok
+    },
+    Ok(Err(e)) => {
+      set_last_error(e.to_string());
+      match e.kind() {
+        ErrorKind::InvalidData => LnkStatus::Parse,
+        _ => LnkStatus::Io,
+      }
+    },
+    Err(_) => {
+      set_last_error(String::from("the parser panicked"));
+      LnkStatus::Panic
+    },
+  }
+}
+
+/// Parse the LNK file in `data`. On success `*out` is a handle to free with `lnk_free`, otherwise it is NULL.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_parse_buffer(data: *const u8, len: usize, out: *mut *mut LnkHandle) -> LnkStatus {
+  if data.is_null() {
+    set_last_error(String::from("data is NULL"));
+    return LnkStatus::NullArgument;
+  }
+  let data = slice::from_raw_parts(data, len);
+  parse(out, || from_buffer(data))
+}
+
+/// Parse the LNK file at `path`, the `lnk_*` fields describe the file itself. On success `*out` is a handle to free
+/// with `lnk_free`, otherwise it is NULL.
+///
+/// # Safety
+/// `path` must be a NUL terminated string and `out` a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_parse_path(path: *const c_char, out: *mut *mut LnkHandle) -> LnkStatus {
+  if path.is_null() {
+    set_last_error(String::from("path is NULL"));
+    return LnkStatus::NullArgument;
+  }
+  let Ok(path) = CStr::from_ptr(path).to_str() else {
+    set_last_error(String::from("path is not valid UTF-8"));
+    return LnkStatus::InvalidPath;
+  };
+  parse(out, || {
+    let data = std::fs::read(path)?;
+    let mut lnk = from_buffer(&data)?;
+    lnk.lnk_file_metadata = Some(LnkFileMetaData::from_path(path)?);
+    Ok(lnk)
+  })
+}
+
+/// Release a handle, NULL is ignored.
+///
+/// # Safety
+/// `handle` must come from `lnk_parse_buffer` or `lnk_parse_path` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_free(handle: *mut LnkHandle) {
+  if !handle.is_null() {
+    drop(Box::from_raw(handle));
+  }
+}
+
+/// The message of the error of the last parse call of the calling thread, NULL when it succeeded. The string stays
+/// valid until the next parse call on the same thread.
+#[no_mangle]
+pub extern "C" fn lnk_last_error() -> *const c_char {
+  LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// A normalized field by name, NULL when the handle or the name is NULL, the field is unknown or empty.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL and `name` a NUL terminated string or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_get_field(handle: *const LnkHandle, name: *const c_char) -> *const c_char {
+  if name.is_null() {
+    return ptr::null();
+  }
+  match CStr::from_ptr(name).to_str() {
+    Ok(name) => field(handle, name),
+    Err(_) => ptr::null(),
+  }
+}
+
+unsafe fn field(handle: *const LnkHandle, name: &str) -> *const c_char {
+  match handle.as_ref().and_then(|handle| handle.fields.get(name)) {
+    Some(value) if !value.as_bytes().is_empty() => value.as_ptr(),
+    _ => ptr::null(),
+  }
+}
+
+/// The path of the target, NULL when it is unknown.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_full_path(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "target_full_path")
+}
+
+/// The arguments passed to the target, NULL when there are none.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_command_line_arguments(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "command_line_arguments")
+}
+
+/// The command line run when the shortcut is opened, with the environment variables expanded.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_command_line(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "command_line")
+}
+
+/// The working directory, NULL when it is not set.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_working_dir(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "working_dir")
+}
+
+/// The description of the shortcut, NULL when it is not set.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_name_string(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "name_string")
+}
+
+/// The path of the target relative to the LNK file, NULL when it is not set.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_relative_path(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "relative_path")
+}
+
+/// The location of the icon, NULL when it is not set.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_icon_location(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "icon_location")
+}
+
+/// The NetBIOS name of the machine the target was on (TrackerDataBlock), NULL when it is unknown.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_hostname(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "target_hostname")
+}
+
+/// The type of the volume of the target (`DRIVE_FIXED`, `DRIVE_REMOVABLE`, ...), NULL when it is unknown.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_drive_type(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "drive_type")
+}
+
+/// The serial number of the volume of the target (`XXXX-XXXX`), NULL when it is unknown.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_volume_serial_number(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "volume_serial_number")
+}
+
+/// The label of the volume of the target, NULL when it is unknown.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_volume_label(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "volume_label")
+}
+
+/// The network share of the target (`\\server\share`), NULL when the target is local.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_net_name(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "net_name")
+}
+
+/// The modification time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_modification_time(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "target_modification_time")
+}
+
+/// The access time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_access_time(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "target_access_time")
+}
+
+/// The creation time of the target recorded in the header (`YYYY-MM-DDTHH:MM:SSZ`).
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_creation_time(handle: *const LnkHandle) -> *const c_char {
+  field(handle, "target_creation_time")
+}
+
+/// The size of the target recorded in the header, 0 when the handle is NULL.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_target_size(handle: *const LnkHandle) -> u32 {
+  handle.as_ref().map_or(0, |handle| handle.lnk.shell_link_header.file_size)
+}
+
+/// The full record as JSON, the same as the `json` output of `lnk_parser`. The string must be released with
+/// `lnk_string_free`, NULL when the handle is NULL or the record can not be serialized.
+///
+/// # Safety
+/// `handle` must be a live handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_to_json(handle: *const LnkHandle) -> *mut c_char {
+  let Some(handle) = handle.as_ref() else {
+    return ptr::null_mut();
+  };
+  // Serializing formats the dates stored in the file, a panic must not unwind into C either.
+  match panic::catch_unwind(|| serde_json::to_string(&Versioned::new(&handle.lnk))) {
+    Ok(Ok(json)) => to_c_string(&json).into_raw(),
+    _ => ptr::null_mut(),
+  }
+}
+
+/// Release a string returned by `lnk_to_json`, NULL is ignored.
+///
+/// # Safety
+/// `s` must come from `lnk_to_json` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lnk_string_free(s: *mut c_char) {
+  if !s.is_null() {
+    drop(CString::from_raw(s));
+  }
+}
@@ -0,0 +1,128 @@
+/*
+ * Exercise the C ABI of lnk-ffi. Built and run by tests/c_api.rs, the samples directory of the lnk crate is
+ * the first argument, as an absolute path.
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "lnk.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                   \
+  do {                                                                     \
+    if (!(condition)) {                                                    \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+      failures++;                                                          \
+    }                                                                      \
+  } while (0)
+
+static int equals(const char *value, const char *expected) {
+  return value != NULL && strcmp(value, expected) == 0;
+}
+
+static unsigned char *read_file(const char *path, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  if (file == NULL) {
+    return NULL;
+  }
+  fseek(file, 0, SEEK_END);
+  long size = ftell(file);
+  fseek(file, 0, SEEK_SET);
+  unsigned char *data = malloc(size);
+  *len = fread(data, 1, size, file);
+  fclose(file);
+  return data;
+}
+
+static void test_parse_path(const char *samples) {
+  char path[4096];
+  snprintf(path, sizeof(path), "%s/WIN10/1607_14393/windows_generated.lnk", samples);
+
+  LnkHandle *lnk = NULL;
+  CHECK(lnk_parse_path(path, &lnk) == LNK_STATUS_OK);
+  CHECK(lnk != NULL);
+  CHECK(equals(lnk_target_full_path(lnk), "C:\\Users\\u0041\\Desktop\\test\\test.txt"));
+  CHECK(lnk_target_size(lnk) == 4);
+  CHECK(equals(lnk_target_hostname(lnk), "win10"));
+  CHECK(equals(lnk_drive_type(lnk), "DRIVE_FIXED"));
+  CHECK(equals(lnk_volume_serial_number(lnk), "E02E-8A93"));
+  CHECK(lnk_net_name(lnk) == NULL);
+  CHECK(lnk_target_modification_time(lnk) != NULL);
+  CHECK(equals(lnk_get_field(lnk, "lnk_full_path"), path));
+  CHECK(lnk_get_field(lnk, "no_such_field") == NULL);
+
+  char *json = lnk_to_json(lnk);
+  CHECK(json != NULL && strstr(json, "\"shell_link_header\"") != NULL);
+  lnk_string_free(json);
+  lnk_free(lnk);
+}
+
+static void test_parse_buffer(const char *samples) {
+  char path[4096];
+  snprintf(path, sizeof(path), "%s/WIN10/1607_14393/network_share.lnk", samples);
+  size_t len = 0;
+  unsigned char *data = read_file(path, &len);
+  CHECK(data != NULL);
+  if (data == NULL) {
+    return;
+  }
+
+  LnkHandle *lnk = NULL;
+  CHECK(lnk_parse_buffer(data, len, &lnk) == LNK_STATUS_OK);
+  CHECK(equals(lnk_net_name(lnk), "\\\\127.0.0.1\\TEST"));
+  /* A buffer has no file, the lnk_* fields are empty. */
+  CHECK(lnk_get_field(lnk, "lnk_full_path") == NULL);
+  lnk_free(lnk);
+
+  /* A truncated file fails and leaves the handle NULL. */
+  lnk = (LnkHandle *)data;
+  CHECK(lnk_parse_buffer(data, 10, &lnk) == LNK_STATUS_PARSE);
+  CHECK(lnk == NULL);
+  CHECK(lnk_last_error() != NULL);
+
+  /* A successful call clears the last error. */
+  CHECK(lnk_parse_buffer(data, len, &lnk) == LNK_STATUS_OK);
+  CHECK(lnk_last_error() == NULL);
+  lnk_free(lnk);
+
+  /* A CommonNetworkRelativeLink of size 0 makes the parser panic, the panic does not unwind into C. */
+  memset(data + 0x68, 0, 4);
+  CHECK(lnk_parse_buffer(data, len, &lnk) == LNK_STATUS_PANIC);
+  CHECK(lnk == NULL);
+  CHECK(lnk_last_error() != NULL);
+  free(data);
+}
+
+static void test_errors(void) {
+  LnkHandle *lnk = NULL;
+  CHECK(lnk_parse_path("/no/such/file.lnk", &lnk) == LNK_STATUS_IO);
+  CHECK(lnk == NULL);
+  CHECK(lnk_parse_path(NULL, &lnk) == LNK_STATUS_NULL_ARGUMENT);
+  CHECK(lnk_parse_buffer(NULL, 0, &lnk) == LNK_STATUS_NULL_ARGUMENT);
+  CHECK(lnk_parse_path("/no/such/file.lnk", NULL) == LNK_STATUS_NULL_ARGUMENT);
+
+  /* NULL handles are accepted everywhere. */
+  CHECK(lnk_target_full_path(NULL) == NULL);
+  CHECK(lnk_target_size(NULL) == 0);
+  CHECK(lnk_to_json(NULL) == NULL);
+  lnk_free(NULL);
+  lnk_string_free(NULL);
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s SAMPLES_DIR\n", argv[0]);
+    return 2;
+  }
+  test_parse_path(argv[1]);
+  test_parse_buffer(argv[1]);
+  test_errors();
+  if (failures > 0) {
+    fprintf(stderr, "%d check(s) failed\n", failures);
+    return 1;
+  }
+  printf("all checks passed\n");
+  return 0;
+}
@@ -0,0 +1,65 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+/// The directory cargo puts the shared library in, `target/<profile>` next to the `deps` directory of the test.
+fn library_dir() -> PathBuf {
+  let exe = std::env::current_exe().unwrap();
+  exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_c_program() {
+  let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+  let library_dir = library_dir();
+  assert!(library_dir.join("liblnk_ffi.so").exists(), "liblnk_ffi.so not found in {}", library_dir.display());
+  let program = library_dir.join("test_lnk_c");
+
+  let status = Command::new(std::env::var("CC").unwrap_or_else(|_| String::from("cc")))
+    .arg("-std=c99")
+    .arg("-Wall")
+    .arg("-Werror")
+    .arg("-I")
+    .arg(manifest_dir.join("include"))
+    .arg(manifest_dir.join("tests/c/test_lnk.c"))
+    .arg("-o")
+    .arg(&program)
+    .arg("-L")
+    .arg(&library_dir)
+    .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+    .arg("-llnk_ffi")
+    .status()
+    .expect("a C compiler is needed to run this test");
+  assert!(status.success(), "the C test program did not compile");
+
+  let samples = fs::canonicalize(manifest_dir.join("../lnk/samples")).unwrap();
+  let output = Command::new(&program).arg(samples).output().unwrap();
+  assert!(
+    output.status.success(),
+    "{}{}",
+    String::from_utf8_lossy(&output.stdout),
+    String::from_utf8_lossy(&output.stderr)
+  );
+}
+
+#[test]
+fn test_header_declares_every_function() {
+  let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+  let source = fs::read_to_string(manifest_dir.join("src/lib.rs")).unwrap();
+  let header = fs::read_to_string(manifest_dir.join("include/lnk.h")).unwrap();
+  let functions: Vec<&str> = source
+    .split("#[no_mangle]")
+    .skip(1)
+    .map(|item| {
+      let name = item.split("fn ").nth(1).unwrap();
+      &name[..name.find('(').unwrap()]
+    })
+    .collect();
+  assert!(functions.contains(&"lnk_parse_buffer"));
+  for function in functions {
+    assert!(header.contains(&format!("{}(", function)), "{} is not declared in include/lnk.h", function);
+  }
+}
@@ -0,0 +1,269 @@
+//! Read LNK files and jump lists from triage archives (`.zip`, `.tar`, `.tar.gz`/`.tgz`) without extracting them.
+//!
+//! The members are read into memory, so they can be parsed with [`Lnk::from_buffer`](crate::Lnk::from_buffer) or
+//! [`JumpList::from_buffer`](crate::jump_list::JumpList::from_buffer), and the member timestamps are kept in a
+//! [`LnkFileMetaData`], the times the archive does not store are `None`. Members larger than [`MAX_MEMBER_SIZE`] are
+//! not read.
+
+use crate::LnkFileMetaData;
+use byteorder::{ByteOrder, LittleEndian};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use glob::{MatchOptions, Pattern};
+use std::{
+  fs::File,
+  io::{BufReader, Error, ErrorKind, Read, Result},
+  path::Path,
+};
+
+/// Windows paths are case-insensitive, `*` does not match `/`.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+  case_sensitive: false,
+  require_literal_separator: true,
+  require_literal_leading_dot: false,
+};
+
+/// Member names matched when only the archive is given.
+pub const DEFAULT_MEMBER_PATTERNS: &[&str] = &[
+  "**/*.lnk",
+  "**/*.automaticDestinations-ms",
+  "**/*.customDestinations-ms",
+];
+
+/// Members larger than this are not read: LNK files are a few KB and jump lists a few MB, a larger member is more
+/// likely a decompression bomb.
+pub const MAX_MEMBER_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Seconds between 1601-01-01 and 1970-01-01.
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
+/// A file read from an archive.
+#[derive(Debug)]
+pub struct ArchiveMember {
+  /// The member name, always with `/` separators.
+  pub name: String,
+  pub data: Vec<u8>,
+  /// `full_path` is `<archive>/<member name>` and the times are the ones stored in the archive.
+  pub metadata: LnkFileMetaData,
+}
+
+/// Returns `true` if the file name has an archive extension this module can read.
+pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+  let name = path
+    .as_ref()
+    .file_name()
+    .map(|name| name.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  [".zip", ".tar", ".tar.gz", ".tgz"]
+    .iter()
+    .any(|extension| name.ends_with(extension))
+}
+
+/// Split a path that points into an archive into the archive path and the member name pattern.
+/// The first component with an archive extension is the archive, `None` is returned if there is none.
+/// # Example
+/// ```
+/// use lnk::archive::split_path;
+///
+/// assert_eq!(
+///   split_path("triage/WS01.zip/C/Users/*/AppData/Roaming/Microsoft/Windows/Recent/*.lnk"),
+///   Some((String::from("triage/WS01.zip"), String::from("C/Users/*/AppData/Roaming/Microsoft/Windows/Recent/*.lnk")))
+/// );
+/// assert_eq!(split_path("WS01.tar.gz"), Some((String::from("WS01.tar.gz"), String::new())));
+/// assert_eq!(split_path("Recent/*.lnk"), None);
+/// ```
+pub fn split_path(path: &str) -> Option<(String, String)> {
+  let mut end = 0;
+  for component in path.split_inclusive(['/', '\\']) {
+    end += component.len();
+    let name = component.trim_end_matches(['/', '\\']);
+    if is_archive(name) {
+      let member = path[end..].replace('\\', "/");
+      return Some((path[..end].trim_end_matches(['/', '\\']).to_string(), member));
+    }
+  }
+  None
+}
+
+/// Read every member of the archive whose name matches one of `patterns`, in archive order. A member larger than
+/// [`MAX_MEMBER_SIZE`] is an `InvalidData` error in its place, the other members are still read.
+pub fn read_members<P: AsRef<Path>>(path: P, patterns: &[Pattern]) -> Result<Vec<Result<ArchiveMember>>> {
+  let path = path.as_ref();
+  let archive_path = path.to_string_lossy().to_string();
+  let matches = |name: &str| patterns.iter().any(|p| p.matches_with(name, MATCH_OPTIONS));
+  let name = archive_path.to_lowercase();
+  let file = BufReader::new(File::open(path)?);
+  if name.ends_with(".zip") {
+    read_zip(file, &archive_path, matches)
+  } else if name.ends_with(".tar") {
+    read_tar(file, &archive_path, matches)
+  } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+    read_tar(GzDecoder::new(file), &archive_path, matches)
+  } else {
+    Err(Error::new(ErrorKind::InvalidInput, format!("'{}' is not a supported archive", archive_path)))
+  }
+}
+
+/// Read the data of a member, the size stored in the archive is not trusted: at most [`MAX_MEMBER_SIZE`] bytes are
+/// read.
+fn member_data<R: Read>(reader: R, member_path: &str) -> Result<Result<Vec<u8>>> {
+  let mut data = vec![];
+  reader.take(MAX_MEMBER_SIZE + 1).read_to_end(&mut data)?;
+  if data.len() as u64 > MAX_MEMBER_SIZE {
+    return Ok(Err(Error::new(
+      ErrorKind::InvalidData,
+      format!("'{}' is larger than {} bytes, it was not read", member_path, MAX_MEMBER_SIZE),
+    )));
+  }
+  Ok(Ok(data))
+}
+
+/// Member names are relative, without the `./` some archivers add.
+fn member_name(name: &str) -> String {
+  name
+    .replace('\\', "/")
+    .trim_start_matches("./")
+    .trim_start_matches('/')
+    .to_string()
+}
+
+fn read_zip<R, F>(reader: R, archive_path: &str, matches: F) -> Result<Vec<Result<ArchiveMember>>>
+where
+  R: Read + std::io::Seek,
+  F: Fn(&str) -> bool,
+{
+  let mut archive = zip::ZipArchive::new(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+  let mut members = vec![];
+  for index in 0..archive.len() {
+    let mut file = archive.by_index(index).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let name = member_name(file.name());
+    if file.is_dir() || !matches(&name) {
+      continue;
+    }
+    // The DOS time has no time zone, it is used as UTC when the extra fields do not carry better times.
+    let modified = file
+      .last_modified()
+      .and_then(|t| {
+        NaiveDate::from_ymd_opt(t.year() as i32, t.month() as u32, t.day() as u32)?.and_hms_opt(
+          t.hour() as u32,
+          t.minute() as u32,
+          t.second() as u32,
+        )
+      })
+      .map(|t| t.and_utc());
+    let times = zip_extra_times(file.extra_data().unwrap_or_default());
+    let full_path = format!("{}/{}", archive_path, name);
+    let member = member_data(&mut file, &full_path)?.map(|data| ArchiveMember {
+      metadata: LnkFileMetaData::new(full_path, times.modified.or(modified), times.accessed, times.created),
+      name,
+      data,
+    });
+    members.push(member);
+  }
+  Ok(members)
+}
+
+#[derive(Default)]
+struct MemberTimes {
+  modified: Option<DateTime<Utc>>,
+  accessed: Option<DateTime<Utc>>,
+  created: Option<DateTime<Utc>>,
+}
+
+/// Read the NTFS (0x000A, FILETIMEs) and extended timestamp (0x5455, Unix times) zip extra fields.
+fn zip_extra_times(mut extra: &[u8]) -> MemberTimes {
+  let mut times = MemberTimes::default();
+  while extra.len() >= 4 {
+    let id = LittleEndian::read_u16(extra);
+    let size = (LittleEndian::read_u16(&extra[2..]) as usize).min(extra.len() - 4);
+    let field = &extra[4..4 + size];
+    match id {
+      // Reserved (4 bytes) then attributes tagged with their size, tag 1 holds mtime, atime and ctime.
+      0x000A if size >= 32 && LittleEndian::read_u16(&field[4..]) == 1 => {
+        let filetime = |offset: usize| filetime_to_datetime(LittleEndian::read_u64(&field[offset..]));
+        times.modified = filetime(8);
+        times.accessed = filetime(16);
+        times.created = filetime(24);
+      },
+      // Flags then the times listed in the flags, the NTFS field is preferred when both are present.
+      0x5455 if size >= 1 && times.modified.is_none() => {
+        let flags = field[0];
+        let mut offset = 1;
+        let mut next = |present: bool| {
+          if !present || offset + 4 > field.len() {
+            return None;
+          }
+          let time = LittleEndian::read_i32(&field[offset..]);
+          offset += 4;
+          DateTime::from_timestamp(time as i64, 0)
+        };
+        times.modified = next(flags & 1 != 0);
+        times.accessed = next(flags & 2 != 0);
+        times.created = next(flags & 4 != 0);
+      },
+      _ => {},
+    }
+    extra = &extra[4 + size..];
+  }
+  times
+}
+
+fn filetime_to_datetime(filetime: u64) -> Option<DateTime<Utc>> {
+  if filetime == 0 {
+    return None;
+  }
+  let seconds = (filetime / 10_000_000) as i64 - FILETIME_UNIX_EPOCH;
+  let nanoseconds = (filetime % 10_000_000) as u32 * 100;
+  DateTime::from_timestamp(seconds, nanoseconds)
+}
+
+fn read_tar<R, F>(reader: R, archive_path: &str, matches: F) -> Result<Vec<Result<ArchiveMember>>>
+where
+  R: Read,
+  F: Fn(&str) -> bool,
+{
+  let mut archive = tar::Archive::new(reader);
+  let mut members = vec![];
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    if !entry.header().entry_type().is_file() {
+      continue;
+    }
+    let name = member_name(&String::from_utf8_lossy(&entry.path_bytes()));
+    if !matches(&name) {
+      continue;
+    }
+    let header = entry.header();
+    let unix_time = |time: u64| DateTime::from_timestamp(time as i64, 0);
+    let mut times = MemberTimes {
+      modified: header.mtime().ok().and_then(unix_time),
+      accessed: header.as_gnu().and_then(|gnu| gnu.atime().ok()).and_then(unix_time),
+      created: None,
+    };
+    // PAX records have sub second precision, `ctime` is the status change time so only the libarchive creation
+    // time is used for `created`.
+    if let Some(extensions) = entry.pax_extensions()? {
+      for extension in extensions.flatten() {
+        let time = extension
+          .value()
+          .ok()
+          .and_then(|v| v.parse::<f64>().ok())
+          .and_then(|v| DateTime::from_timestamp(v.floor() as i64, (v.fract() * 1e9) as u32));
+        match extension.key() {
+          Ok("mtime") => times.modified = time.or(times.modified),
+          Ok("atime") => times.accessed = time.or(times.accessed),
+          Ok("LIBARCHIVE.creationtime") => times.created = time,
+          _ => {},
+        }
+      }
+    }
+    let full_path = format!("{}/{}", archive_path, name);
+    let member = member_data(&mut entry, &full_path)?.map(|data| ArchiveMember {
+      metadata: LnkFileMetaData::new(full_path, times.modified, times.accessed, times.created),
+      name,
+      data,
+    });
+    members.push(member);
+  }
+  Ok(members)
+}
@@ -0,0 +1,165 @@
+//! Inputs and outputs of the subcommands that parse the artifacts found next to LNK files (`prefetch`, `shellbags`).
+//! They support the csv, json, jsonl and ecs output formats with the same options as `parse`.
+
+use crate::output::{parse_delimiter, UTF8_BOM};
+use clap::{Arg, ArgMatches};
+use glob::glob;
+use serde_json::Value;
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::PathBuf,
+};
+
+/// A parsed artifact.
+pub trait ArtifactRecord {
+  fn to_json(&self) -> Value;
+  /// The normalized fields, written by the csv output and by `--normalize`.
+  fn normalize(&self) -> HashMap<String, String>;
+  /// The bulk API lines of the ecs output, an event for every timestamp.
+  fn bulk_lines(&self, index: &str) -> Vec<Value>;
+}
+
+/// The output options, `ecs_index` is the default of `--ecs-index`.
+pub fn args(ecs_index: &'static str) -> Vec<Arg<'static, 'static>> {
+  vec![
+    Arg::with_name("output")
+      .short("-o")
+      .long("--output")
+      .default_value("stdout")
+      .takes_value(true)
+      .help("The file path to write the output to"),
+    Arg::with_name("output-format")
+      .long("--output-format")
+      .takes_value(true)
+      .possible_values(&["csv", "jsonl", "json", "ecs"])
+      .default_value("csv")
+      .help(
+        "Output format. 'ecs' writes an Elastic Common Schema event for every timestamp as a bulk API request body",
+      ),
+    Arg::with_name("ecs-index")
+      .long("--ecs-index")
+      .takes_value(true)
+      .value_name("INDEX")
+      .default_value(ecs_index)
+      .help("Index name written in the bulk API action lines of the ecs output format"),
+    Arg::with_name("no-headers")
+      .long("--no-headers")
+      .takes_value(false)
+      .help("Don't print headers when using CSV as the output format"),
+    Arg::with_name("delimiter")
+      .long("--delimiter")
+      .takes_value(true)
+      .value_name("CHAR")
+      .default_value(",")
+      .validator(|d| parse_delimiter(&d).map(|_| ()))
+      .help("Field delimiter when using CSV as the output format ('\\t' for tab)"),
+    Arg::with_name("excel")
+      .long("--excel")
+      .takes_value(false)
+      .help("Start the CSV output with a UTF-8 BOM so Excel opens it with the right encoding"),
+    Arg::with_name("normalize")
+      .long("--normalize")
+      .takes_value(false)
+      .help("Normalize the result to the most important fields"),
+  ]
+}
+
+/// The files matching `patterns`, in the order of the patterns.
+pub fn glob_paths<S: AsRef<str>>(patterns: &[S]) -> Vec<PathBuf> {
+  let mut paths = vec![];
+  for pattern in patterns {
+    match glob(pattern.as_ref()) {
+      Ok(entries) => paths.extend(entries.flatten()),
+      Err(e) => eprintln!("Invalid path pattern '{}'. ERROR : '{}'", pattern.as_ref(), e),
+    }
+  }
+  paths
+}
+
+enum Output {
+  Json(Box<dyn Write>, usize),
+  Jsonl(Box<dyn Write>),
+  Csv(Box<csv::Writer<Box<dyn Write>>>, &'static [&'static str]),
+  Ecs(Box<dyn Write>, String),
+}
+
+/// Writes the artifacts in the format selected by the output options.
+pub struct ArtifactWriter {
+  output: Output,
+  normalize: bool,
+}
+
+impl ArtifactWriter {
+  /// Open the output, `columns` are the normalized fields written by the csv output.
+  pub fn open(args: &ArgMatches, columns: &'static [&'static str]) -> io::Result<Self> {
+    let mut output: Box<dyn Write> = match args.value_of("output").unwrap() {
+      "stdout" => Box::new(BufWriter::new(io::stdout())),
+      path => Box::new(BufWriter::new(File::create(path)?)),
+    };
+    let output = match args.value_of("output-format").unwrap() {
+      "json" => Output::Json(output, 0),
+      "jsonl" => Output::Jsonl(output),
+      "ecs" => Output::Ecs(output, args.value_of("ecs-index").unwrap().to_string()),
+      _ => {
+        if args.is_present("excel") {
+          output.write_all(UTF8_BOM)?;
+        }
+        let mut csv_writer = csv::WriterBuilder::new()
+          .delimiter(parse_delimiter(args.value_of("delimiter").unwrap()).unwrap())
+          .quote_style(csv::QuoteStyle::Always)
+          .terminator(csv::Terminator::CRLF)
+          .from_writer(output);
+        if !args.is_present("no-headers") {
+          csv_writer.write_record(columns)?;
+        }
+        Output::Csv(Box::new(csv_writer), columns)
+      },
+    };
+    Ok(Self {
+      output,
+      normalize: args.is_present("normalize"),
+    })
+  }
+
+  pub fn write(&mut self, record: &impl ArtifactRecord) -> io::Result<()> {
+    let json = || match self.normalize {
+      true => serde_json::to_string(&record.normalize()).unwrap(),
+      false => record.to_json().to_string(),
+    };
+    match &mut self.output {
+      Output::Json(output, records) => {
+        output.write_all(if *records == 0 { b"[" } else { b"," })?;
+        output.write_all(json().as_bytes())?;
+        *records += 1;
+      },
+      Output::Jsonl(output) => {
+        output.write_all(json().as_bytes())?;
+        output.write_all(b"\r\n")?;
+      },
+      Output::Csv(csv_writer, columns) => {
+        let fields = record.normalize();
+        csv_writer.write_record(columns.iter().map(|column| &fields[*column]))?;
+      },
+      Output::Ecs(output, index) => {
+        for line in record.bulk_lines(index) {
+          output.write_all(line.to_string().as_bytes())?;
+          output.write_all(b"\n")?;
+        }
+      },
+    }
+    Ok(())
+  }
+
+  pub fn finish(&mut self) -> io::Result<()> {
+    match &mut self.output {
+      Output::Json(output, records) => {
+        output.write_all(if *records == 0 { b"[]" } else { b"]" })?;
+        output.flush()
+      },
+      Output::Jsonl(output) | Output::Ecs(output, _) => output.flush(),
+      Output::Csv(csv_writer, _) => csv_writer.flush(),
+    }
+  }
+}
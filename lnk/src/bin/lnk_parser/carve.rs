@@ -0,0 +1,71 @@
+use crate::{
+  error::CliError,
+  output::{self, Record},
+};
+use chrono::{DateTime, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{carve, LnkFileMetaData};
+use std::{
+  fs::{self, File},
+  io::BufReader,
+  path::Path,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("carve")
+    .about("Carve LNK files out of a disk image, unallocated space or any other data")
+    .arg(Arg::with_name("IMAGE").required(true).help("The file to scan"))
+    .arg(
+      Arg::with_name("output-dir")
+        .long("--output-dir")
+        .takes_value(true)
+        .value_name("DIR")
+        .help("Also write every carved LNK file to DIR, named after its offset (0x<offset>.lnk)"),
+    )
+    .args(&output::args())
+}
+
+/// Scan the image and write a record for every carved LNK file, `lnk_full_path` is `<image>@0x<offset>`.
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let image = args.value_of("IMAGE").unwrap();
+  let output_dir = args.value_of("output-dir");
+  let mut writer = output::open_writer(args)?;
+
+  let metadata = fs::metadata(image)?;
+  let time = |t: std::io::Result<std::time::SystemTime>| t.map(DateTime::<Utc>::from).ok();
+  let (modified, accessed, created) = (time(metadata.modified()), time(metadata.accessed()), time(metadata.created()));
+  if let Some(dir) = output_dir {
+    fs::create_dir_all(dir)?;
+  }
+
+  let mut count = 0;
+  let mut result = Ok(());
+  carve::carve(BufReader::new(File::open(image)?), |carved| {
+    if result.is_err() {
+      return;
+    }
+    count += 1;
+    if let Some(dir) = output_dir {
+      result = fs::write(Path::new(dir).join(format!("0x{:X}.lnk", carved.offset)), &carved.data);
+      if result.is_err() {
+        return;
+      }
+    }
+    let mut lnk = carved.lnk;
+    lnk.lnk_file_metadata =
+      Some(LnkFileMetaData::new(format!("{}@0x{:X}", image, carved.offset), modified, accessed, created));
+    result = writer.write_record(&Record {
+      lnk,
+      data: carved.data,
+      entry: None,
+    });
+  })?;
+  result?;
+  writer.finish()?;
+
+  if count == 0 {
+    Err(CliError::NoFiles)
+  } else {
+    Ok(())
+  }
+}
@@ -0,0 +1,126 @@
+use crate::error::CliError;
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
+use lnk::writer::{self, LnkSpec};
+use std::{
+  fs,
+  io::{self, ErrorKind, Read},
+};
+
+pub fn create_subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("create")
+    .about("Create a LNK file from a JSON or TOML specification using the normalized field names")
+    .arg(
+      Arg::with_name("SPEC")
+        .required(true)
+        .help("The specification file ('-' reads it from stdin)"),
+    )
+    .arg(
+      Arg::with_name("output")
+        .short("-o")
+        .long("--output")
+        .takes_value(true)
+        .required(true)
+        .help("The LNK file to write"),
+    )
+    .arg(
+      Arg::with_name("spec-format")
+        .long("--spec-format")
+        .takes_value(true)
+        .possible_values(&["json", "toml"])
+        .help("Format of the specification (Defaults to the file extension, JSON for stdin)"),
+    )
+}
+
+pub fn edit_subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("edit")
+    .about("Change the target or the arguments of a LNK file and write the result to a new file")
+    .arg(Arg::with_name("LNK").required(true).help("The LNK file to edit"))
+    .arg(
+      Arg::with_name("output")
+        .short("-o")
+        .long("--output")
+        .takes_value(true)
+        .required(true)
+        .help("The LNK file to write, the input file is never modified"),
+    )
+    .arg(
+      Arg::with_name("target")
+        .long("--target")
+        .takes_value(true)
+        .help("The new target path (local or UNC), target specific data of the old target is removed"),
+    )
+    .arg(
+      Arg::with_name("arguments")
+        .long("--arguments")
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .help("The new command line arguments"),
+    )
+    .arg(
+      Arg::with_name("remove-arguments")
+        .long("--remove-arguments")
+        .conflicts_with("arguments")
+        .help("Remove the command line arguments"),
+    )
+    .group(
+      ArgGroup::with_name("changes")
+        .args(&["target", "arguments", "remove-arguments"])
+        .multiple(true)
+        .required(true),
+    )
+}
+
+/// Errors from the LNK writer are caused by the input, not by the file system.
+fn writer_error(e: io::Error) -> CliError {
+  match e.kind() {
+    ErrorKind::InvalidInput => CliError::Usage(e.to_string()),
+    _ => CliError::Io(e),
+  }
+}
+
+pub fn run_create(args: &ArgMatches) -> Result<(), CliError> {
+  let path = args.value_of("SPEC").unwrap();
+  let spec_data = match path {
+    "-" => {
+      let mut data = String::new();
+      io::stdin().read_to_string(&mut data)?;
+      data
+    },
+    _ => fs::read_to_string(path)?,
+  };
+  let toml = match args.value_of("spec-format") {
+    Some(format) => format == "toml",
+    None => path.to_lowercase().ends_with(".toml"),
+  };
+  let spec: LnkSpec = if toml {
+    toml::from_str(&spec_data).map_err(|e| CliError::Usage(format!("Invalid specification '{}': {}", path, e)))?
+  } else {
+    serde_json::from_str(&spec_data).map_err(|e| CliError::Usage(format!("Invalid specification '{}': {}", path, e)))?
+  };
+  let data = spec.to_bytes().map_err(writer_error)?;
+  fs::write(args.value_of("output").unwrap(), data)?;
+  Ok(())
+}
+
+pub fn run_edit(args: &ArgMatches) -> Result<(), CliError> {
+  let path = args.value_of("LNK").unwrap();
+  let mut data = fs::read(path)?;
+  let edit_error = |e: io::Error| match e.kind() {
+    ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+      eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path, e);
+      CliError::ParseFailed(1)
+    },
+    _ => writer_error(e),
+  };
+  if let Some(target) = args.value_of("target") {
+    data = writer::set_target(&data, target).map_err(edit_error)?;
+  }
+  if let Some(arguments) = args.value_of("arguments") {
+    data = writer::set_arguments(&data, Some(arguments)).map_err(edit_error)?;
+  }
+  if args.is_present("remove-arguments") {
+    data = writer::set_arguments(&data, None).map_err(edit_error)?;
+  }
+  fs::write(args.value_of("output").unwrap(), data)?;
+  Ok(())
+}
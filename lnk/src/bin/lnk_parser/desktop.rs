@@ -0,0 +1,116 @@
+use crate::error::CliError;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{
+  desktop::{DesktopEntry, MountTable},
+  Lnk,
+};
+use std::{
+  fs,
+  io::{self, ErrorKind, Write},
+};
+
+fn mount_args(subcommand: App<'static, 'static>) -> App<'static, 'static> {
+  subcommand
+    .arg(
+      Arg::with_name("mount")
+        .long("--mount")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("PREFIX=DIR")
+        .help("Map a drive letter (C:) or a UNC share (\\\\server\\share) to the directory it is mounted on"),
+    )
+    .arg(
+      Arg::with_name("mounts")
+        .long("--mounts")
+        .takes_value(true)
+        .value_name("FILE")
+        .help("TOML table of drive letters and UNC shares and their mount points ('C:' = '/mnt/c')"),
+    )
+    .after_help(
+      "Paths that are not on a mapped drive or share are kept in the arguments and dropped from the working \
+       directory and the icon, the target has to be mapped.",
+    )
+}
+
+pub fn to_desktop_subcommand() -> App<'static, 'static> {
+  mount_args(
+    SubCommand::with_name("to-desktop")
+      .about("Convert a LNK file to a freedesktop.org .desktop entry")
+      .arg(Arg::with_name("LNK").required(true).help("The LNK file to convert"))
+      .arg(
+        Arg::with_name("output")
+          .short("-o")
+          .long("--output")
+          .takes_value(true)
+          .help("The .desktop file to write (Defaults to stdout)"),
+      ),
+  )
+}
+
+pub fn from_desktop_subcommand() -> App<'static, 'static> {
+  mount_args(
+    SubCommand::with_name("from-desktop")
+      .about("Create a LNK file from a freedesktop.org .desktop entry")
+      .arg(Arg::with_name("DESKTOP").required(true).help("The .desktop file to convert"))
+      .arg(
+        Arg::with_name("output")
+          .short("-o")
+          .long("--output")
+          .takes_value(true)
+          .required(true)
+          .help("The LNK file to write"),
+      ),
+  )
+}
+
+fn mounts(args: &ArgMatches) -> Result<MountTable, CliError> {
+  let mut mounts = match args.value_of("mounts") {
+    Some(path) => MountTable::from_toml(&fs::read_to_string(path)?).map_err(|e| CliError::Usage(e.to_string()))?,
+    None => MountTable::new(),
+  };
+  for mount in args.values_of("mount").into_iter().flatten() {
+    let (prefix, dir) = mount
+      .split_once('=')
+      .ok_or_else(|| CliError::Usage(format!("invalid mount '{}', expected PREFIX=DIR", mount)))?;
+    mounts = mounts.mount(prefix, dir).map_err(|e| CliError::Usage(e.to_string()))?;
+  }
+  Ok(mounts)
+}
+
+/// Conversion errors are caused by the input, not by the file system.
+fn conversion_error(e: io::Error) -> CliError {
+  match e.kind() {
+    ErrorKind::InvalidInput => CliError::Usage(e.to_string()),
+    _ => CliError::Io(e),
+  }
+}
+
+pub fn run_to_desktop(args: &ArgMatches) -> Result<(), CliError> {
+  let mounts = mounts(args)?;
+  let path = args.value_of("LNK").unwrap();
+  let lnk = Lnk::from_path(path).map_err(|e| match e.kind() {
+    ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+      eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path, e);
+      CliError::ParseFailed(1)
+    },
+    _ => CliError::Io(e),
+  })?;
+  let entry = DesktopEntry::from_lnk(&lnk, &mounts).map_err(conversion_error)?;
+  match args.value_of("output") {
+    Some(output) => fs::write(output, entry.to_string())?,
+    None => io::stdout().write_all(entry.to_string().as_bytes())?,
+  }
+  Ok(())
+}
+
+pub fn run_from_desktop(args: &ArgMatches) -> Result<(), CliError> {
+  let mounts = mounts(args)?;
+  let path = args.value_of("DESKTOP").unwrap();
+  let entry = DesktopEntry::parse(&fs::read_to_string(path)?)
+    .map_err(|e| CliError::Usage(format!("Invalid desktop entry '{}': {}", path, e)))?;
+  let spec = entry.to_lnk_spec(&mounts).map_err(conversion_error)?;
+  let data = spec.to_bytes().map_err(conversion_error)?;
+  fs::write(args.value_of("output").unwrap(), data)?;
+  Ok(())
+}
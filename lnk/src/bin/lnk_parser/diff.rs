@@ -0,0 +1,69 @@
+use crate::error::CliError;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{Lnk, NORMALIZED_FIELDS};
+use serde_json::json;
+use winparsingtools::traits::Normalize;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("diff")
+    .about("Compare the normalized fields of two LNK files, exits with 1 when they are different")
+    .arg(Arg::with_name("A").required(true).help("The first LNK file"))
+    .arg(Arg::with_name("B").required(true).help("The second LNK file"))
+    .arg(
+      Arg::with_name("all")
+        .long("--all")
+        .help("Also compare the lnk_* fields, they come from the file system and not from the LNK data"),
+    )
+    .arg(
+      Arg::with_name("output-format")
+        .long("--output-format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .help("Format of the differences"),
+    )
+}
+
+fn parse(path: &str) -> Result<Lnk, CliError> {
+  Lnk::from_path(path).map_err(|e| {
+    eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path, e);
+    CliError::ParseFailed(1)
+  })
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let a = parse(args.value_of("A").unwrap())?.normalize();
+  let b = parse(args.value_of("B").unwrap())?.normalize();
+  let all = args.is_present("all");
+
+  let differences: Vec<(&str, &str, &str)> = NORMALIZED_FIELDS
+    .iter()
+    .filter(|field| all || !field.starts_with("lnk_"))
+    .filter_map(|&field| {
+      let value_a = a.get(field).map(String::as_str).unwrap_or_default();
+      let value_b = b.get(field).map(String::as_str).unwrap_or_default();
+      (value_a != value_b).then_some((field, value_a, value_b))
+    })
+    .collect();
+
+  match args.value_of("output-format").unwrap() {
+    "json" => {
+      let differences: Vec<_> = differences
+        .iter()
+        .map(|(field, a, b)| json!({"field": field, "a": a, "b": b}))
+        .collect();
+      println!("{}", serde_json::to_string(&differences).unwrap());
+    },
+    _ => {
+      for (field, a, b) in &differences {
+        println!("{}: {:?} -> {:?}", field, a, b);
+      }
+    },
+  }
+
+  if differences.is_empty() {
+    Ok(())
+  } else {
+    Err(CliError::Different)
+  }
+}
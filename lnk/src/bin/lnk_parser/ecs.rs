@@ -0,0 +1,256 @@
+//! [Elastic Common Schema](https://www.elastic.co/guide/en/ecs/current/index.html) mapping of the parsed records.
+//!
+//! Every timestamp of a record becomes its own event, the output is the NDJSON body of a `_bulk` request where
+//! each document is preceded by its `index` action.
+
+use crate::output::{Record, EMPTY_TIME};
+use lnk::{prefetch::Prefetch, shellbags::ShellBag};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use winparsingtools::traits::Normalize;
+
+/// The normalized time fields and the `event.action` of the event they produce.
+const EVENTS: &[(&str, &str)] = &[
+  ("target_creation_time", "lnk-target-created"),
+  ("target_modification_time", "lnk-target-modified"),
+  ("target_access_time", "lnk-target-accessed"),
+  ("lnk_creation_time", "lnk-file-created"),
+  ("lnk_modification_time", "lnk-file-modified"),
+  ("lnk_access_time", "lnk-file-accessed"),
+];
+
+/// Normalized fields that have no ECS equivalent, they are kept under the `lnk` field set.
+const LNK_FIELDS: &[&str] = &[
+  "drive_type",
+  "volume_serial_number",
+  "volume_label",
+  "net_name",
+  "device_name",
+  "name_string",
+  "relative_path",
+  "icon_location",
+  "file_droid",
+  "file_droid_birth",
+  "volume_droid",
+  "volume_droid_birth",
+];
+
+/// Returns the bulk API lines (action, document, action, document, ...) of the record.
+pub fn bulk_lines(record: &Record, index: &str) -> Vec<Value> {
+  let fields = record.lnk.normalize();
+  let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+  let sha256 = record.sha256();
+  // The document IDs are derived from the LNK data and where it was read from, so loading the same file twice does
+  // not duplicate events and copies of a shortcut at different paths or jump list entries stay distinct.
+  let id = Sha256::new()
+    .chain_update(&sha256)
+    .chain_update(field("lnk_full_path"))
+    .chain_update([0])
+    .chain_update(record.entry.as_deref().unwrap_or_default())
+    .finalize();
+  let id: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+
+  let mut document = Map::new();
+  let mut file = json!({
+    "path": field("lnk_full_path"),
+    "name": field("lnk_full_path").rsplit(['\\', '/']).next().unwrap_or_default(),
+    "hash": { "md5": record.lnk.hashes.md5, "sha1": record.lnk.hashes.sha1, "sha256": sha256 },
+    "size": record.data.len(),
+  });
+  if !field("target_full_path").is_empty() {
+    file["target_path"] = json!(field("target_full_path"));
+  }
+  for (name, ecs_name) in [
+    ("lnk_creation_time", "created"),
+    ("lnk_modification_time", "mtime"),
+    ("lnk_access_time", "accessed"),
+  ] {
+    if !field(name).is_empty() {
+      file[ecs_name] = json!(field(name));
+    }
+  }
+  document.insert("file".to_string(), file);
+
+  if !field("command_line").is_empty() {
+    let argv: Vec<String> = serde_json::from_str(field("argv")).unwrap_or_default();
+    let mut process = json!({
+      "executable": argv.first().cloned().unwrap_or_default(),
+      "args": argv,
+      "command_line": field("command_line"),
+    });
+    if !field("working_dir").is_empty() {
+      process["working_directory"] = json!(field("working_dir"));
+    }
+    document.insert("process".to_string(), process);
+  }
+  if !field("target_hostname").is_empty() {
+    document.insert("host".to_string(), json!({ "name": field("target_hostname") }));
+  }
+  if !field("user").is_empty() {
+    document.insert("user".to_string(), json!({ "name": field("user") }));
+  }
+  let rule_matches = record.lnk.rule_matches.as_deref().unwrap_or_default();
+  if !rule_matches.is_empty() {
+    document.insert(
+      "rule".to_string(),
+      json!({
+        "id": rule_matches.iter().map(|m| &m.id).collect::<Vec<_>>(),
+        "name": rule_matches.iter().map(|m| &m.title).collect::<Vec<_>>(),
+      }),
+    );
+    let mut tags: Vec<&String> = rule_matches.iter().flat_map(|m| &m.tags).collect();
+    tags.sort();
+    tags.dedup();
+    document.insert("tags".to_string(), json!(tags));
+  }
+  let lnk: Map<String, Value> = LNK_FIELDS
+    .iter()
+    .filter(|name| !field(name).is_empty())
+    .map(|name| (name.to_string(), json!(field(name))))
+    .collect();
+  document.insert("lnk".to_string(), Value::Object(lnk));
+  document.insert("ecs".to_string(), json!({ "version": "8.11.0" }));
+
+  let mut lines = vec![];
+  for (time_field, action) in EVENTS {
+    let timestamp = field(time_field);
+    if timestamp.is_empty() || timestamp == EMPTY_TIME {
+      continue;
+    }
+    let mut event = document.clone();
+    event.insert("@timestamp".to_string(), json!(timestamp));
+    event.insert(
+      "event".to_string(),
+      json!({
+        // Records that matched a detection rule (--rules) are alerts.
+        "kind": if rule_matches.is_empty() { "event" } else { "alert" },
+        "category": ["file"],
+        "type": ["info"],
+        "action": action,
+        "dataset": "lnk",
+        "module": "lnk_parser",
+      }),
+    );
+    lines.push(json!({ "index": { "_index": index, "_id": format!("{}-{}", id, action) } }));
+    lines.push(Value::Object(event));
+  }
+  lines
+}
+
+/// Normalized prefetch fields that have no ECS equivalent, they are kept under the `prefetch` field set.
+const PREFETCH_FIELDS: &[&str] = &[
+  "version",
+  "prefetch_hash",
+  "run_count",
+  "volume_device_paths",
+  "volume_serial_numbers",
+];
+
+/// Returns the bulk API lines of a prefetch file, an event for every run time it recorded.
+pub fn prefetch_bulk_lines(prefetch: &Prefetch, sha256: &str, size: usize, index: &str) -> Vec<Value> {
+  let fields = prefetch.normalize();
+  let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+
+  let mut document = Map::new();
+  document.insert(
+    "file".to_string(),
+    json!({
+      "path": field("pf_full_path"),
+      "name": field("pf_full_path").rsplit(['\\', '/']).next().unwrap_or_default(),
+      "hash": { "sha256": sha256 },
+      "size": size,
+    }),
+  );
+  let mut process = json!({ "name": field("executable_name") });
+  if !field("executable_path").is_empty() {
+    process["executable"] = json!(field("executable_path"));
+  }
+  document.insert("process".to_string(), process);
+  let prefetch_fields: Map<String, Value> = PREFETCH_FIELDS
+    .iter()
+    .filter(|name| !field(name).is_empty())
+    .map(|name| (name.to_string(), json!(field(name))))
+    .collect();
+  document.insert("prefetch".to_string(), Value::Object(prefetch_fields));
+  document.insert("ecs".to_string(), json!({ "version": "8.11.0" }));
+
+  let mut lines = vec![];
+  for (i, timestamp) in prefetch.last_run_times.iter().enumerate() {
+    let mut event = document.clone();
+    event.insert("@timestamp".to_string(), json!(timestamp.to_string()));
+    event.insert(
+      "event".to_string(),
+      json!({
+        "kind": "event",
+        "category": ["process"],
+        "type": ["start"],
+        "action": "prefetch-executed",
+        "dataset": "prefetch",
+        "module": "lnk_parser",
+      }),
+    );
+    // Run times are ordered from the most recent, the position keeps the IDs of the runs apart.
+    lines.push(json!({ "index": { "_index": index, "_id": format!("{}-prefetch-executed-{}", sha256, i) } }));
+    lines.push(Value::Object(event));
+  }
+  lines
+}
+
+/// The normalized ShellBag time fields and the `event.action` of the event they produce.
+const SHELLBAG_EVENTS: &[(&str, &str)] = &[
+  ("last_interacted", "shellbag-folder-interacted"),
+  ("key_last_written", "shellbag-key-modified"),
+  ("item_creation_time", "shellbag-folder-created"),
+  ("item_modification_time", "shellbag-folder-modified"),
+  ("item_access_time", "shellbag-folder-accessed"),
+];
+
+/// DOS date 0 is formatted as this date, it means the time is not set.
+const EMPTY_DOS_TIME: &str = "1980-01-01T00:00:00Z";
+
+/// Normalized ShellBag fields that have no ECS equivalent, they are kept under the `shellbag` field set.
+const SHELLBAG_FIELDS: &[&str] = &["hive_path", "item_type", "mru_position", "node_slot"];
+
+/// Returns the bulk API lines of a ShellBag, `id` identifies the folder key in the hive it was read from.
+pub fn shellbag_bulk_lines(shell_bag: &ShellBag, id: &str, index: &str) -> Vec<Value> {
+  let fields = shell_bag.normalize();
+  let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+
+  let mut document = Map::new();
+  document.insert("file".to_string(), json!({ "path": field("path"), "type": "dir" }));
+  document.insert("registry".to_string(), json!({ "key": field("key_path") }));
+  if !field("user").is_empty() {
+    document.insert("user".to_string(), json!({ "name": field("user") }));
+  }
+  let shellbag_fields: Map<String, Value> = SHELLBAG_FIELDS
+    .iter()
+    .filter(|name| !field(name).is_empty())
+    .map(|name| (name.to_string(), json!(field(name))))
+    .collect();
+  document.insert("shellbag".to_string(), Value::Object(shellbag_fields));
+  document.insert("ecs".to_string(), json!({ "version": "8.11.0" }));
+
+  let mut lines = vec![];
+  for (time_field, action) in SHELLBAG_EVENTS {
+    let timestamp = field(time_field);
+    if timestamp.is_empty() || timestamp == EMPTY_TIME || timestamp == EMPTY_DOS_TIME {
+      continue;
+    }
+    let mut event = document.clone();
+    event.insert("@timestamp".to_string(), json!(timestamp));
+    event.insert(
+      "event".to_string(),
+      json!({
+        "kind": "event",
+        "category": ["file"],
+        "type": ["info"],
+        "action": action,
+        "dataset": "shellbags",
+        "module": "lnk_parser",
+      }),
+    );
+    lines.push(json!({ "index": { "_index": index, "_id": format!("{}-{}", id, action) } }));
+    lines.push(Value::Object(event));
+  }
+  lines
+}
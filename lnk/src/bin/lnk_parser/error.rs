@@ -0,0 +1,96 @@
+use std::{
+  any::Any,
+  fmt, io,
+  panic::{self, AssertUnwindSafe},
+};
+
+/// Exit codes of `lnk_parser` other than 0 (success), scripts can rely on them.
+pub mod exit_code {
+  /// `diff` found differences.
+  pub const DIFFERENT: i32 = 1;
+  /// Invalid command line arguments or input specification.
+  pub const USAGE: i32 = 2;
+  /// No input file matched the given paths.
+  pub const NO_FILES: i32 = 3;
+  /// At least one input file could not be parsed, the others were still processed.
+  pub const PARSE_FAILED: i32 = 4;
+  /// Reading an input or writing the output failed.
+  pub const IO: i32 = 5;
+  /// `validate` found files that parse but deviate from the specification.
+  pub const INVALID: i32 = 6;
+  /// `rules --test` found rules that do not give the expected result on their test files.
+  pub const RULE_TESTS_FAILED: i32 = 7;
+}
+
+#[derive(Debug)]
+pub enum CliError {
+  Usage(String),
+  NoFiles,
+  /// The number of files that failed to parse, each failure was already reported.
+  ParseFailed(usize),
+  Io(io::Error),
+  /// The number of files that could not be read, each failure was already reported.
+  ReadFailed(usize),
+  Different,
+  /// The number of files that deviate from the specification, each deviation was already reported.
+  Invalid(usize),
+  /// The number of failed rule tests, each failure was already reported.
+  RuleTestsFailed(usize),
+}
+
+impl CliError {
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      CliError::Usage(_) => exit_code::USAGE,
+      CliError::NoFiles => exit_code::NO_FILES,
+      CliError::ParseFailed(_) => exit_code::PARSE_FAILED,
+      CliError::Io(_) | CliError::ReadFailed(_) => exit_code::IO,
+      CliError::Different => exit_code::DIFFERENT,
+      CliError::Invalid(_) => exit_code::INVALID,
+      CliError::RuleTestsFailed(_) => exit_code::RULE_TESTS_FAILED,
+    }
+  }
+}
+
+impl fmt::Display for CliError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CliError::Usage(message) => write!(f, "{}", message),
+      CliError::NoFiles => write!(f, "No files found"),
+      CliError::ParseFailed(count) => write!(f, "{} file(s) could not be parsed", count),
+      CliError::Io(e) => write!(f, "I/O error: {}", e),
+      CliError::ReadFailed(count) => write!(f, "{} file(s) could not be read", count),
+      CliError::Different => write!(f, "The files are different"),
+      CliError::Invalid(count) => write!(f, "{} file(s) deviate from the specification", count),
+      CliError::RuleTestsFailed(count) => write!(f, "{} rule test(s) failed", count),
+    }
+  }
+}
+
+/// Whether the error of an input comes from the file system (missing file, denied access, directory) rather than from
+/// its content.
+pub fn is_read_error(e: &io::Error) -> bool {
+  matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied | io::ErrorKind::IsADirectory)
+}
+
+/// Run the parser of an input, a panic on a malformed file is returned as an error so the other inputs are still
+/// processed.
+pub fn catch_panic<T>(parse: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+  panic::catch_unwind(AssertUnwindSafe(parse))
+    .unwrap_or_else(|payload| Err(io::Error::other(format!("the parser panicked: {}", panic_message(&*payload)))))
+}
+
+/// The message given to `panic!`.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+  match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+    (Some(message), _) => message.to_string(),
+    (_, Some(message)) => message.to_owned(),
+    _ => String::from("unknown panic"),
+  }
+}
+
+impl From<io::Error> for CliError {
+  fn from(e: io::Error) -> Self {
+    CliError::Io(e)
+  }
+}
@@ -0,0 +1,163 @@
+use crate::{
+  error::{self, CliError},
+  parse,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::index::{Index, Query};
+use std::io;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("find")
+    .about("Find the shortcuts pointing at a target, volume, machine, droid or MFT entry")
+    .after_help(
+      "Build the index of a collection once with -p/--root and --index, then query the index without -p/--root:\n    \
+       lnk_parser find --root /mnt/c --index c.idx\n    \
+       lnk_parser find --index c.idx --target-prefix '\\\\fileserver\\finance\\'\n    \
+       lnk_parser find --index c.idx --volume-serial 1234-ABCD",
+    )
+    .arg(
+      Arg::with_name("PATH")
+        .short("-p")
+        .long("--path")
+        .takes_value(true)
+        .multiple(true)
+        .value_name("PATH")
+        .help("Path(s) to LNK Metadata Files to be indexed - accepts glob, can point into .zip/.tar/.tar.gz archives"),
+    )
+    .arg(
+      Arg::with_name("root")
+        .long("--root")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with("PATH")
+        .help("Mount point of a Windows volume, index the LNK files and jump lists of every user profile under it"),
+    )
+    .arg(
+      Arg::with_name("index")
+        .long("--index")
+        .takes_value(true)
+        .value_name("FILE")
+        .required_unless_one(&["PATH", "root"])
+        .help("Index file, written when -p/--root is given and read otherwise"),
+    )
+    .arg(
+      Arg::with_name("target")
+        .long("--target")
+        .takes_value(true)
+        .value_name("PATH")
+        .help("Target path, compared case-insensitively with '/' and '\\' as the same separator"),
+    )
+    .arg(
+      Arg::with_name("target-prefix")
+        .long("--target-prefix")
+        .takes_value(true)
+        .value_name("PREFIX")
+        .help("Start of the target path, e.g. a folder or a share"),
+    )
+    .arg(
+      Arg::with_name("volume-serial")
+        .long("--volume-serial")
+        .takes_value(true)
+        .value_name("SERIAL")
+        .help("Volume serial number (1234-ABCD)"),
+    )
+    .arg(
+      Arg::with_name("machine-id")
+        .long("--machine-id")
+        .takes_value(true)
+        .value_name("NAME")
+        .help("NetBIOS name of the machine from the TrackerDataBlock"),
+    )
+    .arg(
+      Arg::with_name("droid")
+        .long("--droid")
+        .takes_value(true)
+        .value_name("GUID")
+        .help("Volume or file droid from the TrackerDataBlock, current or birth"),
+    )
+    .arg(
+      Arg::with_name("mft")
+        .long("--mft")
+        .takes_value(true)
+        .value_name("ENTRY[-SEQUENCE]")
+        .validator(|v| Query::mft_reference(&v).map(|_| ()).map_err(|e| e.to_string()))
+        .help("MFT entry of the target, and its sequence number"),
+    )
+    .arg(
+      Arg::with_name("output-format")
+        .long("--output-format")
+        .takes_value(true)
+        .possible_values(&["text", "jsonl"])
+        .default_value("text")
+        .help("'text' prints 'LNK -> target' lines, 'jsonl' prints the index entries"),
+    )
+}
+
+/// The queries given on the command line, a shortcut has to match all of them.
+fn queries(args: &ArgMatches) -> Vec<Query> {
+  let mut queries = vec![];
+  let value = |name: &str| args.value_of(name).map(str::to_string);
+  queries.extend(value("target").map(Query::Target));
+  queries.extend(value("target-prefix").map(Query::TargetPrefix));
+  queries.extend(value("volume-serial").map(Query::VolumeSerial));
+  queries.extend(value("machine-id").map(Query::MachineId));
+  queries.extend(value("droid").map(Query::Droid));
+  queries.extend(args.value_of("mft").map(|mft| Query::mft_reference(mft).unwrap()));
+  queries
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let queries = queries(args);
+  let mut failed = 0;
+  let mut unreadable = 0;
+  let build = args.is_present("PATH") || args.is_present("root");
+  let index = if build {
+    let inputs = parse::inputs(args);
+    if inputs.is_empty() {
+      return Err(CliError::NoFiles);
+    }
+    let mut index = Index::new();
+    for input in &inputs {
+      match parse::parse_artifact(input) {
+        Ok(records) => records.iter().for_each(|record| index.add(&record.lnk)),
+        Err(e) if error::is_read_error(&e) => {
+          unreadable += 1;
+          eprintln!("Could not read '{}'. ERROR : '{}'", input.artifact.path.display(), e);
+        },
+        Err(e) => {
+          failed += 1;
+          eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e);
+        },
+      }
+    }
+    if let Some(path) = args.value_of("index") {
+      index.save(path)?;
+      eprintln!("Indexed {} shortcut(s) to '{}'", index.entries().len(), path);
+    }
+    index
+  } else {
+    let path = args.value_of("index").unwrap();
+    Index::load(path).map_err(|e| match e.kind() {
+      io::ErrorKind::InvalidData => CliError::Usage(format!("Could not read the index: {}", e)),
+      _ => CliError::Io(e),
+    })?
+  };
+
+  // Building an index file without a query only writes the index, reading one without a query lists it.
+  if !queries.is_empty() || !build || !args.is_present("index") {
+    for entry in index.find(&queries) {
+      match args.value_of("output-format").unwrap() {
+        "jsonl" => println!("{}", serde_json::to_string(entry).unwrap()),
+        _ => println!("{} -> {}", entry.lnk_full_path, entry.target_full_path),
+      }
+    }
+  }
+
+  if unreadable > 0 {
+    Err(CliError::ReadFailed(unreadable))
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else {
+    Ok(())
+  }
+}
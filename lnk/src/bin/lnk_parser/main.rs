@@ -0,0 +1,120 @@
+mod artifact;
+mod carve;
+mod create;
+mod desktop;
+mod diff;
+mod ecs;
+mod error;
+mod find;
+mod output;
+mod parse;
+mod prefetch;
+mod report;
+mod rules;
+mod schema;
+mod shellbags;
+mod sqlite;
+mod stix;
+mod tui;
+mod validate;
+#[cfg(target_os = "linux")]
+mod watch;
+
+use clap::{App, AppSettings};
+use error::{exit_code, CliError};
+use std::{env, ffi::OsString, process};
+
+const SUBCOMMANDS: &[&str] = &[
+  "parse",
+  "create",
+  "edit",
+  "diff",
+  "carve",
+  "validate",
+  "report",
+  "find",
+  "rules",
+  "schema",
+  "to-desktop",
+  "from-desktop",
+  "tui",
+  "prefetch",
+  "shellbags",
+  "help",
+];
+
+fn cli() -> App<'static, 'static> {
+  App::new("lnk_parser")
+    .version(env!("CARGO_PKG_VERSION"))
+    .author("AbdulRhman Alfaifi - @A__ALFAIFI")
+    .about("Windows LNK Files Parser")
+    .after_help(
+      "EXIT CODES:\n    0    success\n    1    diff found differences\n    2    invalid arguments or specification\n    \
+       3    no files found\n    4    at least one file could not be parsed\n    5    I/O error\n    \
+       6    validate found deviations from the specification\n    7    rules --test found failing rule tests",
+    )
+    .setting(AppSettings::SubcommandRequiredElseHelp)
+    .setting(AppSettings::VersionlessSubcommands)
+    .subcommand(parse::subcommand())
+    .subcommand(create::create_subcommand())
+    .subcommand(create::edit_subcommand())
+    .subcommand(diff::subcommand())
+    .subcommand(carve::subcommand())
+    .subcommand(validate::subcommand())
+    .subcommand(report::subcommand())
+    .subcommand(find::subcommand())
+    .subcommand(rules::subcommand())
+    .subcommand(schema::subcommand())
+    .subcommand(desktop::to_desktop_subcommand())
+    .subcommand(desktop::from_desktop_subcommand())
+    .subcommand(tui::subcommand())
+    .subcommand(prefetch::subcommand())
+    .subcommand(shellbags::subcommand())
+}
+
+/// `parse` is the default subcommand, so `lnk_parser -p ...` keeps working.
+fn cli_args() -> Vec<OsString> {
+  let mut args: Vec<OsString> = env::args_os().collect();
+  let first = args.get(1).and_then(|a| a.to_str()).unwrap_or_default();
+  if !SUBCOMMANDS.contains(&first) && !["-h", "--help", "-V", "--version"].contains(&first) {
+    args.insert(1, OsString::from("parse"));
+  }
+  args
+}
+
+fn main() {
+  let matches = match cli().get_matches_from_safe(cli_args()) {
+    Ok(matches) => matches,
+    Err(e) if e.use_stderr() => {
+      eprintln!("{}", e.message);
+      process::exit(exit_code::USAGE);
+    },
+    // --help and --version
+    Err(e) => e.exit(),
+  };
+
+  let result = match matches.subcommand() {
+    ("parse", Some(args)) => parse::run(args),
+    ("create", Some(args)) => create::run_create(args),
+    ("edit", Some(args)) => create::run_edit(args),
+    ("diff", Some(args)) => diff::run(args),
+    ("carve", Some(args)) => carve::run(args),
+    ("validate", Some(args)) => validate::run(args),
+    ("report", Some(args)) => report::run(args),
+    ("find", Some(args)) => find::run(args),
+    ("rules", Some(args)) => rules::run(args),
+    ("schema", Some(args)) => schema::run(args),
+    ("to-desktop", Some(args)) => desktop::run_to_desktop(args),
+    ("from-desktop", Some(args)) => desktop::run_from_desktop(args),
+    ("tui", Some(args)) => tui::run(args),
+    ("prefetch", Some(args)) => prefetch::run(args),
+    ("shellbags", Some(args)) => shellbags::run(args),
+    _ => Err(CliError::Usage(matches.usage().to_string())),
+  };
+  if let Err(e) = result {
+    if !matches!(e, CliError::Different) {
+      eprintln!("{}", e);
+    }
+    process::exit(e.exit_code());
+  }
+}
@@ -0,0 +1,448 @@
+use crate::{
+  ecs,
+  error::{self, CliError},
+  sqlite::SqliteSink,
+  stix,
+};
+use clap::{Arg, ArgMatches};
+use lnk::{
+  redact::Redactor,
+  schema::{Normalized, Versioned},
+  spans::spans,
+  Lnk, DEFAULT_NORMALIZED_FIELDS, NORMALIZED_FIELDS,
+};
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, BufWriter, Write},
+  panic::{self, AssertUnwindSafe},
+};
+use winparsingtools::traits::Normalize;
+
+/// UTF-8 byte order mark, Excel needs it to detect the encoding of a CSV file.
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// FILETIME 0 is formatted as this date, it means the time is not set.
+pub(crate) const EMPTY_TIME: &str = "1601-01-01T00:00:00Z";
+
+pub enum OutputFormat {
+  JSON,
+  JSONL,
+  CSV,
+  SQLITE,
+  ECS,
+  STIX,
+}
+
+impl OutputFormat {
+  pub fn from_str(s: &str) -> OutputFormat {
+    match s {
+      "json" => OutputFormat::JSON,
+      "jsonl" => OutputFormat::JSONL,
+      "csv" => OutputFormat::CSV,
+      "sqlite" => OutputFormat::SQLITE,
+      "ecs" => OutputFormat::ECS,
+      "stix" => OutputFormat::STIX,
+      _ => OutputFormat::CSV,
+    }
+  }
+}
+
+/// Checks that every column passed to `--columns` is a normalized field.
+fn validate_columns(columns: String) -> Result<(), String> {
+  match columns.split(',').find(|c| !NORMALIZED_FIELDS.contains(&c.trim())) {
+    Some(column) => Err(format!("unknown column '{}', valid columns are: {}", column, NORMALIZED_FIELDS.join(","))),
+    None => Ok(()),
+  }
+}
+
+/// Parses the `--delimiter` value, `\t` can be used for tab separated output.
+pub fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+  match delimiter {
+    "\\t" | "tab" => Ok(b'\t'),
+    d if d.len() == 1 => Ok(d.as_bytes()[0]),
+    _ => Err(format!("the delimiter must be a single ASCII character, got '{}'", delimiter)),
+  }
+}
+
+/// The output options shared by the subcommands that write records.
+pub fn args() -> Vec<Arg<'static, 'static>> {
+  vec![
+    Arg::with_name("output")
+      .short("-o")
+      .long("--output")
+      .default_value("stdout")
+      .takes_value(true)
+      .help("The file path to write the output to"),
+    Arg::with_name("output-format")
+      .long("--output-format")
+      .takes_value(true)
+      .possible_values(&["csv", "jsonl", "json", "sqlite", "ecs", "stix"])
+      .default_value("csv")
+      .help("Output format. 'sqlite' writes to the database given by -o/--output and updates records parsed before, 'ecs' writes Elastic Common Schema events as a bulk API request body, 'stix' writes a STIX 2.1 bundle of file, directory and process observables"),
+    Arg::with_name("ecs-index")
+      .long("--ecs-index")
+      .takes_value(true)
+      .value_name("INDEX")
+      .default_value("lnk")
+      .help("Index name written in the bulk API action lines of the ecs output format"),
+    Arg::with_name("no-headers")
+      .long("--no-headers")
+      .takes_value(false)
+      .help("Don't print headers when using CSV as the output format"),
+    Arg::with_name("columns")
+      .long("--columns")
+      .takes_value(true)
+      .value_name("COLUMNS")
+      .validator(validate_columns)
+      .help("Comma separated list of normalized fields to write, in order, when using CSV as the output format (Defaults to the fields selected by --all-fields)"),
+    Arg::with_name("delimiter")
+      .long("--delimiter")
+      .takes_value(true)
+      .value_name("CHAR")
+      .default_value(",")
+      .validator(|d| parse_delimiter(&d).map(|_| ()))
+      .help("Field delimiter when using CSV as the output format ('\\t' for tab)"),
+    Arg::with_name("excel")
+      .long("--excel")
+      .takes_value(false)
+      .help("Start the CSV output with a UTF-8 BOM so Excel opens it with the right encoding"),
+    Arg::with_name("normalize")
+      .long("--normalize")
+      .takes_value(false)
+      .help("Normalize the result to the most important fields"),
+    Arg::with_name("all-fields")
+      .long("--all-fields")
+      .takes_value(false)
+      .help("Write every normalized field (command line, volume, share, droids, rule matches, hashes, ...) as CSV columns and with --normalize, instead of the target and LNK file paths and times, the target hostname and the user"),
+    Arg::with_name("spans")
+      .long("--spans")
+      .takes_value(false)
+      .conflicts_with("normalize")
+      .help("Add the offset and length of the bytes every field was read from to the JSON and JSONL records"),
+    Arg::with_name("redact")
+      .long("--redact")
+      .takes_value(true)
+      .value_name("KEY_FILE")
+      .help("Replace user, host and share names, volume serial numbers and labels and the MAC addresses in droids with pseudonyms keyed by the content of KEY_FILE, the same value always gets the same pseudonym (csv, json and jsonl only)"),
+  ]
+}
+
+/// The redactor of `--redact`.
+fn redactor(args: &ArgMatches) -> Result<Option<Redactor>, CliError> {
+  let Some(key_file) = args.value_of("redact") else {
+    return Ok(None);
+  };
+  if let Some(format @ ("sqlite" | "ecs" | "stix")) = args.value_of("output-format") {
+    return Err(CliError::Usage(format!(
+      "The {} output format can not be redacted, use csv, json or jsonl with --redact",
+      format
+    )));
+  }
+  Ok(Some(Redactor::new(&read_key(key_file)?)))
+}
+
+/// The content of a key file (`--redact`, `--manifest-key`) without the trailing new line.
+pub fn read_key(key_file: &str) -> Result<Vec<u8>, CliError> {
+  let key = fs::read(key_file)?;
+  let key = key.trim_ascii_end();
+  if key.is_empty() {
+    return Err(CliError::Usage(format!("The key file '{}' is empty", key_file)));
+  }
+  Ok(key.to_vec())
+}
+
+/// Open the output selected by the output options.
+pub fn open_writer(args: &ArgMatches) -> Result<RecordWriter, CliError> {
+  let output_format = OutputFormat::from_str(args.value_of("output-format").unwrap());
+  let output_to = args.value_of("output").unwrap();
+  let redactor = redactor(args)?;
+  let normalize = args.occurrences_of("normalize") > 0;
+  let csv_options = CsvOptions {
+    columns: match args.value_of("columns") {
+      Some(columns) => columns.split(',').map(|c| c.trim().to_string()).collect(),
+      None => normalized_fields(args).iter().map(|c| c.to_string()).collect(),
+    },
+    delimiter: parse_delimiter(args.value_of("delimiter").unwrap()).unwrap(),
+    excel: args.occurrences_of("excel") > 0,
+    headers: args.occurrences_of("no-headers") == 0,
+  };
+  match (output_format, output_to) {
+    (OutputFormat::SQLITE, "stdout") => {
+      Err(CliError::Usage(String::from("The sqlite output format needs a database path, use -o/--output")))
+    },
+    (OutputFormat::SQLITE, _) => RecordWriter::sqlite(output_to).map_err(|e| CliError::Io(io::Error::other(e))),
+    (output_format, _) => {
+      let output: Box<dyn Write> = match output_to {
+        "stdout" => Box::new(BufWriter::new(io::stdout())),
+        _ => Box::new(BufWriter::new(File::create(output_to)?)),
+      };
+      Ok(
+        RecordWriter::new(output, output_format, normalize, csv_options)?
+          .ecs_index(args.value_of("ecs-index").unwrap())
+          .spans(args.is_present("spans"))
+          .redactor(redactor)
+          .normalized_fields(normalized_fields(args)),
+      )
+    },
+  }
+}
+
+/// The normalized fields written by default, all of them with `--all-fields`.
+fn normalized_fields(args: &ArgMatches) -> &'static [&'static str] {
+  match args.is_present("all-fields") {
+    true => NORMALIZED_FIELDS,
+    false => DEFAULT_NORMALIZED_FIELDS,
+  }
+}
+
+/// A parsed LNK and the bytes it was parsed from.
+pub struct Record {
+  pub lnk: Lnk,
+  pub data: Vec<u8>,
+  /// The stream name or offset of the entry when the LNK was read from a jump list.
+  pub entry: Option<String>,
+}
+
+impl Record {
+  /// SHA-256 of the LNK data as a lowercase hex string.
+  pub fn sha256(&self) -> String {
+    self.lnk.hashes.sha256.to_owned()
+  }
+}
+
+/// Options of the CSV output.
+pub struct CsvOptions {
+  /// Normalized fields to write, in order.
+  pub columns: Vec<String>,
+  pub delimiter: u8,
+  /// Start the output with a UTF-8 BOM so Excel detects the encoding, rows always end with CRLF (RFC 4180).
+  pub excel: bool,
+  pub headers: bool,
+}
+
+/// Open the output of `--watch`. The records are appended as JSONL (or ECS) lines, so the output can be tailed and a
+/// restarted watcher does not overwrite the records written before.
+pub fn open_append_writer(args: &ArgMatches) -> Result<RecordWriter, CliError> {
+  let redactor = redactor(args)?;
+  let output_format = match args.value_of("output-format").unwrap() {
+    _ if args.occurrences_of("output-format") == 0 => OutputFormat::JSONL,
+    "jsonl" => OutputFormat::JSONL,
+    "ecs" => OutputFormat::ECS,
+    format => {
+      return Err(CliError::Usage(format!(
+        "The {} output format can not be appended to, use jsonl or ecs with --watch",
+        format
+      )))
+    },
+  };
+  let output: Box<dyn Write> = match args.value_of("output").unwrap() {
+    "stdout" => Box::new(BufWriter::new(io::stdout())),
+    path => Box::new(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?)),
+  };
+  let csv_options = CsvOptions {
+    columns: vec![],
+    delimiter: b',',
+    excel: false,
+    headers: false,
+  };
+  Ok(
+    RecordWriter::new(output, output_format, args.occurrences_of("normalize") > 0, csv_options)?
+      .ecs_index(args.value_of("ecs-index").unwrap())
+      .spans(args.is_present("spans"))
+      .redactor(redactor)
+      .normalized_fields(normalized_fields(args)),
+  )
+}
+
+enum Output {
+  Text(Box<dyn Write>),
+  Csv(Box<csv::Writer<Box<dyn Write>>>, Vec<String>),
+  Sqlite(SqliteSink),
+  /// The bundle is written once every record was added, so objects shared by records are only written once.
+  Stix(Box<dyn Write>, stix::Bundle),
+}
+
+/// Writes the parsed records to the output in the selected format.
+pub struct RecordWriter {
+  output: Output,
+  output_format: OutputFormat,
+  normalize: bool,
+  records: usize,
+  ecs_index: String,
+  spans: bool,
+  redactor: Option<Redactor>,
+  normalized_fields: &'static [&'static str],
+}
+
+impl RecordWriter {
+  pub fn new(
+    mut output: Box<dyn Write>,
+    output_format: OutputFormat,
+    normalize: bool,
+    csv_options: CsvOptions,
+  ) -> io::Result<Self> {
+    let output = match output_format {
+      OutputFormat::CSV => {
+        if csv_options.excel {
+          output.write_all(UTF8_BOM)?;
+        }
+        let mut csv_writer = csv::WriterBuilder::new()
+          .delimiter(csv_options.delimiter)
+          .quote_style(csv::QuoteStyle::Always)
+          .terminator(csv::Terminator::CRLF)
+          .from_writer(output);
+        if csv_options.headers {
+          csv_writer.write_record(&csv_options.columns)?;
+        }
+        Output::Csv(Box::new(csv_writer), csv_options.columns)
+      },
+      OutputFormat::STIX => Output::Stix(output, stix::Bundle::default()),
+      _ => Output::Text(output),
+    };
+    Ok(Self {
+      output,
+      output_format,
+      normalize,
+      records: 0,
+      ecs_index: String::from("lnk"),
+      spans: false,
+      redactor: None,
+      normalized_fields: DEFAULT_NORMALIZED_FIELDS,
+    })
+  }
+
+  /// Set the index name used in the action lines of the ECS output.
+  pub fn ecs_index(mut self, index: &str) -> Self {
+    self.ecs_index = index.to_string();
+    self
+  }
+
+  /// Add the byte spans of the fields to the JSON records.
+  pub fn spans(mut self, spans: bool) -> Self {
+    self.spans = spans;
+    self
+  }
+
+  /// Replace the personal data of the JSON and CSV records with pseudonyms.
+  pub fn redactor(mut self, redactor: Option<Redactor>) -> Self {
+    self.redactor = redactor;
+    self
+  }
+
+  /// Set the fields of the normalized JSON records.
+  pub fn normalized_fields(mut self, fields: &'static [&'static str]) -> Self {
+    self.normalized_fields = fields;
+    self
+  }
+
+  /// Write the records to a SQLite database instead of a stream.
+  pub fn sqlite(path: &str) -> rusqlite::Result<Self> {
+    Ok(Self {
+      output: Output::Sqlite(SqliteSink::open(path)?),
+      output_format: OutputFormat::SQLITE,
+      normalize: false,
+      records: 0,
+      ecs_index: String::from("lnk"),
+      spans: false,
+      redactor: None,
+      normalized_fields: DEFAULT_NORMALIZED_FIELDS,
+    })
+  }
+
+  /// Write a record. Serializing formats the dates stored in the file, which panics on some invalid ones: every format
+  /// serializes the whole record before writing it, so the panic is returned as an `InvalidData` error and nothing is
+  /// written for the record.
+  pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(|| self.serialize_record(record))).unwrap_or_else(|payload| {
+      Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("the record could not be serialized: {}", error::panic_message(&*payload)),
+      ))
+    })
+  }
+
+  fn serialize_record(&mut self, record: &Record) -> io::Result<()> {
+    let parsed = &record.lnk;
+    match &mut self.output {
+      Output::Text(output) if matches!(self.output_format, OutputFormat::ECS) => {
+        for line in ecs::bulk_lines(record, &self.ecs_index) {
+          output.write_all(line.to_string().as_bytes())?;
+          output.write_all(b"\n")?;
+        }
+      },
+      Output::Text(output) => {
+        let json_data = if self.normalize {
+          let mut data = parsed.normalize();
+          data.retain(|field, _| self.normalized_fields.contains(&field.as_str()));
+          if let Some(redactor) = &self.redactor {
+            redactor.redact_normalized(&mut data);
+          }
+          serde_json::to_string(&Versioned::new(Normalized(data))).unwrap()
+        } else if (self.spans && parsed.spans.is_none()) || self.redactor.is_some() {
+          let mut json = serde_json::to_value(Versioned::new(parsed)).unwrap();
+          if self.spans && parsed.spans.is_none() {
+            json["spans"] = serde_json::to_value(spans(&record.data)).unwrap();
+          }
+          if let Some(redactor) = &self.redactor {
+            redactor.redact_json(&mut json);
+          }
+          json.to_string()
+        } else {
+          serde_json::to_string(&Versioned::new(parsed)).unwrap()
+        };
+        // The JSON array is written one element at a time instead of collecting every record in memory first.
+        if let OutputFormat::JSON = self.output_format {
+          let separator: &[u8] = if self.records == 0 { b"[" } else { b"," };
+          output.write_all(separator)?;
+        }
+        output.write_all(json_data.as_bytes())?;
+        if let OutputFormat::JSONL = self.output_format {
+          output.write_all(b"\r\n")?;
+        }
+      },
+      Output::Csv(csv_writer, columns) => {
+        let mut data = parsed.normalize();
+        if let Some(redactor) = &self.redactor {
+          redactor.redact_normalized(&mut data);
+        }
+        let row = columns.iter().map(|column| match data.get(column) {
+          Some(value) => value.as_str(),
+          None => "",
+        });
+        csv_writer.write_record(row)?;
+      },
+      Output::Sqlite(sqlite) => sqlite.write_record(record).map_err(io::Error::other)?,
+      Output::Stix(_, bundle) => bundle.add(record),
+    }
+    self.records += 1;
+    Ok(())
+  }
+
+  /// Flush the records written so far to the output.
+  pub fn flush(&mut self) -> io::Result<()> {
+    match &mut self.output {
+      Output::Text(output) => output.flush(),
+      Output::Csv(csv_writer, _) => csv_writer.flush(),
+      Output::Sqlite(_) | Output::Stix(..) => Ok(()),
+    }
+  }
+
+  pub fn finish(&mut self) -> io::Result<()> {
+    match &mut self.output {
+      Output::Text(output) => {
+        if let OutputFormat::JSON = self.output_format {
+          let end: &[u8] = if self.records == 0 { b"[]" } else { b"]" };
+          output.write_all(end)?;
+        }
+        output.flush()?;
+      },
+      Output::Csv(csv_writer, _) => csv_writer.flush()?,
+      Output::Sqlite(sqlite) => sqlite.finish().map_err(io::Error::other)?,
+      Output::Stix(output, bundle) => {
+        output.write_all(bundle.to_json().to_string().as_bytes())?;
+        output.flush()?;
+      },
+    }
+    Ok(())
+  }
+}
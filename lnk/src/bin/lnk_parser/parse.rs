@@ -0,0 +1,463 @@
+use crate::{
+  error::{self, CliError},
+  output::{self, Record, RecordWriter},
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use glob::glob;
+use lnk::{
+  archive::{self, ArchiveMember},
+  evidence::Manifest,
+  filter::Filter,
+  jump_list::JumpList,
+  resolve::Resolver,
+  rules::RuleSet,
+  triage::{self, Artifact, ArtifactKind},
+  Lnk, LnkFileMetaData,
+};
+use std::{
+  borrow::Cow,
+  collections::BTreeMap,
+  env, fs, io,
+  path::PathBuf,
+  sync::{mpsc, Mutex},
+  thread,
+  time::Duration,
+};
+use winparsingtools::traits::Normalize;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("parse")
+    .about("Parse LNK files and jump lists (the default when no subcommand is given)")
+    .arg(
+      Arg::with_name("PATH")
+        .short("-p")
+        .long("--path")
+        .takes_value(true)
+        .multiple(true)
+        .value_name("PATH")
+        .help("Path(s) to LNK Metadata Files to be Parsed - accepts glob, can point into .zip/.tar/.tar.gz archives (Defaults to 'RecentItems' for all users)"),
+    )
+    .arg(
+      Arg::with_name("root")
+        .long("--root")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with_all(&["PATH", "watch"])
+        .help("Mount point of a Windows volume, parse the LNK files and jump lists of every user profile under it"),
+    )
+    .arg(
+      Arg::with_name("watch")
+        .long("--watch")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with("PATH")
+        .help("Keep running and parse the LNK files created or modified under DIR (Linux only), the records are appended to the output as JSONL (or ECS) lines"),
+    )
+    .arg(
+      Arg::with_name("debounce")
+        .long("--debounce")
+        .takes_value(true)
+        .value_name("MS")
+        .requires("watch")
+        .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+        .help("With --watch, parse a file once it was not written to for MS milliseconds (Defaults to 500)"),
+    )
+    .arg(
+      Arg::with_name("resolve")
+        .long("--resolve")
+        .takes_value(true)
+        .value_name("DIR")
+        .help("Mount point of the volume the targets are on, add to the JSON and JSONL records whether the target still exists and matches the size, times and MFT entry the shortcut recorded"),
+    )
+    .arg(
+      Arg::with_name("rules")
+        .long("--rules")
+        .takes_value(true)
+        .value_name("DIR")
+        .help("Directory (or file) of YAML detection rules, add the rules every record matches to the output as 'rule_matches' (see the README for the syntax)"),
+    )
+    .arg(
+      Arg::with_name("threads")
+        .long("--threads")
+        .takes_value(true)
+        .value_name("N")
+        .default_value("1")
+        .validator(|v| v.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+        .help("Number of worker threads used to parse the files (0 = one per CPU), the output order is not affected"),
+    )
+    .arg(
+      Arg::with_name("filter")
+        .long("--filter")
+        .takes_value(true)
+        .value_name("EXPRESSION")
+        .validator(|f| Filter::parse(&f).map(|_| ()).map_err(|e| e.to_string()))
+        .help("Only write the records matching the expression, e.g. 'drive_type == \"DRIVE_REMOVABLE\" && target_hostname != \"WS01\"' (see the README for the syntax)"),
+    )
+    .arg(
+      Arg::with_name("manifest")
+        .long("--manifest")
+        .takes_value(true)
+        .value_name("FILE")
+        .requires("manifest-key")
+        .conflicts_with("watch")
+        .help("Write a chain of custody manifest to FILE at the end of the run: the inputs with their MD5, SHA-1 and SHA-256, the tool version, the arguments, the start and end times and an HMAC-SHA256 of the output file keyed by --manifest-key, the manifest is signed with the same key"),
+    )
+    .arg(
+      Arg::with_name("manifest-key")
+        .long("--manifest-key")
+        .takes_value(true)
+        .value_name("KEY_FILE")
+        .requires("manifest")
+        .help("File holding the key of the HMAC written in the --manifest, without the trailing new line"),
+    )
+    .args(&output::args())
+}
+
+/// An artifact to parse, archive members are already read into memory.
+pub struct Input {
+  pub artifact: Artifact,
+  pub member: Option<ArchiveMember>,
+}
+
+/// Read the members matching the member pattern of every archive matching the archive part of `path`.
+fn archive_inputs(archive_path: &str, member_pattern: &str) -> Vec<Input> {
+  let patterns: Vec<&str> = match member_pattern {
+    "" => archive::DEFAULT_MEMBER_PATTERNS.to_vec(),
+    pattern => vec![pattern],
+  };
+  let patterns = match patterns.iter().map(|p| glob::Pattern::new(p)).collect::<Result<Vec<_>, _>>() {
+    Ok(patterns) => patterns,
+    Err(e) => {
+      eprintln!("Invalid archive member pattern '{}'. ERROR : '{}'", member_pattern, e);
+      return vec![];
+    },
+  };
+  let mut inputs = vec![];
+  for archive in glob(archive_path).expect("Failed to read glob pattern").flatten() {
+    match archive::read_members(&archive, &patterns) {
+      Ok(members) => inputs.extend(members.into_iter().filter_map(|member| match member {
+        Ok(member) => Some(Input {
+          artifact: Artifact {
+            path: PathBuf::from(format!("{}/{}", archive.display(), member.name)),
+            kind: ArtifactKind::from_path(&member.name),
+            user: triage::user_from_path(&member.name),
+          },
+          member: Some(member),
+        }),
+        Err(e) => {
+          eprintln!("Skipped a member of '{}'. ERROR : '{}'", archive.display(), e);
+          None
+        },
+      })),
+      Err(e) => eprintln!("Did not read '{}' correctly. ERROR : '{}'", archive.display(), e),
+    }
+  }
+  inputs
+}
+
+/// The content of an artifact, archive members are already read into memory.
+fn read_artifact(input: &Input) -> io::Result<Cow<'_, [u8]>> {
+  match &input.member {
+    Some(member) => Ok(Cow::Borrowed(member.data.as_slice())),
+    None => fs::read(&input.artifact.path).map(Cow::Owned),
+  }
+}
+
+/// Parse the content of an artifact read by `read_artifact`.
+fn parse_artifact_data(input: &Input, data: &[u8]) -> io::Result<Vec<Record>> {
+  let artifact = &input.artifact;
+  let mut parsed = match artifact.kind {
+    ArtifactKind::Lnk => vec![Record {
+      lnk: Lnk::from_buffer(data)?,
+      data: data.to_vec(),
+      entry: None,
+    }],
+    _ => JumpList::from_buffer(data)?
+      .entries
+      .into_iter()
+      .map(|entry| Record {
+        lnk: entry.lnk,
+        data: entry.data,
+        entry: Some(entry.entry),
+      })
+      .collect(),
+  };
+  let mut lnk_file_metadata = match &input.member {
+    Some(member) => member.metadata.clone(),
+    None => LnkFileMetaData::from_path(&artifact.path.to_string_lossy())?,
+  };
+  lnk_file_metadata.user = artifact.user.clone();
+  for record in parsed.iter_mut() {
+    record.lnk.lnk_file_metadata = Some(lnk_file_metadata.clone());
+  }
+  Ok(parsed)
+}
+
+/// Parse an artifact, jump lists produce a record for every LNK entry they contain. A panic of the parser on a
+/// malformed file is returned as an error so the other artifacts are still parsed.
+pub fn parse_artifact(input: &Input) -> io::Result<Vec<Record>> {
+  read_and_parse_artifact(input).1
+}
+
+/// Read and parse an artifact like [`parse_artifact`], the data is returned with the records so the manifest hashes
+/// the bytes that were parsed.
+fn read_and_parse_artifact(input: &Input) -> (Option<Cow<'_, [u8]>>, io::Result<Vec<Record>>) {
+  let data = match read_artifact(input) {
+    Ok(data) => data,
+    Err(e) => return (None, Err(e)),
+  };
+  let result = error::catch_panic(|| parse_artifact_data(input, &data));
+  (Some(data), result)
+}
+
+/// Parse the artifacts on `threads` worker threads. `emit` is called on the calling thread in the same order as
+/// `artifacts` with the data of the artifact (`None` when it could not be read), so the output does not depend on the
+/// number of threads. Once `emit` returns an error the artifacts left are not parsed and the error is returned.
+fn parse_artifacts<F>(artifacts: &[Input], threads: usize, mut emit: F) -> io::Result<()>
+where
+  F: FnMut(&Input, Option<&[u8]>, io::Result<Vec<Record>>) -> io::Result<()>,
+{
+  if threads <= 1 {
+    for artifact in artifacts {
+      let (data, result) = read_and_parse_artifact(artifact);
+      emit(artifact, data.as_deref(), result)?;
+    }
+    return Ok(());
+  }
+
+  // Only a limited number of artifacts are handed to the workers ahead of the next one to emit, this keeps the
+  // number of parsed records waiting to be written bounded when a single file is slow to parse.
+  let window = threads * 4;
+  let (job_sender, job_receiver) = mpsc::channel::<usize>();
+  let (result_sender, result_receiver) = mpsc::channel();
+  let job_receiver = Mutex::new(job_receiver);
+
+  thread::scope(|scope| {
+    for _ in 0..threads {
+      let result_sender = result_sender.clone();
+      let job_receiver = &job_receiver;
+      scope.spawn(move || loop {
+        let job = job_receiver.lock().unwrap().recv();
+        match job {
+          Ok(index) => {
+            if result_sender.send((index, read_and_parse_artifact(&artifacts[index]))).is_err() {
+              break;
+            }
+          },
+          Err(_) => break,
+        }
+      });
+    }
+    // Only the workers hold a sender, `recv` fails instead of blocking forever if they all stopped.
+    drop(result_sender);
+
+    let mut next_job = 0;
+    let mut next_emit = 0;
+    let mut pending = BTreeMap::new();
+    while next_emit < artifacts.len() {
+      while next_job < artifacts.len() && next_job < next_emit + window {
+        job_sender.send(next_job).expect("Worker threads stopped unexpectedly");
+        next_job += 1;
+      }
+      let (index, result) = result_receiver.recv().expect("Worker threads stopped unexpectedly");
+      pending.insert(index, result);
+      while let Some((data, result)) = pending.remove(&next_emit) {
+        if let Err(e) = emit(&artifacts[next_emit], data.as_deref(), result) {
+          // Drop the queued jobs, the workers stop once they sent the result of the artifact they are parsing.
+          drop(job_sender);
+          while job_receiver.lock().unwrap().try_recv().is_ok() {}
+          return Err(e);
+        }
+        next_emit += 1;
+      }
+    }
+    drop(job_sender);
+    Ok(())
+  })
+}
+
+/// The artifacts given by `--path` or found under `--root`, the default paths are used when neither is given.
+pub fn inputs(args: &ArgMatches) -> Vec<Input> {
+  let mut lnk_file_paths = vec![
+    "C:\\Windows\\SysWOW64\\config\\systemprofile\\AppData\\Roaming\\Microsoft\\Windows\\**\\*.lnk",
+    "C:\\Windows\\System32\\config\\systemprofile\\AppData\\Roaming\\Microsoft\\Windows\\**\\*.lnk",
+    "C:\\Users\\*\\AppData\\Roaming\\Microsoft\\Windows\\Recent\\*.lnk",
+  ];
+  if args.occurrences_of("PATH") > 0 {
+    // override lnk_file_paths if the argument -p/--path is specified.
+    lnk_file_paths = args.values_of("PATH").unwrap().collect();
+  }
+
+  match args.value_of("root") {
+    Some(root) => triage::collect(root)
+      .into_iter()
+      .map(|artifact| Input { artifact, member: None })
+      .collect(),
+    None => lnk_file_paths
+      .iter()
+      .flat_map(|dir| match archive::split_path(dir) {
+        // `--path` can point into a zip or tar archive, the rest of the pattern is matched against member names.
+        Some((archive_path, member_pattern)) => archive_inputs(&archive_path, &member_pattern),
+        None => glob(dir)
+          .expect("Failed to read glob pattern")
+          .filter_map(|entry| match entry {
+            Ok(path) => Some(path),
+            Err(e) => {
+              eprintln!("{:?}", e);
+              None
+            },
+          })
+          .map(|path| Input {
+            artifact: Artifact {
+              kind: ArtifactKind::from_path(&path),
+              user: triage::user_from_path(&path.to_string_lossy()),
+              path,
+            },
+            member: None,
+          })
+          .collect(),
+      })
+      .collect(),
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn watch(
+  dir: &str,
+  debounce: Duration,
+  filter: Option<&Filter>,
+  resolver: Option<&Resolver>,
+  rules: Option<&RuleSet>,
+  writer: &mut RecordWriter,
+) -> Result<(), CliError> {
+  crate::watch::run(dir, debounce, filter, resolver, rules, writer)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(
+  _: &str,
+  _: Duration,
+  _: Option<&Filter>,
+  _: Option<&Resolver>,
+  _: Option<&RuleSet>,
+  _: &mut RecordWriter,
+) -> Result<(), CliError> {
+  Err(CliError::Usage(String::from("--watch uses inotify and is only supported on Linux")))
+}
+
+/// Evaluate the rules on the records of an artifact, keep those matching the filter and resolve their targets. The
+/// rules and the filter format the dates stored in the file, which panics on some invalid ones: the panic is returned
+/// as an error.
+pub fn prepare(
+  records: Vec<Record>,
+  rules: Option<&RuleSet>,
+  filter: Option<&Filter>,
+  resolver: Option<&Resolver>,
+) -> io::Result<Vec<Record>> {
+  error::catch_panic(|| {
+    let mut prepared = vec![];
+    for mut record in records {
+      if let Some(rules) = rules {
+        record.lnk.rule_matches = Some(rules.evaluate(&record.lnk, &record.data));
+      }
+      if filter.is_some_and(|f| !f.matches(&record.lnk.normalize())) {
+        continue;
+      }
+      if let Some(resolver) = resolver {
+        record.lnk.resolution = record.lnk.resolve(resolver);
+      }
+      prepared.push(record);
+    }
+    Ok(prepared)
+  })
+}
+
+/// Record an input in the manifest with the data it was parsed from, `None` when it could not be read.
+fn add_to_manifest(manifest: &mut Manifest, input: &Input, data: Option<&[u8]>, result: &io::Result<Vec<Record>>) {
+  let (records, error) = match result {
+    Ok(records) => (records.len(), None),
+    Err(e) => (0, Some(e.to_string())),
+  };
+  manifest.add_input(&input.artifact.path.to_string_lossy(), data, records, error);
+}
+
+/// Parse the files given by `--path` or found under `--root` and write the records.
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let threads = match args.value_of("threads").unwrap().parse::<usize>().unwrap() {
+    0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    n => n,
+  };
+  let filter = args.value_of("filter").map(|f| Filter::parse(f).unwrap());
+  let resolver = args.value_of("resolve").map(Resolver::new);
+  let rules = match args.value_of("rules") {
+    Some(dir) => Some(RuleSet::load(dir).map_err(|e| CliError::Usage(format!("Could not load the rules: {}", e)))?),
+    None => None,
+  };
+
+  if let Some(dir) = args.value_of("watch") {
+    let mut writer = output::open_append_writer(args)?;
+    let debounce = Duration::from_millis(args.value_of("debounce").unwrap_or("500").parse().unwrap());
+    return watch(dir, debounce, filter.as_ref(), resolver.as_ref(), rules.as_ref(), &mut writer);
+  }
+  let output_path = args.value_of("output").unwrap();
+  let mut manifest = match (args.value_of("manifest"), output_path) {
+    (Some(_), "stdout") => {
+      return Err(CliError::Usage(String::from("--manifest needs the output in a file, use -o/--output")))
+    },
+    (Some(_), _) => Some(Manifest::new(env::args_os().map(|a| a.to_string_lossy().into_owned()).collect())),
+    (None, _) => None,
+  };
+  let manifest_key = args.value_of("manifest-key").map(output::read_key).transpose()?;
+  let mut writer = output::open_writer(args)?;
+
+  let artifacts = inputs(args);
+
+  let mut failed = 0;
+  let mut unreadable = 0;
+  parse_artifacts(&artifacts, threads, |input, data, result| {
+    if let Some(manifest) = manifest.as_mut() {
+      add_to_manifest(manifest, input, data, &result);
+    }
+    let result = result.and_then(|records| prepare(records, rules.as_ref(), filter.as_ref(), resolver.as_ref()));
+    match result {
+      Ok(records) => {
+        let mut serialize_error = None;
+        for record in records {
+          match writer.write_record(&record) {
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => serialize_error = Some(e),
+            // A write error (a closed pipe, a full disk) ends the run.
+            result => result?,
+          }
+        }
+        if let Some(e) = serialize_error {
+          failed += 1;
+          eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e);
+        }
+      },
+      Err(e) if error::is_read_error(&e) => {
+        unreadable += 1;
+        eprintln!("Could not read '{}'. ERROR : '{}'", input.artifact.path.display(), e);
+      },
+      Err(e) => {
+        failed += 1;
+        eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e);
+      },
+    }
+    Ok(())
+  })?;
+  writer.finish()?;
+  if let (Some(mut manifest), Some(key)) = (manifest, manifest_key) {
+    manifest.finish(output_path, &key)?;
+    fs::write(args.value_of("manifest").unwrap(), serde_json::to_string_pretty(&manifest).unwrap())?;
+  }
+
+  if artifacts.is_empty() {
+    Err(CliError::NoFiles)
+  } else if unreadable > 0 {
+    Err(CliError::ReadFailed(unreadable))
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else {
+    Ok(())
+  }
+}
@@ -0,0 +1,104 @@
+use crate::{
+  artifact::{self, ArtifactRecord, ArtifactWriter},
+  ecs,
+  error::{self, CliError},
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{
+  prefetch::{Prefetch, PREFETCH_NORMALIZED_FIELDS},
+  triage,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs, io, path::Path};
+use winparsingtools::traits::Normalize;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("prefetch")
+    .about("Parse Windows Prefetch (.pf) files, compressed (Windows 10 and 11) or not")
+    .arg(
+      Arg::with_name("PATH")
+        .short("-p")
+        .long("--path")
+        .takes_value(true)
+        .multiple(true)
+        .value_name("PATH")
+        .help("Path(s) to the Prefetch files to be parsed - accepts glob (Defaults to 'C:\\Windows\\Prefetch\\*.pf')"),
+    )
+    .arg(
+      Arg::with_name("root")
+        .long("--root")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with("PATH")
+        .help("Mount point of a Windows volume, parse the Prefetch files under Windows/Prefetch"),
+    )
+    .args(&artifact::args("prefetch"))
+}
+
+/// A parsed prefetch file and the bytes it was read from.
+struct PrefetchRecord {
+  prefetch: Prefetch,
+  data: Vec<u8>,
+}
+
+impl ArtifactRecord for PrefetchRecord {
+  fn to_json(&self) -> Value {
+    serde_json::to_value(&self.prefetch).unwrap()
+  }
+
+  fn normalize(&self) -> HashMap<String, String> {
+    self.prefetch.normalize()
+  }
+
+  fn bulk_lines(&self, index: &str) -> Vec<Value> {
+    let sha256: String = Sha256::digest(&self.data).iter().map(|b| format!("{:02x}", b)).collect();
+    ecs::prefetch_bulk_lines(&self.prefetch, &sha256, self.data.len(), index)
+  }
+}
+
+fn parse(path: &Path) -> io::Result<PrefetchRecord> {
+  let data = fs::read(path)?;
+  let prefetch = error::catch_panic(|| Prefetch::from_buffer(&data))?.located_at(&path.to_string_lossy());
+  Ok(PrefetchRecord { prefetch, data })
+}
+
+/// Parse the prefetch files given by `--path` or found under `--root` and write the records.
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let mut writer = ArtifactWriter::open(args, PREFETCH_NORMALIZED_FIELDS)?;
+  let paths = match (args.values_of("PATH"), args.value_of("root")) {
+    (Some(paths), _) => artifact::glob_paths(&paths.collect::<Vec<_>>()),
+    // Windows paths are matched case-insensitively, the volume may be mounted on a case-sensitive file system.
+    (None, Some(root)) => triage::resolve(root, &["Windows", "Prefetch"])
+      .iter()
+      .flat_map(|dir| artifact::glob_paths(&[format!("{}/*.pf", glob::Pattern::escape(&dir.to_string_lossy()))]))
+      .collect(),
+    (None, None) => artifact::glob_paths(&["C:\\Windows\\Prefetch\\*.pf"]),
+  };
+  let mut failed = 0;
+  let mut unreadable = 0;
+  for path in &paths {
+    match parse(path) {
+      Ok(record) => writer.write(&record)?,
+      Err(e) if error::is_read_error(&e) => {
+        unreadable += 1;
+        eprintln!("Could not read '{}'. ERROR : '{}'", path.display(), e);
+      },
+      Err(e) => {
+        failed += 1;
+        eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path.display(), e);
+      },
+    }
+  }
+  writer.finish()?;
+
+  if paths.is_empty() {
+    Err(CliError::NoFiles)
+  } else if unreadable > 0 {
+    Err(CliError::ReadFailed(unreadable))
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else {
+    Ok(())
+  }
+}
@@ -0,0 +1,71 @@
+use crate::{
+  error::{self, CliError},
+  parse,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::report::Report;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("report")
+    .about("Aggregate LNK files and jump lists by the machines, volumes and network shares their targets were on")
+    .arg(
+      Arg::with_name("PATH")
+        .short("-p")
+        .long("--path")
+        .takes_value(true)
+        .multiple(true)
+        .value_name("PATH")
+        .help("Path(s) to LNK Metadata Files to be aggregated - accepts glob, can point into .zip/.tar/.tar.gz archives (Defaults to 'RecentItems' for all users)"),
+    )
+    .arg(
+      Arg::with_name("root")
+        .long("--root")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with("PATH")
+        .help("Mount point of a Windows volume, aggregate the LNK files and jump lists of every user profile under it"),
+    )
+    .arg(
+      Arg::with_name("output-format")
+        .long("--output-format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .help("Format of the report"),
+    )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let inputs = parse::inputs(args);
+  let mut report = Report::new();
+  let mut failed = 0;
+  let mut unreadable = 0;
+  for input in &inputs {
+    match parse::parse_artifact(input) {
+      Ok(records) => records.iter().for_each(|record| report.add(&record.lnk)),
+      Err(e) if error::is_read_error(&e) => {
+        unreadable += 1;
+        eprintln!("Could not read '{}'. ERROR : '{}'", input.artifact.path.display(), e);
+      },
+      Err(e) => {
+        failed += 1;
+        eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e);
+      },
+    }
+  }
+
+  match args.value_of("output-format").unwrap() {
+    "json" => println!("{}", serde_json::to_string(&report).unwrap()),
+    _ => print!("{}", report),
+  }
+
+  if inputs.is_empty() {
+    Err(CliError::NoFiles)
+  } else if unreadable > 0 {
+    Err(CliError::ReadFailed(unreadable))
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else {
+    Ok(())
+  }
+}
@@ -0,0 +1,56 @@
+use crate::error::CliError;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::rules::RuleSet;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("rules")
+    .about("Check and list detection rules, or run their tests")
+    .arg(
+      Arg::with_name("DIR")
+        .required(true)
+        .help("Directory (or file) of YAML detection rules"),
+    )
+    .arg(
+      Arg::with_name("test")
+        .long("--test")
+        .takes_value(false)
+        .help("Check every rule against the LNK files listed under its 'tests' and print PASS/FAIL lines"),
+    )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let dir = args.value_of("DIR").unwrap();
+  let rules = RuleSet::load(dir).map_err(|e| CliError::Usage(format!("Could not load the rules: {}", e)))?;
+
+  if !args.is_present("test") {
+    for rule in rules.rules() {
+      println!("{}\t{}\t{}", rule.id, rule.severity, rule.title);
+    }
+    return Ok(());
+  }
+
+  let results = rules.run_tests();
+  for result in &results {
+    let expected = match result.should_match {
+      true => "should match",
+      false => "should not match",
+    };
+    let status = if result.passed() { "PASS" } else { "FAIL" };
+    match &result.outcome {
+      Err(e) => println!("{} {} {} ({}): {}", status, result.rule_id, result.path.display(), expected, e),
+      Ok(_) => println!("{} {} {} ({})", status, result.rule_id, result.path.display(), expected),
+    }
+  }
+  let untested = rules
+    .rules()
+    .iter()
+    .filter(|rule| rule.tests.should_match.is_empty() && rule.tests.should_not_match.is_empty());
+  for rule in untested {
+    eprintln!("Rule '{}' has no tests", rule.id);
+  }
+
+  match results.iter().filter(|result| !result.passed()).count() {
+    0 => Ok(()),
+    failed => Err(CliError::RuleTestsFailed(failed)),
+  }
+}
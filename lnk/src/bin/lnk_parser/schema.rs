@@ -0,0 +1,23 @@
+use crate::error::CliError;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::schema;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("schema")
+    .about("Print the JSON Schema of the json and jsonl records")
+    .arg(
+      Arg::with_name("normalize")
+        .long("--normalize")
+        .takes_value(false)
+        .help("Print the schema of the normalized records (--normalize) instead of the raw records"),
+    )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let schema = match args.is_present("normalize") {
+    true => schema::normalized_schema(),
+    false => schema::lnk_schema(),
+  };
+  println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+  Ok(())
+}
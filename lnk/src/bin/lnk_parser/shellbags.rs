@@ -0,0 +1,121 @@
+use crate::{
+  artifact::{self, ArtifactRecord, ArtifactWriter},
+  ecs,
+  error::CliError,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{
+  shellbags::{regf::Hive, ShellBag, ShellBags, SHELLBAG_NORMALIZED_FIELDS},
+  triage,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs, io, path::Path};
+use winparsingtools::traits::Normalize;
+
+/// The hives holding ShellBags, relative to the root of a Windows volume.
+const HIVES: &[&[&str]] = &[
+  &["Users", "*", "NTUSER.DAT"],
+  &["Users", "*", "AppData", "Local", "Microsoft", "Windows", "UsrClass.dat"],
+  &["Documents and Settings", "*", "NTUSER.DAT"],
+];
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("shellbags")
+    .about("Extract the folders a user browsed (ShellBags) from offline NTUSER.DAT and UsrClass.dat hives")
+    .arg(
+      Arg::with_name("PATH")
+        .short("-p")
+        .long("--path")
+        .takes_value(true)
+        .multiple(true)
+        .value_name("PATH")
+        .help(
+          "Path(s) to the hives to be parsed - accepts glob (Defaults to the NTUSER.DAT and UsrClass.dat of all users)",
+        ),
+    )
+    .arg(
+      Arg::with_name("root")
+        .long("--root")
+        .takes_value(true)
+        .value_name("DIR")
+        .conflicts_with("PATH")
+        .help("Mount point of a Windows volume, parse the NTUSER.DAT and UsrClass.dat of every user profile under it"),
+    )
+    .args(&artifact::args("shellbags"))
+}
+
+/// A ShellBag and the ID of its folder key, derived from the hive data so loading a hive twice does not duplicate
+/// events.
+struct ShellBagRecord<'a> {
+  shell_bag: &'a ShellBag,
+  hive_sha256: &'a [u8],
+}
+
+impl ArtifactRecord for ShellBagRecord<'_> {
+  fn to_json(&self) -> Value {
+    serde_json::to_value(self.shell_bag).unwrap()
+  }
+
+  fn normalize(&self) -> HashMap<String, String> {
+    self.shell_bag.normalize()
+  }
+
+  fn bulk_lines(&self, index: &str) -> Vec<Value> {
+    let id = Sha256::new()
+      .chain_update(self.hive_sha256)
+      .chain_update(self.shell_bag.key_path.as_bytes())
+      .finalize();
+    let id: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    ecs::shellbag_bulk_lines(self.shell_bag, &id, index)
+  }
+}
+
+fn parse(path: &Path) -> io::Result<(ShellBags, Vec<u8>)> {
+  let data = fs::read(path)?;
+  let hive_sha256 = Sha256::digest(&data).to_vec();
+  let shell_bags = ShellBags::from_hive(&Hive::from_buffer(data)?)?.located_at(&path.to_string_lossy());
+  Ok((shell_bags, hive_sha256))
+}
+
+/// Parse the hives given by `--path` or found under `--root` and write a record for every folder.
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let mut writer = ArtifactWriter::open(args, SHELLBAG_NORMALIZED_FIELDS)?;
+  let paths = match (args.values_of("PATH"), args.value_of("root")) {
+    (Some(paths), _) => artifact::glob_paths(&paths.collect::<Vec<_>>()),
+    (None, Some(root)) => HIVES.iter().flat_map(|hive| triage::resolve(root, hive)).collect(),
+    (None, None) => artifact::glob_paths(&[
+      "C:\\Users\\*\\NTUSER.DAT",
+      "C:\\Users\\*\\AppData\\Local\\Microsoft\\Windows\\UsrClass.dat",
+    ]),
+  };
+  let mut failed = 0;
+  for path in &paths {
+    match parse(path) {
+      Ok((shell_bags, hive_sha256)) => {
+        if shell_bags.dirty {
+          eprintln!("'{}' has changes that are only in its transaction logs, they are not read", path.display());
+        }
+        for shell_bag in &shell_bags.entries {
+          writer.write(&ShellBagRecord {
+            shell_bag,
+            hive_sha256: &hive_sha256,
+          })?;
+        }
+      },
+      Err(e) => {
+        failed += 1;
+        eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path.display(), e);
+      },
+    }
+  }
+  writer.finish()?;
+
+  if paths.is_empty() {
+    Err(CliError::NoFiles)
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else {
+    Ok(())
+  }
+}
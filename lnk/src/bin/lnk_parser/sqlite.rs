@@ -0,0 +1,199 @@
+use crate::output::Record;
+use lnk::{extra_data::ExtraDataTypes, schema::Versioned, NORMALIZED_FIELDS};
+use rusqlite::{params, params_from_iter, types::Value, Connection, Result};
+use winparsingtools::{structs::shell_items::Name, traits::Normalize};
+
+/// Bump when the schema changes, databases written by another version are refused instead of mixing layouts.
+const SCHEMA_VERSION: i64 = 4;
+
+/// The comments are stored by SQLite with the schema, `SELECT sql FROM sqlite_master` shows them.
+pub const SCHEMA: &str = r#"
+-- One row per parsed LNK file, records are identified by the SHA-256 of the LNK bytes.
+CREATE TABLE IF NOT EXISTS lnk_records (
+  id INTEGER PRIMARY KEY,
+  sha256 TEXT NOT NULL UNIQUE,         -- SHA-256 of the LNK data, parsing the same data again updates the row
+  target_full_path TEXT,               -- path of the target built from LinkInfo or the LinkTargetIDList
+  target_modification_time TEXT,       -- target times from the ShellLinkHeader (ISO 8601, UTC)
+  target_access_time TEXT,
+  target_creation_time TEXT,
+  target_size TEXT,                    -- target size from the ShellLinkHeader
+  target_hostname TEXT,                -- NetBIOS name of the machine the target was on (TrackerDataBlock machine ID)
+  lnk_full_path TEXT,                  -- path of the LNK file (or of the jump list holding it)
+  lnk_modification_time TEXT,          -- file system times of the LNK file (ISO 8601, UTC)
+  lnk_access_time TEXT,
+  lnk_creation_time TEXT,
+  user TEXT,                           -- user profile the LNK file was found in
+  drive_type TEXT,                     -- VolumeID drive type (DRIVE_FIXED, DRIVE_REMOVABLE, ...)
+  volume_serial_number TEXT,           -- VolumeID serial number (XXXX-XXXX)
+  volume_label TEXT,                   -- VolumeID label
+  net_name TEXT,                       -- CommonNetworkRelativeLink share name (\\server\share)
+  device_name TEXT,                    -- CommonNetworkRelativeLink mapped drive letter
+  name_string TEXT,                    -- StringData: description
+  relative_path TEXT,                  -- StringData: path relative to the LNK file
+  working_dir TEXT,                    -- StringData: working directory
+  command_line_arguments TEXT,         -- StringData: arguments
+  command_line TEXT,                   -- quoted target and arguments with the environment variables expanded
+  argv TEXT,                           -- command line split like CommandLineToArgvW (JSON array)
+  icon_location TEXT,                  -- StringData: icon
+  file_droid TEXT,                     -- TrackerDataBlock object IDs
+  file_droid_birth TEXT,
+  volume_droid TEXT,
+  volume_droid_birth TEXT,
+  rule_matches TEXT,                   -- ids of the detection rules the record matched (--rules), comma separated
+  lnk_md5 TEXT,                        -- hashes of the LNK data (lnk_sha256 is the same as sha256)
+  lnk_sha1 TEXT,
+  lnk_sha256 TEXT,
+  raw_json TEXT NOT NULL,              -- the full record as written by the JSON output format
+  data BLOB NOT NULL                   -- the LNK bytes
+);
+CREATE INDEX IF NOT EXISTS idx_lnk_records_target_full_path ON lnk_records (target_full_path);
+CREATE INDEX IF NOT EXISTS idx_lnk_records_target_hostname ON lnk_records (target_hostname);
+CREATE INDEX IF NOT EXISTS idx_lnk_records_volume_serial_number ON lnk_records (volume_serial_number);
+
+-- The items of the LinkTargetIDList, in order.
+CREATE TABLE IF NOT EXISTS shell_items (
+  record_id INTEGER NOT NULL REFERENCES lnk_records (id) ON DELETE CASCADE,
+  position INTEGER NOT NULL,           -- index of the item in the IDList, starting at 0
+  class_type INTEGER NOT NULL,         -- shell item class type indicator
+  item_type TEXT,                      -- decoded item type (FileEntry, Volume, Root, ...)
+  name TEXT,                           -- name the item contributes to the target path
+  data TEXT,                           -- the decoded item as JSON
+  PRIMARY KEY (record_id, position)
+);
+
+-- The ExtraData blocks, in order.
+CREATE TABLE IF NOT EXISTS extra_data_blocks (
+  record_id INTEGER NOT NULL REFERENCES lnk_records (id) ON DELETE CASCADE,
+  position INTEGER NOT NULL,           -- index of the block in the ExtraData section, starting at 0
+  signature TEXT NOT NULL,             -- block signature (0xA0000003, ...)
+  block_type TEXT NOT NULL,            -- decoded block type (Tracker, ...)
+  data TEXT,                           -- the decoded block as JSON
+  PRIMARY KEY (record_id, position)
+);
+
+-- Results of the analyses run on a record.
+CREATE TABLE IF NOT EXISTS findings (
+  record_id INTEGER NOT NULL REFERENCES lnk_records (id) ON DELETE CASCADE,
+  source TEXT NOT NULL,                -- the analysis that produced the finding ('rules' for --rules matches)
+  severity TEXT,
+  title TEXT NOT NULL,
+  offset INTEGER,                      -- offset in the LNK data the finding refers to, if any
+  details TEXT                         -- additional data as JSON
+);
+CREATE INDEX IF NOT EXISTS idx_findings_record_id ON findings (record_id);
+"#;
+
+/// Writes records to a SQLite database, every run is a single transaction.
+pub struct SqliteSink {
+  connection: Connection,
+}
+
+impl SqliteSink {
+  pub fn open(path: &str) -> Result<Self> {
+    let connection = Connection::open(path)?;
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version != 0 && version != SCHEMA_VERSION {
+      return Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISMATCH),
+        Some(format!("'{}' was written with schema version {}, expected {}", path, version, SCHEMA_VERSION)),
+      ));
+    }
+    connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+    connection.execute_batch(SCHEMA)?;
+    connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    connection.execute_batch("BEGIN;")?;
+    Ok(Self { connection })
+  }
+
+  /// Insert the record, or replace it and its child rows if a record with the same hash exists.
+  pub fn write_record(&mut self, record: &Record) -> Result<()> {
+    let lnk = &record.lnk;
+    let sha256 = record.sha256();
+    let fields = lnk.normalize();
+    let raw_json = serde_json::to_string(&Versioned::new(lnk)).unwrap();
+
+    let columns = NORMALIZED_FIELDS.join(", ");
+    let placeholders = (0..NORMALIZED_FIELDS.len() + 3)
+      .map(|i| format!("?{}", i + 1))
+      .collect::<Vec<String>>()
+      .join(", ");
+    let updates = NORMALIZED_FIELDS
+      .iter()
+      .chain(["raw_json", "data"].iter())
+      .map(|c| format!("{0} = excluded.{0}", c))
+      .collect::<Vec<String>>()
+      .join(", ");
+    let sql = format!(
+      "INSERT INTO lnk_records (sha256, {}, raw_json, data) VALUES ({}) ON CONFLICT (sha256) DO UPDATE SET {}",
+      columns, placeholders, updates
+    );
+
+    let mut values = vec![Value::Text(sha256.clone())];
+    for field in NORMALIZED_FIELDS {
+      values.push(Value::Text(fields.get(*field).cloned().unwrap_or_default()));
+    }
+    values.push(Value::Text(raw_json));
+    values.push(Value::Blob(record.data.clone()));
+    self.connection.execute(&sql, params_from_iter(values))?;
+
+    let record_id: i64 =
+      self
+        .connection
+        .query_row("SELECT id FROM lnk_records WHERE sha256 = ?1", [&sha256], |row| row.get(0))?;
+    for table in ["shell_items", "extra_data_blocks", "findings"] {
+      self
+        .connection
+        .execute(&format!("DELETE FROM {} WHERE record_id = ?1", table), [record_id])?;
+    }
+
+    if let Some(link_target_id_list) = &lnk.link_target_id_list {
+      for (position, shell_item) in link_target_id_list.shell_items.iter().enumerate() {
+        let (item_type, data) = match serde_json::to_value(&shell_item.shell_item_data).unwrap() {
+          serde_json::Value::Object(map) => match map.into_iter().next() {
+            Some((item_type, data)) => (Some(item_type), Some(data.to_string())),
+            None => (None, None),
+          },
+          _ => (None, None),
+        };
+        self.connection.execute(
+          "INSERT INTO shell_items (record_id, position, class_type, item_type, name, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+          params![record_id, position as i64, shell_item.class_type, item_type, shell_item.name(), data],
+        )?;
+      }
+    }
+
+    for rule_match in lnk.rule_matches.iter().flatten() {
+      let details = serde_json::json!({ "id": rule_match.id, "tags": rule_match.tags });
+      self.connection.execute(
+        "INSERT INTO findings (record_id, source, severity, title, details) VALUES (?1, 'rules', ?2, ?3, ?4)",
+        params![
+          record_id,
+          rule_match.severity.to_string(),
+          rule_match.title,
+          details.to_string()
+        ],
+      )?;
+    }
+
+    if let Some(extra_data) = &lnk.extra_data {
+      for (position, block) in extra_data.extra_data_blocks.iter().enumerate() {
+        let (signature, block_type) = match block {
+          ExtraDataTypes::Tracker(_) => ("0xA0000003", "Tracker"),
+        };
+        let data = match serde_json::to_value(block).unwrap() {
+          serde_json::Value::Object(map) => map.into_iter().next().map(|(_, data)| data.to_string()),
+          _ => None,
+        };
+        self.connection.execute(
+          "INSERT INTO extra_data_blocks (record_id, position, signature, block_type, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+          params![record_id, position as i64, signature, block_type, data],
+        )?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn finish(&mut self) -> Result<()> {
+    self.connection.execute_batch("COMMIT;")
+  }
+}
@@ -0,0 +1,199 @@
+//! [STIX 2.1](https://docs.oasis-open.org/cti/stix/v2.1/os/stix-v2.1-os.html) mapping of the parsed records.
+//!
+//! Every record becomes a `file` observable for the LNK file, a `file` observable for its target with a `directory`
+//! for the folder the target is in, a `directory` for the UNC share of the CommonNetworkRelativeLink and a `process`
+//! observable for the command line. `relationship` objects link the LNK file to the objects it points to:
+//!
+//! | Source | Relationship | Target |
+//! |---|---|---|
+//! | LNK `file` | `refers-to` | target `file` |
+//! | LNK `file` | `refers-to` | share `directory` |
+//! | LNK `file` | `starts` | `process` |
+//!
+//! The IDs are UUIDv5 of the ID contributing properties in the namespace of the specification, so the same LNK file,
+//! target or share always has the same ID and objects shared by several records are only written once.
+
+use crate::output::{Record, EMPTY_TIME};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use uuid::Uuid;
+use winparsingtools::traits::Normalize;
+
+/// Namespace of the deterministic identifiers of STIX Cyber-observable Objects.
+const NAMESPACE: Uuid = Uuid::from_u128(0x00abedb4_aa42_466c_9c01_fed23315a9b7);
+
+/// The ID of an object of type `object_type` from its ID contributing properties. `serde_json` sorts the keys of
+/// objects, which gives the canonical JSON the UUID is computed from.
+fn id(object_type: &str, properties: &Value) -> String {
+  let uuid = Uuid::new_v5(&NAMESPACE, properties.to_string().as_bytes());
+  format!("{}--{}", object_type, uuid)
+}
+
+/// Insert the properties that are not empty.
+fn insert(object: &mut Map<String, Value>, properties: &[(&str, &str)]) {
+  for (name, value) in properties {
+    if !value.is_empty() && *value != EMPTY_TIME {
+      object.insert(name.to_string(), json!(value));
+    }
+  }
+}
+
+fn directory(path: &str) -> Value {
+  json!({
+    "type": "directory",
+    "spec_version": "2.1",
+    "id": id("directory", &json!({ "path": path })),
+    "path": path,
+  })
+}
+
+/// A relationship, dated with the time of the LNK file so exporting the same file twice gives the same object.
+fn relationship(source: &Value, relationship_type: &str, target: &Value, time: &str) -> Value {
+  let (source_ref, target_ref) = (&source["id"], &target["id"]);
+  json!({
+    "type": "relationship",
+    "spec_version": "2.1",
+    "id": id("relationship", &json!({
+      "relationship_type": relationship_type,
+      "source_ref": source_ref,
+      "target_ref": target_ref,
+    })),
+    "created": time,
+    "modified": time,
+    "relationship_type": relationship_type,
+    "source_ref": source_ref,
+    "target_ref": target_ref,
+  })
+}
+
+/// Returns the STIX objects of the record, the LNK file first and the relationships last.
+pub fn objects(record: &Record) -> Vec<Value> {
+  let fields = record.lnk.normalize();
+  let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+  let sha256 = record.sha256();
+
+  let lnk_name = field("lnk_full_path").rsplit(['\\', '/']).next().unwrap_or_default();
+  let mut lnk_file = Map::new();
+  lnk_file.insert("type".to_string(), json!("file"));
+  lnk_file.insert("spec_version".to_string(), json!("2.1"));
+  lnk_file.insert("id".to_string(), json!(id("file", &json!({ "hashes": { "SHA-256": sha256 }, "name": lnk_name }))));
+  let hashes = &record.lnk.hashes;
+  lnk_file.insert("hashes".to_string(), json!({ "MD5": hashes.md5, "SHA-1": hashes.sha1, "SHA-256": sha256 }));
+  lnk_file.insert("size".to_string(), json!(record.data.len()));
+  insert(
+    &mut lnk_file,
+    &[
+      ("name", lnk_name),
+      ("ctime", field("lnk_creation_time")),
+      ("mtime", field("lnk_modification_time")),
+      ("atime", field("lnk_access_time")),
+    ],
+  );
+  let lnk_file = Value::Object(lnk_file);
+  // STIX timestamps of SDOs and SROs are precise to the millisecond.
+  let time = [field("lnk_creation_time"), field("target_creation_time")]
+    .into_iter()
+    .find(|time| !time.is_empty() && *time != EMPTY_TIME)
+    .map(|time| time.replace('Z', ".000Z"))
+    .unwrap_or_else(|| String::from("1970-01-01T00:00:00.000Z"));
+  let mut objects = vec![lnk_file.clone()];
+  let mut relationships = vec![];
+
+  let target_full_path = field("target_full_path");
+  let target_file = match target_full_path {
+    // A shortcut to the root of a share points to the share directory.
+    path if path.is_empty() || path.eq_ignore_ascii_case(field("net_name")) => None,
+    path => {
+      let (parent, name) = path.rsplit_once(['\\', '/']).unwrap_or(("", path));
+      // `\\server` is not a directory, only `\\server\share` and its subfolders are.
+      let is_host = parent.strip_prefix("\\\\").is_some_and(|host| !host.contains('\\'));
+      let parent = (!parent.is_empty() && !is_host).then(|| directory(parent));
+      let mut target_file = Map::new();
+      target_file.insert("type".to_string(), json!("file"));
+      target_file.insert("spec_version".to_string(), json!("2.1"));
+      let mut contributing = json!({ "name": name });
+      if let Some(parent) = &parent {
+        contributing["parent_directory_ref"] = parent["id"].clone();
+      }
+      target_file.insert("id".to_string(), json!(id("file", &contributing)));
+      insert(
+        &mut target_file,
+        &[
+          ("name", name),
+          ("ctime", field("target_creation_time")),
+          ("mtime", field("target_modification_time")),
+          ("atime", field("target_access_time")),
+        ],
+      );
+      if let Ok(size) = field("target_size").parse::<u64>() {
+        target_file.insert("size".to_string(), json!(size));
+      }
+      if let Some(parent) = parent {
+        target_file.insert("parent_directory_ref".to_string(), parent["id"].clone());
+        objects.push(parent);
+      }
+      let target_file = Value::Object(target_file);
+      relationships.push(relationship(&lnk_file, "refers-to", &target_file, &time));
+      objects.push(target_file.clone());
+      Some(target_file)
+    },
+  };
+
+  if field("net_name").starts_with("\\\\") {
+    let share = directory(field("net_name"));
+    relationships.push(relationship(&lnk_file, "refers-to", &share, &time));
+    objects.push(share);
+  }
+
+  if !field("command_line").is_empty() {
+    let mut process = Map::new();
+    process.insert("type".to_string(), json!("process"));
+    process.insert("spec_version".to_string(), json!("2.1"));
+    let image_ref = target_file.as_ref().map(|target_file| target_file["id"].clone());
+    process.insert(
+      "id".to_string(),
+      json!(id(
+        "process",
+        &json!({ "command_line": field("command_line"), "cwd": field("working_dir"), "image_ref": image_ref })
+      )),
+    );
+    insert(&mut process, &[("command_line", field("command_line")), ("cwd", field("working_dir"))]);
+    if let Some(image_ref) = image_ref {
+      process.insert("image_ref".to_string(), image_ref);
+    }
+    let process = Value::Object(process);
+    relationships.push(relationship(&lnk_file, "starts", &process, &time));
+    objects.push(process);
+  }
+
+  objects.extend(relationships);
+  objects
+}
+
+/// The objects of the records written so far, every object is kept once.
+#[derive(Default)]
+pub struct Bundle {
+  objects: Vec<Value>,
+  ids: HashSet<String>,
+}
+
+impl Bundle {
+  pub fn add(&mut self, record: &Record) {
+    for object in objects(record) {
+      if self.ids.insert(object["id"].as_str().unwrap_or_default().to_string()) {
+        self.objects.push(object);
+      }
+    }
+  }
+
+  /// The bundle, its ID is derived from the IDs of the objects it holds.
+  pub fn to_json(&self) -> Value {
+    let mut ids: Vec<&String> = self.ids.iter().collect();
+    ids.sort();
+    json!({
+      "type": "bundle",
+      "id": id("bundle", &json!(ids)),
+      "objects": self.objects,
+    })
+  }
+}
@@ -0,0 +1,332 @@
+//! Interactive explorer of the structure of a LNK file.
+//!
+//! The left pane is the tree of the spans of the file (see [`lnk::spans::tree`]), the right pane shows the decoded
+//! values of the selected node and a hex view of its bytes. Only the keys and the colors of a plain terminal are
+//! used so it works over SSH.
+
+use crate::error::CliError;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lnk::{
+  extra_data::BLOCK_NAMES,
+  spans::{self, Node},
+  Lnk,
+};
+use ratatui::{
+  crossterm::event::{self, Event, KeyCode, KeyEventKind},
+  layout::{Constraint, Layout},
+  style::{Modifier, Style},
+  text::{Line, Span},
+  widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+  DefaultTerminal, Frame,
+};
+use serde_json::Value;
+use std::{
+  collections::HashSet,
+  fs,
+  io::{self, ErrorKind, IsTerminal},
+};
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Names of the structures of the specification.
+const SECTION_NAMES: &[(&str, &str)] = &[
+  ("shell_link_header", "ShellLinkHeader"),
+  ("link_target_id_list", "LinkTargetIDList"),
+  ("link_info", "LinkInfo"),
+  ("link_info.volume_id", "VolumeID"),
+  ("link_info.common_network_relative_link", "CommonNetworkRelativeLink"),
+  ("extra_data.terminal_block", "TerminalBlock"),
+];
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("tui")
+    .about("Explore the structure of a LNK file in an interactive terminal UI")
+    .after_help(
+      "KEYS:\n    Up/Down, j/k          select a node\n    Right/Enter, l        expand the node\n    \
+       Left, h               collapse the node or select its parent\n    PageUp/PageDown       scroll the value or the bytes\n    \
+       Tab                   switch the pane PageUp/PageDown scroll\n    q, Esc                quit",
+    )
+    .arg(Arg::with_name("LNK").required(true).help("The LNK file to explore"))
+}
+
+struct Explorer {
+  data: Vec<u8>,
+  /// The serialized record, the decoded values are looked up in it by span path.
+  record: Value,
+  sections: Vec<Node>,
+  expanded: HashSet<String>,
+  selected: usize,
+  /// Scroll offsets of the value and the bytes of the selected node.
+  scroll: [u16; 2],
+  /// The pane PageUp/PageDown scroll, 0 for the value and 1 for the bytes.
+  scrolled_pane: usize,
+}
+
+/// The nodes shown in the tree, with their depth and parent.
+fn visible<'a>(
+  nodes: &'a [Node],
+  parent: Option<&'a Node>,
+  depth: usize,
+  expanded: &HashSet<String>,
+  rows: &mut Vec<(usize, &'a Node, Option<&'a Node>)>,
+) {
+  for node in nodes {
+    rows.push((depth, node, parent));
+    if expanded.contains(&node.path) {
+      visible(&node.children, Some(node), depth + 1, expanded, rows);
+    }
+  }
+}
+
+/// The value at a span path in the serialized record, array items are addressed by their index.
+fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+  let path = path.replace("link_target_id_list.shell_items", "link_target_id_list.id_list");
+  path.split('.').try_fold(record, |value, component| match value {
+    Value::Object(map) => map.get(component),
+    Value::Array(items) => items.get(component.parse::<usize>().ok()?),
+    _ => None,
+  })
+}
+
+impl Explorer {
+  fn rows(&self) -> Vec<(usize, &Node, Option<&Node>)> {
+    let mut rows = vec![];
+    visible(&self.sections, None, 0, &self.expanded, &mut rows);
+    rows
+  }
+
+  fn label(&self, node: &Node, parent: Option<&Node>) -> String {
+    if let Some((_, name)) = SECTION_NAMES.iter().find(|(path, _)| *path == node.path) {
+      return name.to_string();
+    }
+    let index = |prefix: &str| node.path.strip_prefix(prefix).filter(|index| !index.contains('.'));
+    if let Some(index) = index("extra_data.blocks.") {
+      let signature = self
+        .data
+        .get(node.span.offset + 4..node.span.offset + 8)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .unwrap_or_default();
+      return match BLOCK_NAMES.iter().find(|(s, _)| *s == signature) {
+        Some((_, name)) => format!("ExtraData {} {}", index, name),
+        None => format!("ExtraData {} 0x{:08X}", index, signature),
+      };
+    }
+    if let Some(index) = index("link_target_id_list.shell_items.") {
+      return format!("ShellItem {}", index);
+    }
+    match parent.and_then(|parent| node.path.strip_prefix(&format!("{}.", parent.path))) {
+      Some(name) => name.to_string(),
+      // Decoded ExtraData fields, `extra_data.extra_data_blocks.0.Tracker.machine_id` is `Tracker.machine_id`.
+      None if parent.is_some() => match node.path.split_once(".extra_data_blocks.") {
+        Some((_, rest)) => rest.split_once('.').map(|(_, name)| name).unwrap_or(rest).to_string(),
+        None => node.path.rsplit('.').next().unwrap_or_default().to_string(),
+      },
+      None => node.path.clone(),
+    }
+  }
+
+  /// The decoded value of a node. A node that is not in the record (an ExtraData block) shows the value its
+  /// fields belong to, a field that is not in the record its raw integer value.
+  fn value_lines(&self, node: &Node) -> Vec<Line<'static>> {
+    let mut lines = vec![
+      Line::from(node.path.clone()),
+      Line::from(format!("offset 0x{:X} ({}), length {}", node.span.offset, node.span.offset, node.span.length)),
+      Line::from(""),
+    ];
+    let value = lookup(&self.record, &node.path).or_else(|| {
+      node.children.iter().find_map(|child| {
+        let (parent, _) = child.path.rsplit_once('.')?;
+        lookup(&self.record, parent).filter(|_| child.path.contains(".extra_data_blocks."))
+      })
+    });
+    let bytes = &self.data[node.span.offset..node.span.offset + node.span.length];
+    match value {
+      Some(value @ (Value::Object(_) | Value::Array(_))) => lines.extend(
+        serde_json::to_string_pretty(value)
+          .unwrap_or_default()
+          .lines()
+          .map(|line| Line::from(line.to_string())),
+      ),
+      Some(Value::String(s)) => lines.push(Line::from(s.clone())),
+      Some(value) => lines.push(Line::from(value.to_string())),
+      None if matches!(bytes.len(), 1 | 2 | 4 | 8) => {
+        let mut le = [0; 8];
+        le[..bytes.len()].copy_from_slice(bytes);
+        let n = u64::from_le_bytes(le);
+        lines.push(Line::from(format!("{} (0x{:0width$X})", n, n, width = bytes.len() * 2)));
+      },
+      None => lines.push(Line::from("(not decoded)")),
+    }
+    lines
+  }
+
+  /// Rows of 16 bytes covering the node, its bytes are highlighted.
+  fn hex_lines(&self, node: &Node) -> Vec<Line<'static>> {
+    let (start, end) = (node.span.offset, node.span.offset + node.span.length);
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+    let mut lines = vec![];
+    let mut row = start / BYTES_PER_ROW * BYTES_PER_ROW;
+    while row < end.max(start + 1) && row < self.data.len() {
+      let mut spans = vec![Span::raw(format!("{:08X}  ", row))];
+      let mut ascii = vec![];
+      for position in row..row + BYTES_PER_ROW {
+        let style = match (start..end).contains(&position) {
+          true => highlight,
+          false => Style::default(),
+        };
+        match self.data.get(position) {
+          Some(byte) => {
+            spans.push(Span::styled(format!("{:02X}", byte), style));
+            let c = if byte.is_ascii_graphic() || *byte == b' ' {
+              *byte as char
+            } else {
+              '.'
+            };
+            ascii.push(Span::styled(c.to_string(), style));
+          },
+          None => spans.push(Span::raw("  ")),
+        }
+        spans.push(Span::raw(if position % 8 == 7 { "  " } else { " " }));
+      }
+      spans.extend(ascii);
+      lines.push(Line::from(spans));
+      row += BYTES_PER_ROW;
+    }
+    lines
+  }
+
+  fn draw(&self, frame: &mut Frame) {
+    let [main, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+    let [tree, details] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main);
+    let [values, hex] = Layout::vertical([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(details);
+
+    let rows = self.rows();
+    let items: Vec<ListItem> = rows
+      .iter()
+      .map(|(depth, node, parent)| {
+        let marker = match (node.children.is_empty(), self.expanded.contains(&node.path)) {
+          (true, _) => "  ",
+          (false, true) => "- ",
+          (false, false) => "+ ",
+        };
+        ListItem::new(format!("{}{}{}", "  ".repeat(*depth), marker, self.label(node, *parent)))
+      })
+      .collect();
+    let mut state = ListState::default().with_selected(Some(self.selected));
+    frame.render_stateful_widget(
+      List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Structure"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+      tree,
+      &mut state,
+    );
+
+    let title = |title: &str, pane: usize| match pane == self.scrolled_pane {
+      true => format!("{} (PgUp/PgDn)", title),
+      false => title.to_string(),
+    };
+    if let Some((_, node, _)) = rows.get(self.selected) {
+      frame.render_widget(
+        Paragraph::new(self.value_lines(node))
+          .scroll((self.scroll[0], 0))
+          .block(Block::default().borders(Borders::ALL).title(title("Value", 0))),
+        values,
+      );
+      frame.render_widget(
+        Paragraph::new(self.hex_lines(node))
+          .scroll((self.scroll[1], 0))
+          .block(Block::default().borders(Borders::ALL).title(title("Bytes", 1))),
+        hex,
+      );
+    }
+    frame.render_widget(
+      Paragraph::new(
+        "Up/Down select  Right/Enter expand  Left collapse  PgUp/PgDn scroll  Tab switch scrolled pane  q quit",
+      ),
+      help,
+    );
+  }
+
+  fn select(&mut self, selected: usize) {
+    self.selected = selected;
+    self.scroll = [0, 0];
+  }
+
+  /// Handle a key, returns false to quit.
+  fn key(&mut self, code: KeyCode) -> bool {
+    let rows = self.rows();
+    let count = rows.len();
+    let Some(&(_, node, parent)) = rows.get(self.selected) else {
+      return false;
+    };
+    let (path, has_children) = (node.path.clone(), !node.children.is_empty());
+    let parent_position = parent.and_then(|parent| rows.iter().position(|(_, n, _)| n.path == parent.path));
+    match code {
+      KeyCode::Char('q') | KeyCode::Esc => return false,
+      KeyCode::Down | KeyCode::Char('j') if self.selected + 1 < count => self.select(self.selected + 1),
+      KeyCode::Up | KeyCode::Char('k') if self.selected > 0 => self.select(self.selected - 1),
+      KeyCode::Home => self.select(0),
+      KeyCode::End => self.select(count - 1),
+      // Enter toggles the node, Right only expands it.
+      KeyCode::Enter if has_children && self.expanded.contains(&path) => {
+        self.expanded.remove(&path);
+      },
+      KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') if has_children => {
+        self.expanded.insert(path);
+      },
+      // Left collapses the node, or goes to its parent when it is collapsed.
+      KeyCode::Left | KeyCode::Char('h') if self.expanded.contains(&path) => {
+        self.expanded.remove(&path);
+      },
+      KeyCode::Left | KeyCode::Char('h') => {
+        if let Some(position) = parent_position {
+          self.select(position);
+        }
+      },
+      KeyCode::PageDown => self.scroll[self.scrolled_pane] = self.scroll[self.scrolled_pane].saturating_add(8),
+      KeyCode::PageUp => self.scroll[self.scrolled_pane] = self.scroll[self.scrolled_pane].saturating_sub(8),
+      KeyCode::Tab => self.scrolled_pane = 1 - self.scrolled_pane,
+      _ => {},
+    }
+    true
+  }
+
+  fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+    loop {
+      terminal.draw(|frame| self.draw(frame))?;
+      if let Event::Key(key) = event::read()? {
+        if key.kind == KeyEventKind::Press && !self.key(key.code) {
+          return Ok(());
+        }
+      }
+    }
+  }
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  if !io::stdout().is_terminal() || !io::stdin().is_terminal() {
+    return Err(CliError::Usage(String::from("tui needs an interactive terminal")));
+  }
+  let path = args.value_of("LNK").unwrap();
+  let data = fs::read(path)?;
+  let lnk = Lnk::from_buffer_with_spans(&data).map_err(|e| match e.kind() {
+    ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+      eprintln!("Did not parse '{}' correctly. ERROR : '{}'", path, e);
+      CliError::ParseFailed(1)
+    },
+    _ => CliError::Io(e),
+  })?;
+  let mut explorer = Explorer {
+    sections: spans::tree(lnk.spans.as_ref().unwrap()),
+    record: serde_json::to_value(&lnk).unwrap_or_default(),
+    data,
+    expanded: HashSet::new(),
+    selected: 0,
+    scroll: [0, 0],
+    scrolled_pane: 1,
+  };
+  let mut terminal = ratatui::init();
+  let result = explorer.run(&mut terminal);
+  ratatui::restore();
+  Ok(result?)
+}
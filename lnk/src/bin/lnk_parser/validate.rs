@@ -0,0 +1,88 @@
+use crate::error::{self, CliError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use glob::glob;
+use lnk::Lnk;
+use serde_json::json;
+use std::fs;
+
+pub fn subcommand() -> App<'static, 'static> {
+  SubCommand::with_name("validate")
+    .about("Check that LNK files parse and follow the specification, prints every deviation with its offset")
+    .arg(
+      Arg::with_name("PATH")
+        .required(true)
+        .multiple(true)
+        .help("Path(s) to LNK files - accepts glob"),
+    )
+    .arg(
+      Arg::with_name("output-format")
+        .long("--output-format")
+        .takes_value(true)
+        .possible_values(&["text", "jsonl"])
+        .default_value("text")
+        .help("'text' prints OK/FAIL lines followed by the deviations, 'jsonl' prints one object per file"),
+    )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), CliError> {
+  let jsonl = args.value_of("output-format") == Some("jsonl");
+  let mut count = 0;
+  let mut failed = 0;
+  let mut invalid = 0;
+  let mut unreadable = 0;
+  for pattern in args.values_of("PATH").unwrap() {
+    let paths = glob(pattern).map_err(|e| CliError::Usage(format!("Invalid glob pattern '{}': {}", pattern, e)))?;
+    for path in paths.flatten() {
+      count += 1;
+      // A file that can not be read is reported and the remaining files are still checked.
+      let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => {
+          if error::is_read_error(&e) {
+            unreadable += 1;
+          } else {
+            failed += 1;
+          }
+          match jsonl {
+            true => println!("{}", json!({"path": path, "parse_error": e.to_string(), "deviations": []})),
+            false => println!("FAIL {}: {}", path.display(), e),
+          }
+          continue;
+        },
+      };
+      let error = Lnk::from_buffer(&data).err().map(|e| e.to_string());
+      let deviations = lnk::validate(&data);
+      if error.is_some() {
+        failed += 1;
+      } else if !deviations.is_empty() {
+        invalid += 1;
+      }
+
+      if jsonl {
+        let line = json!({"path": path, "parse_error": error, "deviations": deviations});
+        println!("{}", line);
+        continue;
+      }
+      match &error {
+        Some(e) => println!("FAIL {}: {}", path.display(), e),
+        None if deviations.is_empty() => println!("OK {}", path.display()),
+        None => println!("INVALID {}", path.display()),
+      }
+      for deviation in deviations {
+        println!("  {}", deviation);
+      }
+    }
+  }
+
+  if count == 0 {
+    Err(CliError::NoFiles)
+  } else if unreadable > 0 {
+    Err(CliError::ReadFailed(unreadable))
+  } else if failed > 0 {
+    Err(CliError::ParseFailed(failed))
+  } else if invalid > 0 {
+    Err(CliError::Invalid(invalid))
+  } else {
+    Ok(())
+  }
+}
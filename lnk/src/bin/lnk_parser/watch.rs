@@ -0,0 +1,215 @@
+use crate::{
+  error::CliError,
+  output::RecordWriter,
+  parse::{self, Input},
+};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use lnk::{
+  filter::Filter,
+  resolve::Resolver,
+  rules::RuleSet,
+  triage::{self, Artifact, ArtifactKind},
+};
+use std::{
+  collections::HashMap,
+  fs,
+  io::{self, ErrorKind},
+  os::unix::fs::MetadataExt,
+  path::{Path, PathBuf},
+  thread,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the inotify queue is polled while files are waiting for their quiet period.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Margin for the granularity of the file system times when looking for the files changed since a time.
+const TIME_MARGIN: Duration = Duration::from_secs(1);
+
+fn is_lnk(path: &Path) -> bool {
+  path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"))
+}
+
+/// Watches a directory tree and reports the LNK files that were not written to for the debounce period.
+struct Watcher {
+  inotify: Inotify,
+  root: WatchDescriptor,
+  dirs: HashMap<WatchDescriptor, PathBuf>,
+  /// LNK files that were created or modified, with the time of the last change.
+  pending: HashMap<PathBuf, Instant>,
+  /// When the event queue was last read to the end, the events after it are lost when the queue overflows.
+  drained: SystemTime,
+}
+
+impl Watcher {
+  fn new(root: &Path) -> io::Result<Self> {
+    let inotify = Inotify::init()?;
+    let root_descriptor = inotify.watches().add(root, Self::mask())?;
+    let mut watcher = Self {
+      inotify,
+      root: root_descriptor.clone(),
+      dirs: HashMap::from([(root_descriptor, root.to_path_buf())]),
+      pending: HashMap::new(),
+      drained: SystemTime::now(),
+    };
+    watcher.add_subdirs(root, None);
+    Ok(watcher)
+  }
+
+  fn mask() -> WatchMask {
+    WatchMask::CREATE | WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO
+  }
+
+  /// Watch the subdirectories of `dir`. `queue_changed_since` queues the LNK files in them whose status changed since
+  /// that time (created, written or moved in), a directory moved or copied into the tree (a new profile for example)
+  /// does not generate events for its content.
+  fn add_subdirs(&mut self, dir: &Path, queue_changed_since: Option<SystemTime>) {
+    let entries = match fs::read_dir(dir) {
+      Ok(entries) => entries,
+      Err(e) => {
+        eprintln!("Can not watch '{}'. ERROR : '{}'", dir.display(), e);
+        return;
+      },
+    };
+    for entry in entries.flatten() {
+      let path = entry.path();
+      match entry.file_type() {
+        Ok(file_type) if file_type.is_dir() => self.add_dir(&path, queue_changed_since),
+        Ok(file_type) if file_type.is_file() && is_lnk(&path) => {
+          let changed = entry
+            .metadata()
+            .map(|metadata| UNIX_EPOCH + Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32));
+          if queue_changed_since.is_some_and(|since| changed.is_ok_and(|changed| changed >= since)) {
+            self.pending.insert(path, Instant::now());
+          }
+        },
+        _ => {},
+      }
+    }
+  }
+
+  /// Watch `dir` and its subdirectories, adding a directory that is already watched keeps its watch.
+  fn add_dir(&mut self, dir: &Path, queue_changed_since: Option<SystemTime>) {
+    match self.inotify.watches().add(dir, Self::mask()) {
+      Ok(descriptor) => {
+        self.dirs.insert(descriptor, dir.to_path_buf());
+        self.add_subdirs(dir, queue_changed_since);
+      },
+      Err(e) => eprintln!("Can not watch '{}'. ERROR : '{}'", dir.display(), e),
+    }
+  }
+
+  /// Read the queued events without blocking. Returns `false` once the watched directory is gone.
+  fn read_events(&mut self) -> io::Result<bool> {
+    let mut buffer = [0; 4096];
+    loop {
+      let events = match self.inotify.read_events(&mut buffer) {
+        Ok(events) => events,
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+          self.drained = SystemTime::now();
+          return Ok(true);
+        },
+        Err(e) => return Err(e),
+      };
+      let mut new_dirs = vec![];
+      let mut overflowed = false;
+      for event in events {
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+          overflowed = true;
+          continue;
+        }
+        if event.mask.contains(EventMask::IGNORED) {
+          if event.wd == self.root {
+            return Ok(false);
+          }
+          self.dirs.remove(&event.wd);
+          continue;
+        }
+        let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+          continue;
+        };
+        let path = dir.join(name);
+        if event.mask.contains(EventMask::ISDIR) {
+          if event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+            new_dirs.push(path);
+          }
+        } else if is_lnk(&path) {
+          self.pending.insert(path, Instant::now());
+        }
+      }
+      for dir in new_dirs {
+        self.add_dir(&dir, Some(UNIX_EPOCH));
+      }
+      // Events were dropped, scan the tree again for the directories and LNK files they were about.
+      if overflowed {
+        eprintln!("The inotify queue overflowed, scanning '{}' again", self.dirs[&self.root].display());
+        let root = self.dirs[&self.root].clone();
+        let since = self.drained - TIME_MARGIN;
+        self.add_subdirs(&root, Some(since));
+      }
+    }
+  }
+
+  /// Remove and return the files that did not change for `debounce`, in path order.
+  fn ready(&mut self, debounce: Duration) -> Vec<PathBuf> {
+    let mut ready: Vec<PathBuf> = self
+      .pending
+      .iter()
+      .filter(|(_, changed)| changed.elapsed() >= debounce)
+      .map(|(path, _)| path.clone())
+      .collect();
+    ready.sort();
+    for path in &ready {
+      self.pending.remove(path);
+    }
+    ready
+  }
+}
+
+/// Parse the LNK files created or modified under `dir` until the directory is removed. A file is parsed once it was
+/// not written to for `debounce`, so a shortcut that is still being copied is not read half written. Every record is
+/// flushed as soon as it is written.
+pub fn run(
+  dir: &str,
+  debounce: Duration,
+  filter: Option<&Filter>,
+  resolver: Option<&Resolver>,
+  rules: Option<&RuleSet>,
+  writer: &mut RecordWriter,
+) -> Result<(), CliError> {
+  let mut watcher = Watcher::new(Path::new(dir))?;
+  eprintln!("Watching '{}' for LNK files", dir);
+
+  while watcher.read_events()? {
+    for path in watcher.ready(debounce) {
+      let input = Input {
+        artifact: Artifact {
+          kind: ArtifactKind::Lnk,
+          user: triage::user_from_path(&path.to_string_lossy()),
+          path,
+        },
+        member: None,
+      };
+      // A malformed shortcut is reported and the watch goes on, only output errors end it.
+      match parse::parse_artifact(&input).and_then(|records| parse::prepare(records, rules, filter, resolver)) {
+        Ok(records) => {
+          for record in records {
+            match writer.write_record(&record) {
+              Err(e) if e.kind() == ErrorKind::InvalidData => {
+                eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e)
+              },
+              result => result?,
+            }
+          }
+          writer.flush()?;
+        },
+        // The file was removed or renamed again before its quiet period ended.
+        Err(e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => eprintln!("Did not parse '{}' correctly. ERROR : '{}'", input.artifact.path.display(), e),
+      }
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+  writer.finish()?;
+  Ok(())
+}
@@ -0,0 +1,87 @@
+//! Carve LNK files out of arbitrary data (disk images, unallocated space, memory dumps, ...).
+//!
+//! The data is read in chunks, every occurrence of [`LNK_SIGNATURE`] is parsed and the LNK files that parse are
+//! returned with their offset.
+
+use crate::{jump_list::LNK_SIGNATURE, Lnk};
+use std::io::{Cursor, Read, Result};
+
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// LNK files larger than this are not carved, it bounds the data kept after a signature.
+pub const MAX_LNK_SIZE: usize = 1024 * 1024;
+
+/// A LNK file found in the data.
+#[derive(Debug)]
+pub struct CarvedLnk {
+  /// Offset of the LNK file from the start of the data.
+  pub offset: u64,
+  pub lnk: Lnk,
+  pub data: Vec<u8>,
+}
+
+/// Scan `reader` for LNK files and call `found` for each one, in offset order.
+/// The bytes of a carved file are not scanned again, so a LNK file is only reported once.
+/// # Example
+/// ```
+/// use lnk::carve::carve;
+///
+/// let mut image = vec![0xFF; 1000];
+/// image.extend(std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap());
+/// image.extend(vec![0xFF; 1000]);
+/// let mut offsets = vec![];
+/// carve(image.as_slice(), |carved| offsets.push(carved.offset)).unwrap();
+/// assert_eq!(offsets, vec![1000]);
+/// ```
+pub fn carve<R: Read, F: FnMut(CarvedLnk)>(mut reader: R, mut found: F) -> Result<()> {
+  let mut buffer: Vec<u8> = vec![];
+  // Offset of `buffer[0]` in the data.
+  let mut base = 0u64;
+  // Carved LNK data is skipped up to this offset.
+  let mut next = 0u64;
+  let mut eof = false;
+  let mut chunk = vec![0; CHUNK_SIZE];
+
+  loop {
+    // Keep MAX_LNK_SIZE bytes after the last scanned position so a LNK file is never cut.
+    while !eof && buffer.len() < CHUNK_SIZE + MAX_LNK_SIZE {
+      match reader.read(&mut chunk)? {
+        0 => eof = true,
+        n => buffer.extend_from_slice(&chunk[..n]),
+      }
+    }
+    let scan_end = if eof { buffer.len() } else { buffer.len() - MAX_LNK_SIZE };
+
+    let mut position = next.saturating_sub(base) as usize;
+    while position < scan_end {
+      let window_end = (scan_end + LNK_SIGNATURE.len() - 1).min(buffer.len());
+      let found_at = match buffer[position..window_end]
+        .windows(LNK_SIGNATURE.len())
+        .position(|w| w == LNK_SIGNATURE)
+      {
+        Some(found_at) => position + found_at,
+        None => break,
+      };
+      let candidate = &buffer[found_at..(found_at + MAX_LNK_SIZE).min(buffer.len())];
+      let mut cursor = Cursor::new(candidate);
+      match Lnk::from_reader(&mut cursor) {
+        Ok(lnk) => {
+          let size = cursor.position() as usize;
+          found(CarvedLnk {
+            offset: base + found_at as u64,
+            lnk,
+            data: candidate[..size].to_vec(),
+          });
+          position = found_at + size.max(1);
+        },
+        Err(_) => position = found_at + 1,
+      }
+    }
+
+    next = next.max(base + position as u64);
+    if eof {
+      return Ok(());
+    }
+    buffer.drain(..scan_end);
+    base += scan_end as u64;
+  }
+}
@@ -0,0 +1,221 @@
+//! Rebuild the command line Windows runs when a shortcut is opened.
+//!
+//! The target, the arguments and the working directory are expanded against a [`Profile`] of environment variables
+//! and the command line is split the way
+//! [CommandLineToArgvW](https://learn.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw)
+//! does, so `argv` is what the target process receives.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Environment variables used to expand `%NAME%` references, names are case-insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+  variables: HashMap<String, String>,
+}
+
+impl Profile {
+  /// A profile without any variable.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The variables of a default Windows installation on `C:`. The profile variables (`USERPROFILE`, `APPDATA`, ...)
+  /// are only set when the user is known.
+  pub fn windows_defaults(user: Option<&str>) -> Self {
+    let mut profile = Self::new();
+    for (name, value) in [
+      ("SystemDrive", "C:"),
+      ("SystemRoot", "C:\\Windows"),
+      ("windir", "C:\\Windows"),
+      ("ComSpec", "C:\\Windows\\system32\\cmd.exe"),
+      ("ProgramFiles", "C:\\Program Files"),
+      ("ProgramFiles(x86)", "C:\\Program Files (x86)"),
+      ("ProgramW6432", "C:\\Program Files"),
+      ("CommonProgramFiles", "C:\\Program Files\\Common Files"),
+      ("CommonProgramFiles(x86)", "C:\\Program Files (x86)\\Common Files"),
+      ("ProgramData", "C:\\ProgramData"),
+      ("ALLUSERSPROFILE", "C:\\ProgramData"),
+      ("PUBLIC", "C:\\Users\\Public"),
+    ] {
+      profile.set(name, value);
+    }
+    if let Some(user) = user {
+      let user_profile = format!("C:\\Users\\{}", user);
+      profile.set("USERNAME", user);
+      profile.set("HOMEDRIVE", "C:");
+      profile.set("HOMEPATH", &format!("\\Users\\{}", user));
+      profile.set("APPDATA", &format!("{}\\AppData\\Roaming", user_profile));
+      profile.set("LOCALAPPDATA", &format!("{}\\AppData\\Local", user_profile));
+      profile.set("TEMP", &format!("{}\\AppData\\Local\\Temp", user_profile));
+      profile.set("TMP", &format!("{}\\AppData\\Local\\Temp", user_profile));
+      profile.set("USERPROFILE", &user_profile);
+    }
+    profile
+  }
+
+  /// Set a variable, replacing the value of a variable with the same name in any case.
+  pub fn set(&mut self, name: &str, value: &str) {
+    self.variables.insert(name.to_uppercase(), value.to_string());
+  }
+
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.variables.get(&name.to_uppercase()).map(String::as_str)
+  }
+
+  /// Replace the `%NAME%` references like `ExpandEnvironmentStrings`, unknown variables are kept as they are.
+  /// # Example
+  /// ```
+  /// use lnk::command_line::Profile;
+  ///
+  /// let profile = Profile::windows_defaults(Some("bob"));
+  /// assert_eq!(profile.expand("%AppData%\\x.exe %UNKNOWN% 100%"), "C:\\Users\\bob\\AppData\\Roaming\\x.exe %UNKNOWN% 100%");
+  /// ```
+  pub fn expand(&self, s: &str) -> String {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('%') {
+      expanded.push_str(&rest[..start]);
+      let after = &rest[start + 1..];
+      match after.find('%') {
+        Some(end) => match self.get(&after[..end]) {
+          Some(value) if end > 0 => {
+            expanded.push_str(value);
+            rest = &after[end + 1..];
+          },
+          // Not a variable, the closing `%` may start the next reference.
+          _ => {
+            expanded.push('%');
+            expanded.push_str(&after[..end]);
+            rest = &after[end..];
+          },
+        },
+        None => {
+          expanded.push_str(&rest[start..]);
+          rest = "";
+        },
+      }
+    }
+    expanded.push_str(rest);
+    expanded
+  }
+}
+
+/// What runs when the shortcut is opened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandLine {
+  /// The quoted target followed by the arguments.
+  pub command_line: String,
+  /// The command line split the way `CommandLineToArgvW` does, `argv[0]` is the target.
+  pub argv: Vec<String>,
+  pub working_dir: Option<String>,
+}
+
+impl CommandLine {
+  /// Build the command line from the target and the arguments, both are expanded against `profile`.
+  pub fn new(target: &str, arguments: Option<&str>, working_dir: Option<&str>, profile: &Profile) -> Self {
+    let target = profile.expand(target);
+    let mut command_line = match target.contains([' ', '\t']) || target.is_empty() {
+      true => format!("\"{}\"", target),
+      false => target,
+    };
+    if let Some(arguments) = arguments.filter(|a| !a.is_empty()) {
+      command_line.push(' ');
+      command_line.push_str(&profile.expand(arguments));
+    }
+    Self {
+      argv: command_line_to_argv(&command_line),
+      command_line,
+      working_dir: working_dir.filter(|w| !w.is_empty()).map(|w| profile.expand(w)),
+    }
+  }
+}
+
+/// Split a command line like `CommandLineToArgvW`. The program name ends at the next quote when it starts with
+/// one, or at the first space or tab otherwise. In the arguments `2n` backslashes followed by a quote give `n` backslashes and
+/// toggle quoting, `2n + 1` backslashes followed by a quote give `n` backslashes and a literal quote, and every third
+/// quote of a run of quotes is literal.
+/// # Example
+/// ```
+/// use lnk::command_line::command_line_to_argv;
+///
+/// let argv = command_line_to_argv(r#""C:\Program Files\x.exe" -a "b c" d\"e "f\\" g"#);
+/// assert_eq!(argv, vec![r"C:\Program Files\x.exe", "-a", "b c", "d\"e", "f\\", "g"]);
+/// ```
+pub fn command_line_to_argv(command_line: &str) -> Vec<String> {
+  let chars: Vec<char> = command_line.chars().collect();
+  let mut argv = vec![];
+  let mut position = 0;
+
+  // The program name, backslashes have no special meaning in it.
+  let mut program = String::new();
+  let end_of_program = |c: char| c == ' ' || c == '\t';
+  if chars.first() == Some(&'"') {
+    position = 1;
+    while position < chars.len() && chars[position] != '"' {
+      program.push(chars[position]);
+      position += 1;
+    }
+    position += 1;
+  } else {
+    while position < chars.len() && !end_of_program(chars[position]) {
+      program.push(chars[position]);
+      position += 1;
+    }
+  }
+  argv.push(program);
+
+  let mut argument = String::new();
+  let mut in_argument = false;
+  // Number of quotes seen in the current quoted section, odd while inside quotes.
+  let mut quotes = 0;
+  let mut backslashes = 0;
+  while position < chars.len() {
+    let c = chars[position];
+    match c {
+      ' ' | '\t' if quotes == 0 => {
+        if in_argument {
+          argv.push(std::mem::take(&mut argument));
+          in_argument = false;
+        }
+        backslashes = 0;
+      },
+      '\\' => {
+        argument.push('\\');
+        backslashes += 1;
+        in_argument = true;
+      },
+      '"' => {
+        in_argument = true;
+        argument.truncate(argument.len() - backslashes / 2 - backslashes % 2);
+        if backslashes % 2 == 1 {
+          argument.push('"');
+        } else {
+          quotes += 1;
+        }
+        backslashes = 0;
+        while chars.get(position + 1) == Some(&'"') {
+          position += 1;
+          quotes += 1;
+          if quotes == 3 {
+            argument.push('"');
+            quotes = 0;
+          }
+        }
+        if quotes == 2 {
+          quotes = 0;
+        }
+      },
+      c => {
+        argument.push(c);
+        backslashes = 0;
+        in_argument = true;
+      },
+    }
+    position += 1;
+  }
+  if in_argument {
+    argv.push(argument);
+  }
+  argv
+}
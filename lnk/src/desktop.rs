@@ -0,0 +1,547 @@
+//! Convert shortcuts to [freedesktop.org desktop entries](https://specifications.freedesktop.org/desktop-entry-spec/latest/)
+//! and back.
+//!
+//! Windows paths are mapped to the directories their volumes and shares are mounted on with a [`MountTable`], paths
+//! that are not on a mounted volume are kept as they are in the arguments and dropped from the working directory and
+//! the icon. A shortcut to a program becomes an `Application` entry, a shortcut to a document or a folder a `Link`
+//! entry with a `file://` URL.
+
+use crate::{command_line::Profile, writer::LnkSpec, Lnk, StringData};
+use std::{
+  collections::BTreeMap,
+  fmt,
+  io::{Error, ErrorKind, Result},
+};
+use winparsingtools::file_system::FileAttributesFlags;
+
+/// Extensions of the targets Windows runs instead of opening them with another program.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+  "exe", "com", "bat", "cmd", "msi", "ps1", "vbs", "vbe", "js", "jse", "wsf", "wsh", "scr", "cpl", "hta",
+];
+
+/// Field codes of the `Exec` key, they are replaced by the files or URLs the entry is opened with.
+const FIELD_CODES: &[&str] = &[
+  "%f", "%F", "%u", "%U", "%i", "%c", "%k", "%d", "%D", "%n", "%N", "%v", "%m",
+];
+
+/// Characters that have to be quoted in an argument of the `Exec` key.
+const RESERVED: &[char] = &[
+  ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`',
+];
+
+fn invalid_input(message: String) -> Error {
+  Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data(message: String) -> Error {
+  Error::new(ErrorKind::InvalidData, message)
+}
+
+/// `C:\x`, `\\server\share\x` and `\\?\` paths with either separator, in the case they were written.
+fn windows_path(path: &str) -> String {
+  let path = path.replace('/', "\\");
+  match path.strip_prefix("\\\\?\\") {
+    Some(path) => match path.strip_prefix("UNC\\") {
+      Some(unc) => format!("\\\\{}", unc),
+      None => path.to_string(),
+    },
+    None => path,
+  }
+}
+
+fn is_absolute_windows_path(path: &str) -> bool {
+  let bytes = path.as_bytes();
+  path.starts_with("\\\\")
+    || (bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\')
+}
+
+/// Maps drive letters and UNC shares to the directories they are mounted on, in both directions. Only the paths are
+/// mapped, the file system is not accessed.
+/// # Example
+/// ```
+/// use lnk::desktop::MountTable;
+///
+/// let mounts = MountTable::new().drive('C', "/mnt/c").share(r"\\fs01\finance", "/srv/finance");
+/// assert_eq!(mounts.to_unix(r"c:\Users\bob\x.txt").as_deref(), Some("/mnt/c/Users/bob/x.txt"));
+/// assert_eq!(mounts.to_windows("/srv/finance/2021").as_deref(), Some(r"\\fs01\finance\2021"));
+/// assert_eq!(mounts.to_unix(r"D:\x.txt"), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MountTable {
+  /// Windows prefix (`C:` or `\\server\share`) and mount point without a trailing `/`.
+  mounts: Vec<(String, String)>,
+}
+
+impl MountTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn insert(mut self, prefix: String, dir: &str) -> Self {
+    let dir = match dir.trim_end_matches('/') {
+      "" => "/",
+      dir => dir,
+    };
+    self.mounts.retain(|(p, _)| !p.eq_ignore_ascii_case(&prefix));
+    self.mounts.push((prefix, dir.to_string()));
+    self
+  }
+
+  /// Map the volume `letter` to `dir`.
+  pub fn drive(self, letter: char, dir: &str) -> Self {
+    self.insert(format!("{}:", letter.to_ascii_uppercase()), dir)
+  }
+
+  /// Map the UNC share `share` (`\\server\share`) to `dir`.
+  pub fn share(self, share: &str, dir: &str) -> Self {
+    let share = windows_path(share);
+    self.insert(format!("\\\\{}", share.trim_start_matches('\\').trim_end_matches('\\')), dir)
+  }
+
+  /// Map a drive (`C`, `C:` or `C:\`) or a UNC share (`\\server\share`) to `dir`.
+  pub fn mount(self, prefix: &str, dir: &str) -> Result<Self> {
+    let windows = windows_path(prefix.trim());
+    let windows = windows.trim_end_matches('\\');
+    let letter = windows.strip_suffix(':').unwrap_or(windows);
+    if letter.len() == 1 && letter.as_bytes()[0].is_ascii_alphabetic() {
+      return Ok(self.drive(letter.chars().next().unwrap(), dir));
+    }
+    match windows.strip_prefix("\\\\").map(|unc| unc.split('\\').collect::<Vec<_>>()) {
+      Some(components) if components.len() == 2 && components.iter().all(|c| !c.is_empty()) => {
+        Ok(self.share(windows, dir))
+      },
+      _ => Err(invalid_input(format!(
+        "invalid mount '{}', expected a drive letter (C:) or a UNC share (\\\\server\\share)",
+        prefix
+      ))),
+    }
+  }
+
+  /// Read a table of Windows prefixes and mount points written in TOML:
+  /// ```toml
+  /// "C:" = "/mnt/c"
+  /// '\\fs01\finance' = "/srv/finance"
+  /// ```
+  pub fn from_toml(table: &str) -> Result<Self> {
+    let mounts: BTreeMap<String, String> =
+      toml::from_str(table).map_err(|e| invalid_input(format!("invalid mount table: {}", e)))?;
+    mounts
+      .iter()
+      .try_fold(Self::new(), |table, (prefix, dir)| table.mount(prefix, dir))
+  }
+
+  /// The path under the mount point of the volume or share of `path`, `None` when it is not mounted.
+  pub fn to_unix(&self, path: &str) -> Option<String> {
+    let path = windows_path(path);
+    let (prefix, dir) = self
+      .mounts
+      .iter()
+      .filter(|(prefix, _)| {
+        path.len() >= prefix.len()
+          && path.is_char_boundary(prefix.len())
+          && path[..prefix.len()].eq_ignore_ascii_case(prefix)
+          && matches!(path.as_bytes().get(prefix.len()), None | Some(b'\\'))
+      })
+      .max_by_key(|(prefix, _)| prefix.len())?;
+    let mut unix = dir.trim_end_matches('/').to_string();
+    for component in path[prefix.len()..].split('\\').filter(|c| !c.is_empty()) {
+      unix.push('/');
+      unix.push_str(component);
+    }
+    if unix.is_empty() {
+      unix.push('/');
+    }
+    Some(unix)
+  }
+
+  /// The Windows path of `path` when it is under a mount point, `None` otherwise.
+  pub fn to_windows(&self, path: &str) -> Option<String> {
+    let (prefix, rest) = self
+      .mounts
+      .iter()
+      .filter_map(|(prefix, dir)| {
+        let rest = match dir.as_str() {
+          "/" => path.strip_prefix('/')?,
+          dir => match path.strip_prefix(dir)? {
+            "" => "",
+            rest => rest.strip_prefix('/')?,
+          },
+        };
+        Some((prefix, dir.len(), rest))
+      })
+      .max_by_key(|(_, length, _)| *length)
+      .map(|(prefix, _, rest)| (prefix, rest))?;
+    let mut windows = prefix.clone();
+    for component in rest.split('/').filter(|c| !c.is_empty()) {
+      windows.push('\\');
+      windows.push_str(component);
+    }
+    if windows.ends_with(':') {
+      windows.push('\\');
+    }
+    Some(windows)
+  }
+}
+
+/// The `Type` of a desktop entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+  /// Runs `Exec`.
+  Application,
+  /// Opens `URL`.
+  Link,
+}
+
+impl fmt::Display for EntryType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EntryType::Application => write!(f, "Application"),
+      EntryType::Link => write!(f, "Link"),
+    }
+  }
+}
+
+/// The keys of the `[Desktop Entry]` group a shortcut maps to. The `Display` implementation writes the entry file.
+/// # Example
+/// ```
+/// use lnk::desktop::{DesktopEntry, MountTable};
+///
+/// let entry = DesktopEntry::parse("[Desktop Entry]\nType=Application\nName=Edit\nExec=\"/mnt/c/Program Files/x.exe\" %f\n").unwrap();
+/// assert_eq!(entry.exec, vec!["/mnt/c/Program Files/x.exe"]);
+/// let spec = entry.to_lnk_spec(&MountTable::new().drive('C', "/mnt/c")).unwrap();
+/// assert_eq!(spec.target_full_path, r"C:\Program Files\x.exe");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopEntry {
+  pub entry_type: EntryType,
+  pub name: String,
+  pub comment: Option<String>,
+  /// The program and its arguments, without the field codes.
+  pub exec: Vec<String>,
+  /// The working directory.
+  pub path: Option<String>,
+  pub icon: Option<String>,
+  pub url: Option<String>,
+}
+
+/// Escape a value of type string: backslashes and control characters, and a leading space.
+fn escape_string(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for (i, c) in value.chars().enumerate() {
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\t' => escaped.push_str("\\t"),
+      '\r' => escaped.push_str("\\r"),
+      ' ' if i == 0 => escaped.push_str("\\s"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+fn unescape_string(value: &str) -> String {
+  let mut unescaped = String::with_capacity(value.len());
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => match chars.next() {
+        Some('s') => unescaped.push(' '),
+        Some('n') => unescaped.push('\n'),
+        Some('t') => unescaped.push('\t'),
+        Some('r') => unescaped.push('\r'),
+        Some(c) => unescaped.push(c),
+        None => unescaped.push('\\'),
+      },
+      c => unescaped.push(c),
+    }
+  }
+  unescaped
+}
+
+/// Quote an argument of the `Exec` key, before the value is escaped as a string.
+fn quote_exec_argument(argument: &str) -> String {
+  let argument = argument.replace('%', "%%");
+  if !argument.is_empty() && !argument.contains(RESERVED) {
+    return argument;
+  }
+  let mut quoted = String::from("\"");
+  for c in argument.chars() {
+    if matches!(c, '"' | '`' | '$' | '\\') {
+      quoted.push('\\');
+    }
+    quoted.push(c);
+  }
+  quoted.push('"');
+  quoted
+}
+
+/// Split the unescaped `Exec` value into the arguments, the field codes are removed.
+fn split_exec(exec: &str) -> Result<Vec<String>> {
+  let mut arguments = vec![];
+  let mut chars = exec.chars().peekable();
+  loop {
+    while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+    let Some(&first) = chars.peek() else { break };
+    let mut argument = String::new();
+    if first == '"' {
+      chars.next();
+      loop {
+        match chars.next() {
+          Some('"') => break,
+          Some('\\') => argument.extend(chars.next()),
+          Some(c) => argument.push(c),
+          None => return Err(invalid_data(format!("unterminated quote in Exec '{}'", exec))),
+        }
+      }
+    } else {
+      while let Some(c) = chars.next_if(|c| *c != ' ' && *c != '\t') {
+        argument.push(c);
+      }
+    }
+    if first == '"' || !FIELD_CODES.contains(&argument.as_str()) {
+      arguments.push(argument.replace("%%", "%"));
+    }
+  }
+  Ok(arguments)
+}
+
+/// Quote an argument so `CommandLineToArgvW` gives it back.
+fn quote_windows_argument(argument: &str) -> String {
+  if !argument.is_empty() && !argument.contains([' ', '\t', '"']) {
+    return argument.to_string();
+  }
+  let mut quoted = String::from("\"");
+  let mut backslashes = 0;
+  for c in argument.chars() {
+    match c {
+      '\\' => backslashes += 1,
+      '"' => {
+        quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+        backslashes = 0;
+      },
+      _ => {
+        quoted.push_str(&"\\".repeat(backslashes));
+        backslashes = 0;
+      },
+    }
+    if c != '\\' {
+      quoted.push(c);
+    }
+  }
+  quoted.push_str(&"\\".repeat(backslashes * 2));
+  quoted.push('"');
+  quoted
+}
+
+/// Percent-encode a path for a `file://` URL.
+fn file_url(path: &str) -> String {
+  let mut url = String::from("file://");
+  for byte in path.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => url.push(byte as char),
+      byte => url.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  url
+}
+
+/// The path of a `file://` URL, `None` for other URLs.
+fn file_url_path(url: &str) -> Option<String> {
+  let path = url.strip_prefix("file://")?;
+  let path = path.strip_prefix("localhost").unwrap_or(path);
+  let mut bytes = vec![];
+  let mut rest = path.as_bytes();
+  while let Some((&byte, tail)) = rest.split_first() {
+    let decoded = match (byte, tail) {
+      (b'%', [high, low, ..]) => std::str::from_utf8(&[*high, *low])
+        .ok()
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+      _ => None,
+    };
+    match decoded {
+      Some(decoded) => {
+        bytes.push(decoded);
+        rest = &tail[2..];
+      },
+      None => {
+        bytes.push(byte);
+        rest = tail;
+      },
+    }
+  }
+  String::from_utf8(bytes).ok()
+}
+
+/// The last component of a Windows or Unix path without its extension.
+fn file_stem(path: &str) -> &str {
+  let name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+  match name.rsplit_once('.') {
+    Some((stem, _)) if !stem.is_empty() => stem,
+    _ => name,
+  }
+}
+
+impl DesktopEntry {
+  /// Map a shortcut to a desktop entry. The target is expanded with the variables of a default Windows installation
+  /// and has to be on a mounted volume or share.
+  pub fn from_lnk(lnk: &Lnk, mounts: &MountTable) -> Result<Self> {
+    let user = lnk.lnk_file_metadata.as_ref().and_then(|metadata| metadata.user.as_deref());
+    let profile = Profile::windows_defaults(user);
+    let command_line = lnk
+      .command_line(&profile)
+      .ok_or_else(|| invalid_input(String::from("the shortcut has no target path")))?;
+    let target = &command_line.argv[0];
+    let unix_target = mounts
+      .to_unix(target)
+      .ok_or_else(|| invalid_input(format!("the target '{}' is not on a mounted volume or share", target)))?;
+    let string_data = |s: &Option<StringData>| s.as_ref().map(|s| s.to_string()).filter(|s| !s.is_empty());
+
+    let name = lnk
+      .lnk_file_metadata
+      .as_ref()
+      .map(|metadata| file_stem(&metadata.full_path))
+      .filter(|name| !name.is_empty())
+      .unwrap_or_else(|| file_stem(target))
+      .to_string();
+    let extension = target.rsplit_once('.').map(|(_, e)| e.to_lowercase()).unwrap_or_default();
+    let is_directory = lnk
+      .shell_link_header
+      .file_attr
+      .iter()
+      .any(|attribute| matches!(attribute, FileAttributesFlags::DIRECTORY));
+    let executable = EXECUTABLE_EXTENSIONS.contains(&extension.as_str()) && !is_directory;
+
+    let mut entry = Self {
+      entry_type: EntryType::Link,
+      name,
+      comment: string_data(&lnk.name_string),
+      exec: vec![],
+      path: command_line.working_dir.as_deref().and_then(|dir| mounts.to_unix(dir)),
+      icon: string_data(&lnk.icon_location)
+        .map(|icon| profile.expand(&icon))
+        .and_then(|icon| mounts.to_unix(&icon)),
+      url: None,
+    };
+    if executable || command_line.argv.len() > 1 {
+      entry.entry_type = EntryType::Application;
+      entry.exec.push(unix_target);
+      entry.exec.extend(
+        command_line.argv[1..]
+          .iter()
+          .map(|argument| match is_absolute_windows_path(argument) {
+            true => mounts.to_unix(argument).unwrap_or_else(|| argument.clone()),
+            false => argument.clone(),
+          }),
+      );
+    } else {
+      entry.url = Some(file_url(&unix_target));
+    }
+    Ok(entry)
+  }
+
+  /// Read the `[Desktop Entry]` group of a desktop entry file, localized keys are ignored.
+  pub fn parse(data: &str) -> Result<Self> {
+    let mut keys = BTreeMap::new();
+    let mut in_entry = false;
+    for line in data.lines().map(str::trim) {
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      if line.starts_with('[') {
+        in_entry = line == "[Desktop Entry]";
+        continue;
+      }
+      if let Some((key, value)) = line.split_once('=').filter(|_| in_entry) {
+        keys.insert(key.trim().to_string(), unescape_string(value.trim_start()));
+      }
+    }
+    let value = |key: &str| keys.get(key).filter(|v| !v.is_empty()).cloned();
+    let entry_type = match value("Type").as_deref() {
+      Some("Application") => EntryType::Application,
+      Some("Link") => EntryType::Link,
+      Some(other) => return Err(invalid_data(format!("unsupported desktop entry type '{}'", other))),
+      None => return Err(invalid_data(String::from("not a desktop entry, the Type key is missing"))),
+    };
+    let entry = Self {
+      entry_type,
+      name: value("Name").unwrap_or_default(),
+      comment: value("Comment"),
+      exec: value("Exec").map(|exec| split_exec(&exec)).transpose()?.unwrap_or_default(),
+      path: value("Path"),
+      icon: value("Icon"),
+      url: value("URL"),
+    };
+    match entry.entry_type {
+      EntryType::Application if entry.exec.is_empty() => Err(invalid_data(String::from("Exec is required"))),
+      EntryType::Link if entry.url.is_none() => Err(invalid_data(String::from("URL is required"))),
+      _ => Ok(entry),
+    }
+  }
+
+  /// The specification of a shortcut doing what the entry does. The program or the file of the URL has to be under a
+  /// mount point, the arguments under a mount point are mapped to Windows paths.
+  pub fn to_lnk_spec(&self, mounts: &MountTable) -> Result<LnkSpec> {
+    let (target, arguments) = match self.entry_type {
+      EntryType::Application => {
+        let (program, arguments) = self
+          .exec
+          .split_first()
+          .ok_or_else(|| invalid_input(String::from("the desktop entry has no Exec")))?;
+        let arguments: Vec<String> = arguments
+          .iter()
+          .map(|argument| match argument.starts_with('/') {
+            true => mounts.to_windows(argument).unwrap_or_else(|| argument.clone()),
+            false => argument.clone(),
+          })
+          .map(|argument| quote_windows_argument(&argument))
+          .collect();
+        (program.clone(), Some(arguments.join(" ")).filter(|a| !a.is_empty()))
+      },
+      EntryType::Link => {
+        let url = self.url.as_deref().unwrap_or_default();
+        let path = file_url_path(url).ok_or_else(|| invalid_input(format!("'{}' is not a file:// URL", url)))?;
+        (path, None)
+      },
+    };
+    let target_full_path = mounts
+      .to_windows(&target)
+      .ok_or_else(|| invalid_input(format!("'{}' is not under a mount point", target)))?;
+    Ok(LnkSpec {
+      target_full_path,
+      command_line_arguments: arguments,
+      working_dir: self.path.as_deref().and_then(|path| mounts.to_windows(path)),
+      name_string: self
+        .comment
+        .clone()
+        .or_else(|| Some(self.name.clone()).filter(|n| !n.is_empty())),
+      icon_location: self.icon.as_deref().and_then(|icon| mounts.to_windows(icon)),
+      ..LnkSpec::default()
+    })
+  }
+}
+
+impl fmt::Display for DesktopEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "[Desktop Entry]")?;
+    writeln!(f, "Type={}", self.entry_type)?;
+    writeln!(f, "Name={}", escape_string(&self.name))?;
+    if let Some(comment) = &self.comment {
+      writeln!(f, "Comment={}", escape_string(comment))?;
+    }
+    if !self.exec.is_empty() {
+      let exec: Vec<String> = self.exec.iter().map(|argument| quote_exec_argument(argument)).collect();
+      writeln!(f, "Exec={}", escape_string(&exec.join(" ")))?;
+    }
+    if let Some(path) = &self.path {
+      writeln!(f, "Path={}", escape_string(path))?;
+    }
+    if let Some(icon) = &self.icon {
+      writeln!(f, "Icon={}", escape_string(icon))?;
+    }
+    if let Some(url) = &self.url {
+      writeln!(f, "URL={}", escape_string(url))?;
+    }
+    Ok(())
+  }
+}
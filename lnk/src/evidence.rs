@@ -0,0 +1,234 @@
+//! Hashes tying the records to the bytes they were parsed from, and the chain of custody manifest of a run.
+//!
+//! Every [`Lnk`](crate::Lnk) carries the MD5, SHA-1 and SHA-256 of its data. A [`Manifest`] lists the inputs of a
+//! run with their hashes, the tool version, the arguments and the start and end times, and authenticates the output
+//! file and the manifest itself with HMAC-SHA256 under a secret key, so a modified output or manifest can be detected
+//! by anyone holding the key.
+
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use md5::Md5;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::{
+  fs::File,
+  io::{BufReader, Read, Result},
+};
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare two HMACs as hex strings without exiting early.
+fn same_hmac(a: &str, b: &str) -> bool {
+  a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The MD5, SHA-1 and SHA-256 of some data, as lowercase hex strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Hashes {
+  pub md5: String,
+  pub sha1: String,
+  pub sha256: String,
+}
+
+impl Hashes {
+  /// Hash `data`.
+  /// # Example
+  /// ```
+  /// use lnk::evidence::Hashes;
+  ///
+  /// let hashes = Hashes::of(b"abc");
+  /// assert_eq!(hashes.md5, "900150983cd24fb0d6963f7d28e17f72");
+  /// assert_eq!(hashes.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+  /// ```
+  pub fn of(data: &[u8]) -> Self {
+    let mut digests = Digests::new(None);
+    digests.update(data);
+    digests.finalize().0
+  }
+}
+
+/// Computes the hashes (and the HMAC) of data read in chunks.
+struct Digests {
+  md5: Md5,
+  sha1: Sha1,
+  sha256: Sha256,
+  hmac: Option<Hmac<Sha256>>,
+  size: u64,
+}
+
+impl Digests {
+  fn new(key: Option<&[u8]>) -> Self {
+    Self {
+      md5: Md5::new(),
+      sha1: Sha1::new(),
+      sha256: Sha256::new(),
+      hmac: key.map(|key| Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size")),
+      size: 0,
+    }
+  }
+
+  fn update(&mut self, data: &[u8]) {
+    self.md5.update(data);
+    self.sha1.update(data);
+    self.sha256.update(data);
+    if let Some(hmac) = self.hmac.as_mut() {
+      hmac.update(data);
+    }
+    self.size += data.len() as u64;
+  }
+
+  /// Hash the content of the file at `path`.
+  fn read_file(mut self, path: &str) -> Result<Self> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+      match reader.read(&mut buf)? {
+        0 => return Ok(self),
+        read => self.update(&buf[..read]),
+      }
+    }
+  }
+
+  /// The hashes, the HMAC (empty without a key) and the size of the data.
+  fn finalize(self) -> (Hashes, String, u64) {
+    let hashes = Hashes {
+      md5: hex(&self.md5.finalize()),
+      sha1: hex(&self.sha1.finalize()),
+      sha256: hex(&self.sha256.finalize()),
+    };
+    let hmac = self.hmac.map(|hmac| hex(&hmac.finalize().into_bytes())).unwrap_or_default();
+    (hashes, hmac, self.size)
+  }
+}
+
+/// A file read during the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestInput {
+  pub path: String,
+  /// `None` when the file could not be read.
+  pub size: Option<u64>,
+  pub hashes: Option<Hashes>,
+  /// The number of records parsed from the file, jump lists hold several.
+  pub records: usize,
+  /// Why the file could not be parsed.
+  pub error: Option<String>,
+}
+
+/// The output file of the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestOutput {
+  pub path: String,
+  pub size: u64,
+  pub hashes: Hashes,
+  /// HMAC-SHA256 of the whole file under the manifest key, as a lowercase hex string.
+  pub hmac_sha256: String,
+}
+
+/// The chain of custody record of a run.
+/// # Example
+/// ```
+/// use lnk::evidence::Manifest;
+///
+/// let output = std::env::temp_dir().join("lnk_manifest_example.csv");
+/// std::fs::write(&output, "target_full_path\r\n").unwrap();
+/// let mut manifest = Manifest::new(vec![String::from("lnk_parser")]);
+/// manifest.add_input("shortcut.lnk", Some(b"L\0\0\0"), 1, None);
+/// manifest.finish(&output.to_string_lossy(), b"secret").unwrap();
+/// assert!(manifest.verify(b"secret").unwrap());
+/// assert!(!manifest.verify(b"other key").unwrap());
+/// manifest.inputs[0].hashes.as_mut().unwrap().sha256 = "0".repeat(64);
+/// assert!(!manifest.verify(b"secret").unwrap());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+  pub tool: String,
+  pub version: String,
+  /// The command line of the run, the program name first.
+  pub arguments: Vec<String>,
+  pub started: String,
+  /// Empty until [`Manifest::finish`] is called.
+  pub finished: String,
+  /// The inputs in the order they were parsed.
+  pub inputs: Vec<ManifestInput>,
+  pub output: Option<ManifestOutput>,
+  /// HMAC-SHA256 under the manifest key of the manifest serialized as JSON with an empty signature, as a lowercase hex
+  /// string. Empty until [`Manifest::finish`] is called.
+  #[serde(default)]
+  pub signature: String,
+}
+
+fn now() -> String {
+  Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+impl Manifest {
+  /// Start the manifest of a run, the start time is now.
+  pub fn new(arguments: Vec<String>) -> Self {
+    Self {
+      tool: String::from("lnk_parser"),
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      arguments,
+      started: now(),
+      finished: String::new(),
+      inputs: vec![],
+      output: None,
+      signature: String::new(),
+    }
+  }
+
+  /// Add an input, `data` is the content of the file or `None` when it could not be read.
+  pub fn add_input(&mut self, path: &str, data: Option<&[u8]>, records: usize, error: Option<String>) {
+    self.inputs.push(ManifestInput {
+      path: path.to_string(),
+      size: data.map(|data| data.len() as u64),
+      hashes: data.map(Hashes::of),
+      records,
+      error,
+    });
+  }
+
+  /// Hash the output file, which must be complete, authenticate it under `key`, set the end time and sign the
+  /// manifest.
+  pub fn finish(&mut self, output_path: &str, key: &[u8]) -> Result<()> {
+    let (hashes, hmac_sha256, size) = Digests::new(Some(key)).read_file(output_path)?.finalize();
+    self.output = Some(ManifestOutput {
+      path: output_path.to_string(),
+      size,
+      hashes,
+      hmac_sha256,
+    });
+    self.finished = now();
+    self.signature = self.sign(key);
+    Ok(())
+  }
+
+  /// The HMAC of the manifest under `key`, the signature is left out.
+  fn sign(&self, key: &[u8]) -> String {
+    let unsigned = Self {
+      signature: String::new(),
+      ..self.clone()
+    };
+    let mut hmac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    hmac.update(&serde_json::to_vec(&unsigned).expect("a manifest serializes to JSON"));
+    hex(&hmac.finalize().into_bytes())
+  }
+
+  /// Whether the manifest has the signature it was given under `key` and the output file still has the HMAC recorded
+  /// in the manifest.
+  pub fn verify(&self, key: &[u8]) -> Result<bool> {
+    let Some(output) = &self.output else {
+      return Ok(false);
+    };
+    if !same_hmac(&self.sign(key), &self.signature) {
+      return Ok(false);
+    }
+    let (_, hmac_sha256, _) = Digests::new(Some(key)).read_file(&output.path)?.finalize();
+    Ok(same_hmac(&hmac_sha256, &output.hmac_sha256))
+  }
+}
@@ -2,13 +2,57 @@
 
 mod tracker_data_block;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::io::{Cursor, Read, Result, Seek};
 use tracker_data_block::TrackerDataBlock;
 
+/// ExtraData block signatures and the names the specification gives to the blocks.
+pub const BLOCK_NAMES: &[(u32, &str)] = &[
+  (0xA0000001, "EnvironmentVariableDataBlock"),
+  (0xA0000002, "ConsoleDataBlock"),
+  (0xA0000003, "TrackerDataBlock"),
+  (0xA0000004, "ConsoleFEDataBlock"),
+  (0xA0000005, "SpecialFolderDataBlock"),
+  (0xA0000006, "DarwinDataBlock"),
+  (0xA0000007, "IconEnvironmentDataBlock"),
+  (0xA0000008, "ShimDataBlock"),
+  (0xA0000009, "PropertyStoreDataBlock"),
+  (0xA000000B, "KnownFolderDataBlock"),
+  (0xA000000C, "VistaAndAboveIDListDataBlock"),
+];
+
+/// The names of every ExtraData block of a LNK file, decoded or not, in file order. Blocks with an unknown signature
+/// are named by their signature (`0xA000000A`).
+/// # Example
+/// ```
+/// let data = std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+/// assert!(lnk::extra_data::block_names(&data).contains(&"TrackerDataBlock".to_string()));
+/// ```
+pub fn block_names(data: &[u8]) -> Vec<String> {
+  let mut signatures: Vec<(usize, u32)> = crate::spans::spans(data)
+    .iter()
+    .filter(|(path, _)| path.starts_with("extra_data.blocks.") && path.ends_with(".signature"))
+    .filter_map(|(_, span)| {
+      let signature = data.get(span.offset..span.offset + 4)?;
+      Some((span.offset, LittleEndian::read_u32(signature)))
+    })
+    .collect();
+  signatures.sort();
+  signatures
+    .into_iter()
+    .map(|(_, signature)| match BLOCK_NAMES.iter().find(|(s, _)| *s == signature) {
+      Some((_, name)) => name.to_string(),
+      None => format!("0x{:08X}", signature),
+    })
+    .collect()
+}
+
 /// ExtraData types implemented
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum ExtraDataTypes {
   Tracker(TrackerDataBlock),
 }
@@ -16,6 +60,7 @@ pub enum ExtraDataTypes {
 /// ExtraData refers to a set of structures that convey additional information about a link target.
 /// These optional structures can be present in an extra data section that is appended to the basic Shell Link Binary File Format.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ExtraData {
   pub extra_data_blocks: Vec<ExtraDataTypes>,
 }
@@ -1,18 +1,25 @@
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::io::{Cursor, Read, Result};
 use winparsingtools::{structs::Guid, utils::read_utf8_string};
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct TrackerDataBlock {
   #[serde(skip_serializing)]
   pub size: u32,
   #[serde(skip_serializing)]
   pub version: u32,
   pub machine_id: String,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::guid"))]
   pub file_droid: Guid,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::guid"))]
   pub file_droid_birth: Guid,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::guid"))]
   pub volume_droid: Guid,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::guid"))]
   pub volume_droid_birth: Guid,
 }
 
@@ -0,0 +1,328 @@
+//! Filter expressions evaluated against the normalized fields of a record (see [`NORMALIZED_FIELDS`]).
+//!
+//! A comparison is a field name, an operator and a value. Values are double quoted strings, where `\"` is the only
+//! escape so Windows paths and regular expressions are written as they are, single quoted strings without any
+//! escape (`'C:\Users\'`) or bare words such as numbers:
+//!
+//! | Operator | Meaning |
+//! |---|---|
+//! | `==`, `!=` | equal, not equal |
+//! | `<`, `<=`, `>`, `>=` | numbers are compared as numbers, dates as dates, anything else as strings |
+//! | `=~`, `!~` | the field matches (does not match) the regular expression, `(?i)` makes it case-insensitive |
+//! | `contains` | the field contains the value |
+//! | `in "start".."end"` | `start <= field < end`, mostly useful for time ranges |
+//!
+//! Comparisons are combined with `&&`, `||`, `!` and parentheses, `&&` binds tighter than `||`. Dates can be
+//! written as `2021-02-08`, `2021-02-08 12:41:03` or `2021-02-08T12:41:03Z` and are UTC. Ordering comparisons
+//! on an empty field are false.
+
+use crate::NORMALIZED_FIELDS;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use regex::Regex;
+use std::{
+  cmp::Ordering,
+  collections::HashMap,
+  io::{Error, ErrorKind, Result},
+};
+
+/// A parsed filter expression.
+/// # Example
+/// ```
+/// use lnk::filter::Filter;
+/// use std::collections::HashMap;
+///
+/// let filter = Filter::parse(r#"drive_type == "DRIVE_REMOVABLE" && target_hostname != "WS01""#).unwrap();
+/// let mut fields = HashMap::new();
+/// fields.insert("drive_type".to_string(), "DRIVE_REMOVABLE".to_string());
+/// fields.insert("target_hostname".to_string(), "WS02".to_string());
+/// assert!(filter.matches(&fields));
+/// ```
+#[derive(Debug)]
+pub struct Filter {
+  expression: Expression,
+}
+
+#[derive(Debug)]
+enum Expression {
+  And(Box<Expression>, Box<Expression>),
+  Or(Box<Expression>, Box<Expression>),
+  Not(Box<Expression>),
+  Compare(String, Operator, String),
+  Matches(String, Regex, bool),
+  Range(String, String, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+  Equal,
+  NotEqual,
+  Less,
+  LessOrEqual,
+  Greater,
+  GreaterOrEqual,
+  Contains,
+}
+
+impl Filter {
+  /// Parse a filter expression, the error message points at the position of the problem.
+  pub fn parse(expression: &str) -> Result<Self> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens, position: 0 };
+    let expression = parser.or()?;
+    match parser.peek() {
+      None => Ok(Self { expression }),
+      Some((offset, token)) => Err(error(*offset, &format!("unexpected {}", token))),
+    }
+  }
+
+  /// Evaluate the filter against the normalized fields of a record, missing fields are empty.
+  pub fn matches(&self, fields: &HashMap<String, String>) -> bool {
+    self.expression.evaluate(fields)
+  }
+}
+
+impl Expression {
+  fn evaluate(&self, fields: &HashMap<String, String>) -> bool {
+    let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+    match self {
+      Expression::And(left, right) => left.evaluate(fields) && right.evaluate(fields),
+      Expression::Or(left, right) => left.evaluate(fields) || right.evaluate(fields),
+      Expression::Not(expression) => !expression.evaluate(fields),
+      Expression::Matches(name, regex, expected) => regex.is_match(field(name)) == *expected,
+      Expression::Range(name, start, end) => {
+        let value = field(name);
+        !value.is_empty()
+          && compare(value, start).is_some_and(|o| o != Ordering::Less)
+          && compare(value, end) == Some(Ordering::Less)
+      },
+      Expression::Compare(name, operator, expected) => {
+        let value = field(name);
+        match operator {
+          Operator::Equal => value == expected || compare(value, expected) == Some(Ordering::Equal),
+          Operator::NotEqual => !(value == expected || compare(value, expected) == Some(Ordering::Equal)),
+          Operator::Contains => value.contains(expected.as_str()),
+          _ if value.is_empty() => false,
+          Operator::Less => compare(value, expected) == Some(Ordering::Less),
+          Operator::LessOrEqual => compare(value, expected).is_some_and(|o| o != Ordering::Greater),
+          Operator::Greater => compare(value, expected) == Some(Ordering::Greater),
+          Operator::GreaterOrEqual => compare(value, expected).is_some_and(|o| o != Ordering::Less),
+        }
+      },
+    }
+  }
+}
+
+/// Compare as numbers, then as dates, then as strings.
+fn compare(value: &str, expected: &str) -> Option<Ordering> {
+  if let (Some(value), Some(expected)) = (number(value), number(expected)) {
+    return value.partial_cmp(&expected);
+  }
+  if let (Some(value), Some(expected)) = (parse_time(value), parse_time(expected)) {
+    return Some(value.cmp(&expected));
+  }
+  Some(value.cmp(expected))
+}
+
+/// `value` as a number, text such as "inf" or "NaN" that Rust also reads as a number is not one.
+fn number(value: &str) -> Option<f64> {
+  value.parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+  if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+    return Some(time.with_timezone(&Utc));
+  }
+  for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"] {
+    if let Ok(time) = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), format) {
+      return Some(time.and_utc());
+    }
+  }
+  NaiveDate::parse_from_str(value, "%Y-%m-%d")
+    .ok()
+    .and_then(|date| date.and_hms_opt(0, 0, 0))
+    .map(|time| time.and_utc())
+}
+
+fn error(offset: usize, message: &str) -> Error {
+  Error::new(ErrorKind::InvalidInput, format!("invalid filter at position {}: {}", offset, message))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Word(String),
+  String(String),
+  Operator(&'static str),
+}
+
+impl std::fmt::Display for Token {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Token::Word(word) => write!(f, "'{}'", word),
+      Token::String(string) => write!(f, "\"{}\"", string),
+      Token::Operator(operator) => write!(f, "'{}'", operator),
+    }
+  }
+}
+
+/// Longest operators first so `<=` is not read as `<` followed by `=`.
+const OPERATORS: &[&str] = &[
+  "==", "!=", "<=", ">=", "=~", "!~", "&&", "||", "..", "<", ">", "!", "(", ")",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>> {
+  let mut tokens = vec![];
+  let mut chars = expression.char_indices().peekable();
+  while let Some(&(offset, c)) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    } else if c == '"' || c == '\'' {
+      chars.next();
+      let mut string = String::new();
+      loop {
+        match chars.next() {
+          Some((_, q)) if q == c => break,
+          // Only `\"` is an escape, backslashes in Windows paths and regular expressions are kept as they are.
+          Some((_, '\\')) if c == '"' && chars.peek().is_some_and(|&(_, c)| c == '"') => {
+            chars.next();
+            string.push('"');
+          },
+          Some((_, c)) => string.push(c),
+          None => return Err(error(offset, "unterminated string")),
+        }
+      }
+      tokens.push((offset, Token::String(string)));
+    } else if let Some(operator) = OPERATORS.iter().find(|o| expression[offset..].starts_with(**o)) {
+      for _ in 0..operator.len() {
+        chars.next();
+      }
+      tokens.push((offset, Token::Operator(operator)));
+    } else if c.is_alphanumeric() || "_-:+".contains(c) {
+      let mut word = String::new();
+      while let Some(&(o, c)) = chars.peek() {
+        // A `..` ends the word so `1..10` is a range.
+        if !(c.is_alphanumeric() || "_-:+.".contains(c)) || expression[o..].starts_with("..") {
+          break;
+        }
+        word.push(c);
+        chars.next();
+      }
+      tokens.push((offset, Token::Word(word)));
+    } else {
+      return Err(error(offset, &format!("unexpected character '{}'", c)));
+    }
+  }
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<(usize, Token)>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&(usize, Token)> {
+    self.tokens.get(self.position)
+  }
+
+  fn next(&mut self, expected: &str) -> Result<(usize, Token)> {
+    match self.tokens.get(self.position) {
+      Some(token) => {
+        self.position += 1;
+        Ok(token.clone())
+      },
+      None => {
+        let end = self.tokens.last().map(|(offset, _)| offset + 1).unwrap_or_default();
+        Err(error(end, &format!("expected {}", expected)))
+      },
+    }
+  }
+
+  fn accept(&mut self, operator: &str) -> bool {
+    match self.peek() {
+      Some((_, Token::Operator(o))) if *o == operator => {
+        self.position += 1;
+        true
+      },
+      _ => false,
+    }
+  }
+
+  fn or(&mut self) -> Result<Expression> {
+    let mut expression = self.and()?;
+    while self.accept("||") {
+      expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+    }
+    Ok(expression)
+  }
+
+  fn and(&mut self) -> Result<Expression> {
+    let mut expression = self.unary()?;
+    while self.accept("&&") {
+      expression = Expression::And(Box::new(expression), Box::new(self.unary()?));
+    }
+    Ok(expression)
+  }
+
+  fn unary(&mut self) -> Result<Expression> {
+    if self.accept("!") {
+      return Ok(Expression::Not(Box::new(self.unary()?)));
+    }
+    if self.accept("(") {
+      let expression = self.or()?;
+      return match self.next("')'")? {
+        (_, Token::Operator(")")) => Ok(expression),
+        (offset, token) => Err(error(offset, &format!("expected ')', got {}", token))),
+      };
+    }
+    self.comparison()
+  }
+
+  fn comparison(&mut self) -> Result<Expression> {
+    let field = match self.next("a field name")? {
+      (offset, Token::Word(word)) => {
+        if !NORMALIZED_FIELDS.contains(&word.as_str()) {
+          return Err(error(
+            offset,
+            &format!("unknown field '{}', valid fields are: {}", word, NORMALIZED_FIELDS.join(",")),
+          ));
+        }
+        word
+      },
+      (offset, token) => return Err(error(offset, &format!("expected a field name, got {}", token))),
+    };
+    let (offset, operator) = self.next("an operator")?;
+    let operator = match operator {
+      Token::Operator("==") => Operator::Equal,
+      Token::Operator("!=") => Operator::NotEqual,
+      Token::Operator("<") => Operator::Less,
+      Token::Operator("<=") => Operator::LessOrEqual,
+      Token::Operator(">") => Operator::Greater,
+      Token::Operator(">=") => Operator::GreaterOrEqual,
+      Token::Word(word) if word == "contains" => Operator::Contains,
+      Token::Operator(o @ ("=~" | "!~")) => {
+        let (offset, pattern) = self.value()?;
+        let regex = Regex::new(&pattern).map_err(|e| error(offset, &e.to_string()))?;
+        return Ok(Expression::Matches(field, regex, o == "=~"));
+      },
+      Token::Word(word) if word == "in" => {
+        let (_, start) = self.value()?;
+        match self.next("'..'")? {
+          (_, Token::Operator("..")) => {},
+          (offset, token) => return Err(error(offset, &format!("expected '..', got {}", token))),
+        }
+        let (_, end) = self.value()?;
+        return Ok(Expression::Range(field, start, end));
+      },
+      token => return Err(error(offset, &format!("expected an operator, got {}", token))),
+    };
+    let (_, value) = self.value()?;
+    Ok(Expression::Compare(field, operator, value))
+  }
+
+  fn value(&mut self) -> Result<(usize, String)> {
+    match self.next("a value")? {
+      (offset, Token::String(value)) | (offset, Token::Word(value)) => Ok((offset, value)),
+      (offset, token) => Err(error(offset, &format!("expected a value, got {}", token))),
+    }
+  }
+}
@@ -0,0 +1,308 @@
+//! Reverse lookup of the shortcuts referencing a target, a volume, a machine, a droid or an MFT entry.
+//!
+//! An [`Index`] keeps what every shortcut of a collection points at. It is built once from the parsed files and
+//! saved to a file, queries then only load the index instead of parsing the collection again. Paths are compared
+//! case-insensitively with `/` and `\` as the same separator, volume serial numbers, machine IDs and droids in any
+//! case, the braces of droids and the dash of serial numbers are optional.
+
+use crate::{extra_data::ExtraDataTypes, Lnk};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap},
+  fs::File,
+  io::{BufReader, BufWriter, Error, ErrorKind, Result, Write},
+  path::Path,
+};
+
+/// Version of the index file format, indexes written by another version are refused.
+pub const INDEX_VERSION: u32 = 1;
+
+/// What a shortcut points at.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+  pub lnk_full_path: String,
+  pub target_full_path: String,
+  /// The paths of the target: `target_full_path` and, when the LinkInfo has a share, the path on the share.
+  pub target_paths: Vec<String>,
+  pub volume_serial_number: String,
+  /// NetBIOS names of the machine the target was on, from the TrackerDataBlocks.
+  pub machine_ids: Vec<String>,
+  /// Volume and file droids of the TrackerDataBlocks, current and birth.
+  pub droids: Vec<String>,
+  pub mft_entry: Option<u64>,
+  pub mft_sequence_number: Option<u16>,
+}
+
+impl Entry {
+  pub fn new(lnk: &Lnk) -> Self {
+    let mut entry = Self {
+      lnk_full_path: lnk
+        .lnk_file_metadata
+        .as_ref()
+        .map(|metadata| metadata.full_path.clone())
+        .unwrap_or_default(),
+      target_full_path: lnk.target_full_path.clone().unwrap_or_default(),
+      ..Self::default()
+    };
+    if !entry.target_full_path.is_empty() {
+      entry.target_paths.push(entry.target_full_path.clone());
+    }
+    if let Some(link_info) = &lnk.link_info {
+      if let Some(volume_id) = &link_info.volume_id {
+        entry.volume_serial_number = volume_id.serial_number.clone();
+      }
+      let net_name = link_info
+        .common_network_relative_link
+        .as_ref()
+        .and_then(|link| link.net_name.as_ref());
+      if let Some(net_name) = net_name {
+        let share_path = match link_info.common_path_suffix.as_deref().unwrap_or_default() {
+          "" => net_name.clone(),
+          suffix => format!("{}\\{}", net_name.trim_end_matches('\\'), suffix),
+        };
+        if !entry
+          .target_paths
+          .iter()
+          .any(|path| normalize_path(path) == normalize_path(&share_path))
+        {
+          entry.target_paths.push(share_path);
+        }
+      }
+    }
+    for block in lnk.extra_data.iter().flat_map(|extra_data| &extra_data.extra_data_blocks) {
+      match block {
+        ExtraDataTypes::Tracker(tracker) => {
+          if !tracker.machine_id.is_empty() {
+            entry.machine_ids.push(tracker.machine_id.clone());
+          }
+          for droid in [
+            &tracker.volume_droid,
+            &tracker.file_droid,
+            &tracker.volume_droid_birth,
+            &tracker.file_droid_birth,
+          ] {
+            let droid = droid.to_string();
+            if !entry.droids.contains(&droid) {
+              entry.droids.push(droid);
+            }
+          }
+        },
+      }
+    }
+    if let Some((mft_entry, sequence_number)) = lnk.link_target_id_list.as_ref().and_then(|l| l.mft_reference()) {
+      entry.mft_entry = Some(mft_entry);
+      entry.mft_sequence_number = Some(sequence_number);
+    }
+    entry
+  }
+}
+
+fn normalize_path(path: &str) -> String {
+  path.replace('/', "\\").to_lowercase()
+}
+
+fn normalize_serial(serial: &str) -> String {
+  let serial = serial.trim().replace('-', "").to_uppercase();
+  match serial.len() {
+    8 => format!("{}-{}", &serial[..4], &serial[4..]),
+    _ => serial,
+  }
+}
+
+fn normalize_droid(droid: &str) -> String {
+  droid.trim().trim_start_matches('{').trim_end_matches('}').to_uppercase()
+}
+
+/// A lookup, see [`Index::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+  /// A target path.
+  Target(String),
+  /// The start of a target path, `\\fileserver\finance\` finds every target under the share.
+  TargetPrefix(String),
+  VolumeSerial(String),
+  MachineId(String),
+  /// A volume or file droid, current or birth.
+  Droid(String),
+  /// An MFT entry, and its sequence number if given.
+  MftReference(u64, Option<u16>),
+}
+
+impl Query {
+  /// Parse an MFT reference query, `ENTRY` or `ENTRY-SEQUENCE` in decimal.
+  pub fn mft_reference(reference: &str) -> Result<Self> {
+    let invalid = || {
+      Error::new(
+        ErrorKind::InvalidInput,
+        format!("invalid MFT reference '{}', expected ENTRY or ENTRY-SEQUENCE", reference),
+      )
+    };
+    let (entry, sequence_number) = match reference.trim().split_once('-') {
+      Some((entry, sequence_number)) => (entry, Some(sequence_number)),
+      None => (reference.trim(), None),
+    };
+    Ok(Query::MftReference(
+      entry.parse().map_err(|_| invalid())?,
+      sequence_number.map(|s| s.parse()).transpose().map_err(|_| invalid())?,
+    ))
+  }
+
+  /// Whether the entry matches the query.
+  pub fn matches(&self, entry: &Entry) -> bool {
+    match self {
+      Query::Target(path) => entry.target_paths.iter().any(|p| normalize_path(p) == normalize_path(path)),
+      Query::TargetPrefix(prefix) => entry
+        .target_paths
+        .iter()
+        .any(|p| normalize_path(p).starts_with(&normalize_path(prefix))),
+      Query::VolumeSerial(serial) => normalize_serial(&entry.volume_serial_number) == normalize_serial(serial),
+      Query::MachineId(machine_id) => entry.machine_ids.iter().any(|m| m.eq_ignore_ascii_case(machine_id.trim())),
+      Query::Droid(droid) => entry.droids.iter().any(|d| normalize_droid(d) == normalize_droid(droid)),
+      Query::MftReference(mft_entry, sequence_number) => {
+        entry.mft_entry == Some(*mft_entry) && sequence_number.is_none_or(|s| entry.mft_sequence_number == Some(s))
+      },
+    }
+  }
+}
+
+/// The index file, the lookup tables are rebuilt when it is loaded.
+#[derive(Serialize, Deserialize)]
+struct IndexFile<E> {
+  version: u32,
+  entries: E,
+}
+
+/// Shortcuts indexed by what they point at.
+/// # Example
+/// ```
+/// use lnk::{index::{Index, Query}, Lnk};
+///
+/// let mut index = Index::new();
+/// index.add(&Lnk::from_path("samples/WIN10/1607_14393/windows_generated.lnk").unwrap());
+/// index.add(&Lnk::from_path("samples/WIN10/1607_14393/network_share.lnk").unwrap());
+/// let found = index.find(&[Query::TargetPrefix(String::from("c:/users/U0041/"))]);
+/// assert_eq!(found[0].target_full_path, "C:\\Users\\u0041\\Desktop\\test\\test.txt");
+/// assert_eq!(index.find(&[Query::VolumeSerial(String::from("e02e8a93"))]).len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Index {
+  entries: Vec<Entry>,
+  /// Normalized target paths, ordered so prefixes are ranges.
+  paths: BTreeMap<String, Vec<usize>>,
+  serials: HashMap<String, Vec<usize>>,
+  machine_ids: HashMap<String, Vec<usize>>,
+  droids: HashMap<String, Vec<usize>>,
+  mft_entries: HashMap<u64, Vec<usize>>,
+}
+
+impl Index {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add what a shortcut points at.
+  pub fn add(&mut self, lnk: &Lnk) {
+    self.add_entry(Entry::new(lnk));
+  }
+
+  pub fn add_entry(&mut self, entry: Entry) {
+    let position = self.entries.len();
+    let insert = |keys: Vec<String>, map: &mut HashMap<String, Vec<usize>>| {
+      for key in keys.into_iter().filter(|key| !key.is_empty()) {
+        let positions = map.entry(key).or_default();
+        if positions.last() != Some(&position) {
+          positions.push(position);
+        }
+      }
+    };
+    insert(vec![normalize_serial(&entry.volume_serial_number)], &mut self.serials);
+    insert(entry.machine_ids.iter().map(|m| m.to_lowercase()).collect(), &mut self.machine_ids);
+    insert(entry.droids.iter().map(|d| normalize_droid(d)).collect(), &mut self.droids);
+    for path in &entry.target_paths {
+      let positions = self.paths.entry(normalize_path(path)).or_default();
+      if positions.last() != Some(&position) {
+        positions.push(position);
+      }
+    }
+    if let Some(mft_entry) = entry.mft_entry {
+      self.mft_entries.entry(mft_entry).or_default().push(position);
+    }
+    self.entries.push(entry);
+  }
+
+  pub fn entries(&self) -> &[Entry] {
+    &self.entries
+  }
+
+  /// The positions of the entries that can match the query, looked up in the tables.
+  fn candidates(&self, query: &Query) -> BTreeSet<usize> {
+    let positions = |positions: Option<&Vec<usize>>| positions.into_iter().flatten().copied().collect();
+    match query {
+      Query::Target(path) => positions(self.paths.get(&normalize_path(path))),
+      Query::TargetPrefix(prefix) => {
+        let prefix = normalize_path(prefix);
+        self
+          .paths
+          .range(prefix.clone()..)
+          .take_while(|(path, _)| path.starts_with(&prefix))
+          .flat_map(|(_, positions)| positions.iter().copied())
+          .collect()
+      },
+      Query::VolumeSerial(serial) => positions(self.serials.get(&normalize_serial(serial))),
+      Query::MachineId(machine_id) => positions(self.machine_ids.get(&machine_id.trim().to_lowercase())),
+      Query::Droid(droid) => positions(self.droids.get(&normalize_droid(droid))),
+      Query::MftReference(mft_entry, _) => positions(self.mft_entries.get(mft_entry)),
+    }
+  }
+
+  /// The entries matching every query, in the order they were added. No query matches every entry.
+  pub fn find(&self, queries: &[Query]) -> Vec<&Entry> {
+    let Some((first, rest)) = queries.split_first() else {
+      return self.entries.iter().collect();
+    };
+    self
+      .candidates(first)
+      .into_iter()
+      .map(|position| &self.entries[position])
+      .filter(|entry| first.matches(entry) && rest.iter().all(|query| query.matches(entry)))
+      .collect()
+  }
+
+  /// Write the index to a file.
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let index_file = IndexFile {
+      version: INDEX_VERSION,
+      entries: &self.entries,
+    };
+    serde_json::to_writer(&mut writer, &index_file)?;
+    writer.flush()
+  }
+
+  /// Read an index written by [`Index::save`]. A file that is not an index gives an `InvalidData` error, the errors
+  /// reading the file keep their kind.
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let index_file: IndexFile<Vec<Entry>> =
+      serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(|e| match e.is_io() {
+        true => Error::from(e),
+        false => Error::new(ErrorKind::InvalidData, format!("'{}' is not an index: {}", path.display(), e)),
+      })?;
+    if index_file.version != INDEX_VERSION {
+      return Err(Error::new(
+        ErrorKind::InvalidData,
+        format!(
+          "'{}' was written with index version {}, expected {}",
+          path.display(),
+          index_file.version,
+          INDEX_VERSION
+        ),
+      ));
+    }
+    let mut index = Self::new();
+    for entry in index_file.entries {
+      index.add_entry(entry);
+    }
+    Ok(index)
+  }
+}
@@ -0,0 +1,245 @@
+//! [Jump list](https://github.com/libyal/dtformats/blob/main/documentation/Jump%20lists%20format.asciidoc) parsers.
+//!
+//! `*.automaticDestinations-ms` files are OLE compound files that store every entry as a LNK stream next to a
+//! `DestList` stream, `*.customDestinations-ms` files are LNK entries written one after the other.
+
+use crate::{Lnk, LnkFileMetaData};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+  fs,
+  io::{Cursor, Error, ErrorKind, Result},
+};
+
+/// The first 20 bytes of every LNK file (`header_size` followed by the LinkCLSID).
+pub const LNK_SIGNATURE: [u8; 20] = [
+  0x4C, 0x00, 0x00, 0x00, 0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  0x46,
+];
+
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const END_OF_CHAIN: u32 = 0xFFFFFFFE;
+const MAX_REG_SECTOR: u32 = 0xFFFFFFFA;
+
+/// A LNK entry found inside a jump list.
+#[derive(Debug)]
+pub struct JumpListEntry {
+  /// The stream name for automatic destinations or the entry offset for custom destinations.
+  pub entry: String,
+  pub lnk: Lnk,
+  /// The bytes of the LNK entry.
+  pub data: Vec<u8>,
+}
+
+/// LNK entries stored in a jump list file.
+#[derive(Debug)]
+pub struct JumpList {
+  pub entries: Vec<JumpListEntry>,
+}
+
+impl JumpList {
+  /// Parse an automatic or custom destinations file from path.
+  /// The `lnk_file_metadata` of every entry describes the jump list file itself.
+  pub fn from_path(path: &str) -> Result<Self> {
+    let data = fs::read(path)?;
+    let lnk_file_metadata = LnkFileMetaData::from_path(path)?;
+    let mut jump_list = Self::from_buffer(&data)?;
+    for entry in jump_list.entries.iter_mut() {
+      entry.lnk.lnk_file_metadata = Some(lnk_file_metadata.clone());
+    }
+    Ok(jump_list)
+  }
+
+  /// Parse the jump list from buffer, the format is detected from the data.
+  pub fn from_buffer(buf: &[u8]) -> Result<Self> {
+    if buf.starts_with(&CFB_SIGNATURE) {
+      Self::from_automatic_destinations(buf)
+    } else {
+      Ok(Self::from_custom_destinations(buf))
+    }
+  }
+
+  /// Read every LNK stream from an `*.automaticDestinations-ms` compound file.
+  pub fn from_automatic_destinations(buf: &[u8]) -> Result<Self> {
+    let cfb = CompoundFile::new(buf)?;
+    let mut entries = vec![];
+    for (name, data) in cfb.streams()? {
+      if name == "DestList" || !data.starts_with(&LNK_SIGNATURE) {
+        continue;
+      }
+      // A corrupted entry should not hide the remaining ones.
+      if let Ok(lnk) = Lnk::from_buffer(&data) {
+        entries.push(JumpListEntry { entry: name, lnk, data });
+      }
+    }
+    Ok(Self { entries })
+  }
+
+  /// Read every LNK entry from an `*.customDestinations-ms` file by looking for the LNK signature.
+  pub fn from_custom_destinations(buf: &[u8]) -> Self {
+    let mut entries = vec![];
+    let mut offset = 0;
+    while let Some(position) = find_signature(&buf[offset..]) {
+      let start = offset + position;
+      let mut reader = Cursor::new(&buf[start..]);
+      if let Ok(lnk) = Lnk::from_reader(&mut reader) {
+        let end = start + reader.position() as usize;
+        entries.push(JumpListEntry {
+          entry: format!("0x{:X}", start),
+          lnk,
+          data: buf[start..end].to_vec(),
+        });
+      }
+      offset = start + LNK_SIGNATURE.len();
+    }
+    Self { entries }
+  }
+}
+
+fn find_signature(buf: &[u8]) -> Option<usize> {
+  buf.windows(LNK_SIGNATURE.len()).position(|w| w == LNK_SIGNATURE)
+}
+
+/// Minimal [compound file](https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-cfb/53989ce4-7b05-4f8d-829b-d08d6148375b)
+/// reader, only what is needed to read the streams stored in the root storage.
+struct CompoundFile<'a> {
+  data: &'a [u8],
+  sector_size: usize,
+  mini_sector_size: usize,
+  mini_stream_cutoff: u64,
+  fat: Vec<u32>,
+  mini_fat: Vec<u32>,
+  directory: Vec<DirectoryEntry>,
+}
+
+struct DirectoryEntry {
+  name: String,
+  object_type: u8,
+  start_sector: u32,
+  size: u64,
+}
+
+impl<'a> CompoundFile<'a> {
+  fn new(data: &'a [u8]) -> Result<Self> {
+    if data.len() < 512 || !data.starts_with(&CFB_SIGNATURE) {
+      return Err(Error::new(ErrorKind::InvalidData, "Not a compound file"));
+    }
+    let sector_shift = LittleEndian::read_u16(&data[0x1E..]);
+    let mini_sector_shift = LittleEndian::read_u16(&data[0x20..]);
+    if !(7..=16).contains(&sector_shift) || mini_sector_shift >= sector_shift {
+      return Err(Error::new(ErrorKind::InvalidData, "Invalid compound file sector size"));
+    }
+    let mut cfb = Self {
+      data,
+      sector_size: 1 << sector_shift,
+      mini_sector_size: 1 << mini_sector_shift,
+      mini_stream_cutoff: LittleEndian::read_u32(&data[0x38..]) as u64,
+      fat: vec![],
+      mini_fat: vec![],
+      directory: vec![],
+    };
+
+    // The first 109 FAT sector locations are stored in the header, the rest in the DIFAT chain.
+    let mut fat_sectors: Vec<u32> = (0..109).map(|i| LittleEndian::read_u32(&data[0x4C + i * 4..])).collect();
+    let mut difat_sector = LittleEndian::read_u32(&data[0x44..]);
+    let mut difat_count = LittleEndian::read_u32(&data[0x48..]);
+    while difat_sector <= MAX_REG_SECTOR && difat_count > 0 {
+      let sector = cfb.sector(difat_sector)?;
+      let entries = cfb.sector_size / 4 - 1;
+      fat_sectors.extend((0..entries).map(|i| LittleEndian::read_u32(&sector[i * 4..])));
+      difat_sector = LittleEndian::read_u32(&sector[entries * 4..]);
+      difat_count -= 1;
+    }
+    for fat_sector in fat_sectors.into_iter().filter(|&s| s <= MAX_REG_SECTOR) {
+      let sector = cfb.sector(fat_sector)?;
+      cfb.fat.extend(sector.chunks_exact(4).map(LittleEndian::read_u32));
+    }
+
+    let mini_fat_data = cfb.read_chain(LittleEndian::read_u32(&data[0x3C..]))?;
+    cfb.mini_fat = mini_fat_data.chunks_exact(4).map(LittleEndian::read_u32).collect();
+
+    let directory_data = cfb.read_chain(LittleEndian::read_u32(&data[0x30..]))?;
+    cfb.directory = directory_data
+      .chunks_exact(128)
+      .map(|entry| {
+        let name_size = (LittleEndian::read_u16(&entry[64..]) as usize).min(64);
+        let name: Vec<u16> = entry[..name_size]
+          .chunks_exact(2)
+          .map(LittleEndian::read_u16)
+          .take_while(|&c| c != 0)
+          .collect();
+        DirectoryEntry {
+          name: String::from_utf16_lossy(&name),
+          object_type: entry[66],
+          start_sector: LittleEndian::read_u32(&entry[116..]),
+          // Version 3 files only use the low 32 bits, the high bits may contain garbage.
+          size: match sector_shift {
+            9 => LittleEndian::read_u32(&entry[120..]) as u64,
+            _ => LittleEndian::read_u64(&entry[120..]),
+          },
+        }
+      })
+      .collect();
+    Ok(cfb)
+  }
+
+  fn sector(&self, sector: u32) -> Result<&'a [u8]> {
+    let start = (sector as usize + 1) * self.sector_size;
+    self
+      .data
+      .get(start..start + self.sector_size)
+      .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, format!("Sector {} is out of bounds", sector)))
+  }
+
+  fn read_chain(&self, start: u32) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    let mut sector = start;
+    while sector <= MAX_REG_SECTOR {
+      // A chain can not be longer than the FAT itself, anything else is a loop.
+      if data.len() > self.fat.len() * self.sector_size {
+        return Err(Error::new(ErrorKind::InvalidData, "Loop in the compound file FAT chain"));
+      }
+      data.extend_from_slice(self.sector(sector)?);
+      sector = *self.fat.get(sector as usize).unwrap_or(&END_OF_CHAIN);
+    }
+    Ok(data)
+  }
+
+  fn read_mini_chain(&self, mini_stream: &[u8], start: u32) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    let mut sector = start;
+    while sector <= MAX_REG_SECTOR {
+      if data.len() > self.mini_fat.len() * self.mini_sector_size {
+        return Err(Error::new(ErrorKind::InvalidData, "Loop in the compound file mini FAT chain"));
+      }
+      let offset = sector as usize * self.mini_sector_size;
+      let mini_sector = mini_stream
+        .get(offset..offset + self.mini_sector_size)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, format!("Mini sector {} is out of bounds", sector)))?;
+      data.extend_from_slice(mini_sector);
+      sector = *self.mini_fat.get(sector as usize).unwrap_or(&END_OF_CHAIN);
+    }
+    Ok(data)
+  }
+
+  /// Returns the name and the data of every stream object.
+  fn streams(&self) -> Result<Vec<(String, Vec<u8>)>> {
+    let root = self
+      .directory
+      .iter()
+      .find(|entry| entry.object_type == 5)
+      .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Compound file has no root entry"))?;
+    let mini_stream = self.read_chain(root.start_sector)?;
+
+    let mut streams = vec![];
+    for entry in self.directory.iter().filter(|entry| entry.object_type == 2) {
+      let mut data = if entry.size < self.mini_stream_cutoff {
+        self.read_mini_chain(&mini_stream, entry.start_sector)?
+      } else {
+        self.read_chain(entry.start_sector)?
+      };
+      data.truncate(entry.size as usize);
+      streams.push((entry.name.clone(), data));
+    }
+    Ok(streams)
+  }
+}
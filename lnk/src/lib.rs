@@ -1,15 +1,50 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod carve;
+pub mod command_line;
+pub mod desktop;
+#[cfg(feature = "evidence")]
+pub mod evidence;
 pub mod extra_data;
+pub mod filter;
+pub mod index;
+pub mod jump_list;
 pub mod link_info;
-mod link_target_id_list;
+pub mod link_target_id_list;
+pub mod prefetch;
+#[cfg(feature = "redact")]
+pub mod redact;
+pub mod report;
+pub mod resolve;
+#[cfg(feature = "rules")]
+pub mod rules;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod shell_link_header;
+pub mod shellbags;
+pub mod spans;
+pub mod triage;
+pub mod validate;
+pub mod writer;
 
+pub use validate::validate;
+
+use command_line::{CommandLine, Profile};
+#[cfg(feature = "evidence")]
+use evidence::Hashes;
 use extra_data::{ExtraData, ExtraDataTypes};
 use link_info::LinkInfo;
 use link_target_id_list::LinkTargetIDList;
+use resolve::{Resolution, Resolver};
+#[cfg(feature = "rules")]
+use rules::RuleMatch;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::{ser::SerializeStruct, Serialize, Serializer};
 use shell_link_header::ShellLinkHeader;
+use spans::Spans;
 
 use chrono::{DateTime, Utc};
 use std::{
@@ -22,16 +57,23 @@ use winparsingtools::{
   traits::{Normalize, Path},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LnkFileMetaData {
   full_path: String,
-  mtime: DateTime<Utc>,
-  atime: DateTime<Utc>,
-  ctime: DateTime<Utc>,
+  /// The times are `None` when the source does not record them, e.g. an archive member without access time.
+  #[cfg_attr(feature = "schema", schemars(schema_with = "schema::optional_date_time"))]
+  mtime: Option<DateTime<Utc>>,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "schema::optional_date_time"))]
+  atime: Option<DateTime<Utc>>,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "schema::optional_date_time"))]
+  ctime: Option<DateTime<Utc>>,
+  /// The user profile the LNK file was found in.
+  pub user: Option<String>,
 }
 
 impl LnkFileMetaData {
-  fn from_path(path: &str) -> Result<Self> {
+  pub fn from_path(path: &str) -> Result<Self> {
     let file_metadata = fs::metadata(path)?;
     let full_path = match fs::canonicalize(path) {
       Ok(path_buf) => path_buf
@@ -46,11 +88,28 @@ impl LnkFileMetaData {
     let ctime: DateTime<Utc> = DateTime::from(file_metadata.modified()?);
     Ok(Self {
       full_path,
-      mtime,
-      ctime,
-      atime,
+      mtime: Some(mtime),
+      ctime: Some(ctime),
+      atime: Some(atime),
+      user: None,
     })
   }
+
+  /// Metadata of a LNK file that was not read from the file system, e.g. an archive member.
+  pub fn new(
+    full_path: String,
+    modified: Option<DateTime<Utc>>,
+    accessed: Option<DateTime<Utc>>,
+    created: Option<DateTime<Utc>>,
+  ) -> Self {
+    Self {
+      full_path,
+      mtime: modified,
+      atime: accessed,
+      ctime: created,
+      user: None,
+    }
+  }
 }
 
 impl Serialize for LnkFileMetaData {
@@ -59,17 +118,20 @@ impl Serialize for LnkFileMetaData {
     S: Serializer,
   {
     let tfmt = "%Y-%m-%dT%H:%M:%SZ";
-    let mut state = serializer.serialize_struct("LnkFileMetaData", 4)?;
+    let mut state = serializer.serialize_struct("LnkFileMetaData", 5)?;
     state.serialize_field("full_path", &self.full_path)?;
-    state.serialize_field("mtime", &format!("{}", self.mtime.format(tfmt)))?;
-    state.serialize_field("atime", &format!("{}", self.atime.format(tfmt)))?;
-    state.serialize_field("ctime", &format!("{}", self.ctime.format(tfmt)))?;
+    let time = |time: &Option<DateTime<Utc>>| time.map(|time| time.format(tfmt).to_string());
+    state.serialize_field("mtime", &time(&self.mtime))?;
+    state.serialize_field("atime", &time(&self.atime))?;
+    state.serialize_field("ctime", &time(&self.ctime))?;
+    state.serialize_field("user", &self.user)?;
     state.end()
   }
 }
 
 /// Reads LNK file and determine its parts then parses them
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Lnk {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub target_full_path: Option<String>,
@@ -77,6 +139,11 @@ pub struct Lnk {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub lnk_file_metadata: Option<LnkFileMetaData>,
 
+  /// The hashes of the LNK data, the whole file or buffer it was read from (with any trailing bytes) or the bytes
+  /// read by `Lnk::from_reader`.
+  #[cfg(feature = "evidence")]
+  pub hashes: Hashes,
+
   pub shell_link_header: ShellLinkHeader,
 
   #[serde(skip_serializing_if = "Option::is_none")]
@@ -86,22 +153,40 @@ pub struct Lnk {
   pub link_info: Option<LinkInfo>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
   pub name_string: Option<StringData>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
   pub relative_path: Option<StringData>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
   pub working_dir: Option<StringData>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
   pub command_line_arguments: Option<StringData>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
   pub icon_location: Option<StringData>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
   pub extra_data: Option<ExtraData>,
+
+  /// Where every field was read from, only set by `Lnk::from_buffer_with_spans`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub spans: Option<Spans>,
+
+  /// What was found at the target on a mounted volume, only set when a caller stores `Lnk::resolve` in it.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub resolution: Option<Resolution>,
+
+  /// The rules the LNK file matched, only set when a caller stores `RuleSet::evaluate` in it.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg(feature = "rules")]
+  pub rule_matches: Option<Vec<RuleMatch>>,
 }
 
 impl Lnk {
@@ -115,15 +200,75 @@ impl Lnk {
   /// ```
   pub fn from_path(path: &str) -> Result<Self> {
     let lnk_file_metadata = LnkFileMetaData::from_path(path)?;
-    let mut lnk_file_reader = fs::File::open(path)?;
-    let mut lnk_parser = Self::from_reader(&mut lnk_file_reader)?;
+    let mut lnk_parser = Self::from_buffer(&fs::read(path)?)?;
     lnk_parser.lnk_file_metadata = Some(lnk_file_metadata);
     Ok(lnk_parser)
   }
 
-  /// Parse the LNK file data from buffer
+  /// Parse the LNK file data from buffer, the hashes are those of the whole buffer.
   pub fn from_buffer(buf: &[u8]) -> Result<Self> {
-    Self::from_reader(&mut Cursor::new(buf))
+    let lnk = Self::parse(&mut Cursor::new(buf))?;
+    #[cfg(feature = "evidence")]
+    let lnk = Self {
+      hashes: Hashes::of(buf),
+      ..lnk
+    };
+    Ok(lnk)
+  }
+
+  /// Parse the LNK file data from buffer and record the byte span of every field, see [`spans`].
+  /// # Example
+  /// ```
+  /// use lnk::Lnk;
+  ///
+  /// let data = std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+  /// let lnk = Lnk::from_buffer_with_spans(&data).unwrap();
+  /// let json = serde_json::to_value(&lnk).unwrap();
+  /// assert_eq!(json["spans"]["link_info.local_base_path"]["offset"], 0x125);
+  /// ```
+  pub fn from_buffer_with_spans(buf: &[u8]) -> Result<Self> {
+    let mut lnk = Self::from_buffer(buf)?;
+    lnk.spans = Some(spans::spans(buf));
+    Ok(lnk)
+  }
+
+  /// The command line Windows runs when the shortcut is opened, with the environment variables of the target, the
+  /// arguments and the working directory expanded against `profile`. `None` when the target path is unknown.
+  /// # Example
+  /// ```
+  /// use lnk::{command_line::Profile, Lnk};
+  ///
+  /// let lnk = Lnk::from_path("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+  /// let command_line = lnk.command_line(&Profile::new()).unwrap();
+  /// assert_eq!(command_line.argv, vec![r"C:\Users\u0041\Desktop\test\test.txt"]);
+  /// ```
+  pub fn command_line(&self, profile: &Profile) -> Option<CommandLine> {
+    let target = self.path()?;
+    let string_data = |s: &Option<StringData>| s.as_ref().map(|s| s.to_string());
+    Some(CommandLine::new(
+      &target,
+      string_data(&self.command_line_arguments).as_deref(),
+      string_data(&self.working_dir).as_deref(),
+      profile,
+    ))
+  }
+
+  /// Find the target on the volume mounted for `resolver` and compare it with what the shortcut recorded. The known
+  /// folders are looked up in the profile of the user the LNK file was found in. `None` when the target path is
+  /// unknown or not on a mounted volume.
+  /// # Example
+  /// ```
+  /// use lnk::{resolve::Resolver, Lnk};
+  ///
+  /// let lnk = Lnk::from_path("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+  /// let resolution = lnk.resolve(&Resolver::new("/nonexistent")).unwrap();
+  /// assert!(!resolution.exists);
+  /// assert_eq!(resolution.mft_entry, Some(90070));
+  /// ```
+  pub fn resolve(&self, resolver: &Resolver) -> Option<Resolution> {
+    let user = self.lnk_file_metadata.as_ref().and_then(|m| m.user.as_deref());
+    let (path, exists) = resolver.map_path(&self.path()?, user)?;
+    Some(Resolution::new(path, exists, self))
   }
 
   /// Parse LNK file from an instance that implement `Read` & `Seek` traits.
@@ -140,6 +285,26 @@ impl Lnk {
   /// }
   /// ```
   pub fn from_reader<R: Read + Seek>(r: &mut R) -> Result<Self> {
+    #[cfg(feature = "evidence")]
+    let start = r.stream_position()?;
+    let lnk = Self::parse(r)?;
+    // Read the parsed bytes again to hash them, the reader is left at the end of the LNK data.
+    #[cfg(feature = "evidence")]
+    let lnk = {
+      let end = r.stream_position()?;
+      let mut data = vec![0; (end - start) as usize];
+      r.seek(std::io::SeekFrom::Start(start))?;
+      r.read_exact(&mut data)?;
+      Self {
+        hashes: Hashes::of(&data),
+        ..lnk
+      }
+    };
+    Ok(lnk)
+  }
+
+  /// Parse the sections of the LNK file, the hashes are set by the caller.
+  fn parse<R: Read + Seek>(r: &mut R) -> Result<Self> {
     let shell_link_header = ShellLinkHeader::from_reader(r)?;
     let mut link_target_id_list = None;
     let mut link_info = None;
@@ -177,6 +342,8 @@ impl Lnk {
     };
 
     let mut lnk_parser = Self {
+      #[cfg(feature = "evidence")]
+      hashes: Hashes::default(),
       shell_link_header,
       link_target_id_list,
       link_info,
@@ -188,6 +355,10 @@ impl Lnk {
       extra_data,
       lnk_file_metadata: None,
       target_full_path: None,
+      spans: None,
+      resolution: None,
+      #[cfg(feature = "rules")]
+      rule_matches: None,
     };
     lnk_parser.target_full_path = lnk_parser.path();
 
@@ -213,6 +384,57 @@ impl Path for Lnk {
   }
 }
 
+/// The normalized fields written by default as CSV columns and with `--normalize`, the other fields of
+/// [`NORMALIZED_FIELDS`] are only written when asked for so the output of the first versions does not change.
+pub const DEFAULT_NORMALIZED_FIELDS: &[&str] = &[
+  "target_full_path",
+  "target_modification_time",
+  "target_access_time",
+  "target_creation_time",
+  "target_size",
+  "target_hostname",
+  "lnk_full_path",
+  "lnk_modification_time",
+  "lnk_access_time",
+  "lnk_creation_time",
+  "user",
+];
+
+/// The fields returned by `Lnk::normalize`, in the order they are written to tabular outputs.
+pub const NORMALIZED_FIELDS: &[&str] = &[
+  "target_full_path",
+  "target_modification_time",
+  "target_access_time",
+  "target_creation_time",
+  "target_size",
+  "target_hostname",
+  "lnk_full_path",
+  "lnk_modification_time",
+  "lnk_access_time",
+  "lnk_creation_time",
+  "user",
+  "drive_type",
+  "volume_serial_number",
+  "volume_label",
+  "net_name",
+  "device_name",
+  "name_string",
+  "relative_path",
+  "working_dir",
+  "command_line_arguments",
+  "command_line",
+  "argv",
+  "icon_location",
+  "file_droid",
+  "file_droid_birth",
+  "volume_droid",
+  "volume_droid_birth",
+  "rule_matches",
+  "lnk_md5",
+  "lnk_sha1",
+  "lnk_sha256",
+];
+
 impl Normalize for Lnk {
   fn normalize(&self) -> HashMap<String, String> {
     let mut fields: HashMap<String, String> = HashMap::new();
@@ -226,6 +448,10 @@ impl Normalize for Lnk {
     let lnk_access_time;
     let lnk_creation_time;
     let mut target_hostname = String::new();
+    let mut file_droid = String::new();
+    let mut file_droid_birth = String::new();
+    let mut volume_droid = String::new();
+    let mut volume_droid_birth = String::new();
 
     target_full_path = match &self.path() {
       Some(path) => path.to_owned(),
@@ -243,6 +469,10 @@ impl Normalize for Lnk {
         extra_data.extra_data_blocks.iter().find(|&edb| match edb {
           ExtraDataTypes::Tracker(tracker) => {
             target_hostname = tracker.machine_id.to_owned();
+            file_droid = tracker.file_droid.to_string();
+            file_droid_birth = tracker.file_droid_birth.to_string();
+            volume_droid = tracker.volume_droid.to_string();
+            volume_droid_birth = tracker.volume_droid_birth.to_string();
             true
           },
         });
@@ -256,17 +486,41 @@ impl Normalize for Lnk {
     };
 
     lnk_modification_time = match &self.lnk_file_metadata {
-      Some(lnk_file_metadata) => lnk_file_metadata.mtime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+      Some(lnk_file_metadata) => lnk_file_metadata
+        .mtime
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default(),
       None => String::new(),
     };
 
     lnk_access_time = match &self.lnk_file_metadata {
-      Some(lnk_file_metadata) => lnk_file_metadata.atime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+      Some(lnk_file_metadata) => lnk_file_metadata
+        .atime
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default(),
       None => String::new(),
     };
 
     lnk_creation_time = match &self.lnk_file_metadata {
-      Some(lnk_file_metadata) => lnk_file_metadata.ctime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+      Some(lnk_file_metadata) => lnk_file_metadata
+        .ctime
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default(),
+      None => String::new(),
+    };
+
+    let user = match &self.lnk_file_metadata {
+      Some(lnk_file_metadata) => lnk_file_metadata.user.to_owned().unwrap_or_default(),
+      None => String::new(),
+    };
+
+    let volume_id = self.link_info.as_ref().and_then(|link_info| link_info.volume_id.as_ref());
+    let common_network_relative_link = self
+      .link_info
+      .as_ref()
+      .and_then(|link_info| link_info.common_network_relative_link.as_ref());
+    let string_data = |s: &Option<StringData>| match s {
+      Some(s) => s.to_string(),
       None => String::new(),
     };
 
@@ -280,6 +534,61 @@ impl Normalize for Lnk {
     fields.insert("lnk_modification_time".to_string(), lnk_modification_time);
     fields.insert("lnk_access_time".to_string(), lnk_access_time);
     fields.insert("lnk_creation_time".to_string(), lnk_creation_time);
+    fields.insert("user".to_string(), user);
+    fields.insert("drive_type".to_string(), volume_id.map(|v| format!("{:?}", v.drive_type)).unwrap_or_default());
+    fields
+      .insert("volume_serial_number".to_string(), volume_id.map(|v| v.serial_number.to_owned()).unwrap_or_default());
+    fields.insert("volume_label".to_string(), volume_id.and_then(|v| v.volume_lable.to_owned()).unwrap_or_default());
+    fields.insert(
+      "net_name".to_string(),
+      common_network_relative_link
+        .and_then(|c| c.net_name.to_owned())
+        .unwrap_or_default(),
+    );
+    fields.insert(
+      "device_name".to_string(),
+      common_network_relative_link
+        .and_then(|c| c.device_name.to_owned())
+        .unwrap_or_default(),
+    );
+    fields.insert("name_string".to_string(), string_data(&self.name_string));
+    fields.insert("relative_path".to_string(), string_data(&self.relative_path));
+    fields.insert("working_dir".to_string(), string_data(&self.working_dir));
+    fields.insert("command_line_arguments".to_string(), string_data(&self.command_line_arguments));
+    let user = self.lnk_file_metadata.as_ref().and_then(|m| m.user.as_deref());
+    let command_line = self.command_line(&Profile::windows_defaults(user));
+    fields
+      .insert("command_line".to_string(), command_line.as_ref().map(|c| c.command_line.to_owned()).unwrap_or_default());
+    fields.insert(
+      "argv".to_string(),
+      command_line
+        .map(|c| serde_json::to_string(&c.argv).unwrap())
+        .unwrap_or_default(),
+    );
+    fields.insert("icon_location".to_string(), string_data(&self.icon_location));
+    fields.insert("file_droid".to_string(), file_droid);
+    fields.insert("file_droid_birth".to_string(), file_droid_birth);
+    fields.insert("volume_droid".to_string(), volume_droid);
+    fields.insert("volume_droid_birth".to_string(), volume_droid_birth);
+    // The fields of disabled features are empty, every field of NORMALIZED_FIELDS is always present.
+    #[cfg(feature = "rules")]
+    let rule_matches = self
+      .rule_matches
+      .iter()
+      .flatten()
+      .map(|m| m.id.as_str())
+      .collect::<Vec<&str>>()
+      .join(",");
+    #[cfg(not(feature = "rules"))]
+    let rule_matches = String::new();
+    fields.insert("rule_matches".to_string(), rule_matches);
+    #[cfg(feature = "evidence")]
+    let (md5, sha1, sha256) = (self.hashes.md5.to_owned(), self.hashes.sha1.to_owned(), self.hashes.sha256.to_owned());
+    #[cfg(not(feature = "evidence"))]
+    let (md5, sha1, sha256) = (String::new(), String::new(), String::new());
+    fields.insert("lnk_md5".to_string(), md5);
+    fields.insert("lnk_sha1".to_string(), sha1);
+    fields.insert("lnk_sha256".to_string(), sha256);
     fields
   }
 }
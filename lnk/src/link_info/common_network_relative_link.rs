@@ -1,16 +1,20 @@
 //! [CommonNetworkRelativeLink](https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/23bb5877-e3dd-4799-9f50-79f05f938537) related structs
 
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::io::{Cursor, Read, Result, Seek, SeekFrom};
 use winparsingtools::{traits::Path, utils};
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum CommonNetworkRelativeLinkFlags {
   ValidDevice,
   ValidNetType,
 }
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum NetworkProviderType {
   WNNC_NET_MSNET,
   WNNC_NET_SMB_LANMAN,
@@ -163,6 +167,7 @@ impl From<u32> for NetworkProviderType {
 /// The CommonNetworkRelativeLink structure specifies information about the network location where a
 /// link target is stored, including the mapped drive letter and the UNC path prefix.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct CommonNetworkRelativeLink {
   #[serde(skip_serializing)]
   pub size: u32,
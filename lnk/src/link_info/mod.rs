@@ -4,6 +4,8 @@ mod common_network_relative_link;
 mod volume_id;
 use byteorder::{LittleEndian, ReadBytesExt};
 pub use common_network_relative_link::CommonNetworkRelativeLink;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::{Serialize, Serializer};
 use std::fmt::{self, Display};
 use std::io::{Cursor, Read, Result, Seek, SeekFrom};
@@ -12,6 +14,7 @@ use winparsingtools::{traits::Path, utils};
 
 /// The LinkInfo structure specifies information necessary to resolve a link target if it is not found in its original location.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LinkInfo {
   #[serde(skip_serializing)]
   pub size: u32,
@@ -20,6 +23,7 @@ pub struct LinkInfo {
   pub header_size: u32,
 
   #[serde(skip_serializing)]
+  #[cfg_attr(feature = "schema", schemars(skip))]
   pub flags: LinkInfoFlags,
 
   #[serde(skip_serializing)]
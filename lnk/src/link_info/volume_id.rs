@@ -1,10 +1,13 @@
 //! [VolumeID](https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/b7b3eea7-dbff-4275-bd58-83ba3f12d87a) related structs
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::io::{Cursor, Read, Result, Seek, SeekFrom};
 use winparsingtools::utils;
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum VolumeIDDriveType {
   DRIVE_UNKNOWN,     //The drive type cannot be determined.
   DRIVE_NO_ROOT_DIR, //The root path is invalid; for example, there is no volume mounted at the path.
@@ -31,12 +34,13 @@ impl From<u32> for VolumeIDDriveType {
 
 /// The VolumeID structure specifies information about the volume that a link target was on when the link was created.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct VolumeID {
   #[serde(skip_serializing)]
   pub size: u32,
 
-  drive_type: VolumeIDDriveType,
-  serial_number: String,
+  pub drive_type: VolumeIDDriveType,
+  pub serial_number: String,
 
   #[serde(skip_serializing)]
   pub volume_lable_offset: u32,
@@ -45,7 +49,7 @@ pub struct VolumeID {
   pub volume_lable_offset_unicode: Option<u32>,
 
   #[serde(skip_serializing_if = "Option::is_none")]
-  volume_lable: Option<String>,
+  pub volume_lable: Option<String>,
 }
 
 impl VolumeID {
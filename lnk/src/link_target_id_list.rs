@@ -1,14 +1,25 @@
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::io::{Cursor, Read, Result, Seek};
-use winparsingtools::{structs::shell_items::IDList, traits::Path};
+use winparsingtools::{
+  structs::shell_items::{IDList, ShellItem, ShellItemTypes},
+  traits::Path,
+};
 
 /// The [LinkTargetIDList](https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/881d7a83-07a5-4702-93e3-f9fc34c3e1e4) structure specifies the target of the link.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LinkTargetIDList {
   #[serde(skip_serializing)]
   pub size: u16,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::shell_items"))]
   pub id_list: IDList,
+  /// The same items as `id_list`, `IDList` does not give access to the individual items.
+  #[serde(skip_serializing)]
+  #[cfg_attr(feature = "schema", schemars(skip))]
+  pub shell_items: Vec<ShellItem>,
 }
 
 impl LinkTargetIDList {
@@ -21,7 +32,41 @@ impl LinkTargetIDList {
     let mut id_list_data = vec![0; size as usize];
     r.read_exact(&mut id_list_data)?;
     let id_list = IDList::from_buffer(&id_list_data)?;
-    Ok(Self { size, id_list })
+    let shell_items = Self::read_shell_items(&id_list_data)?;
+    Ok(Self {
+      size,
+      id_list,
+      shell_items,
+    })
+  }
+
+  /// The MFT entry of the target, from the extension block of the last file entry shell item.
+  pub fn mft_entry(&self) -> Option<u64> {
+    self.mft_reference().map(|(mft_entry, _)| mft_entry)
+  }
+
+  /// The MFT entry and sequence number of the target, from the extension block of the last file entry shell item.
+  pub fn mft_reference(&self) -> Option<(u64, u16)> {
+    let Some(ShellItemTypes::FileEntry(file_entry)) = &self.shell_items.last()?.shell_item_data else {
+      return None;
+    };
+    // `FileReference` only exposes its fields through `Serialize`.
+    let file_ref = serde_json::to_value(file_entry.extention_block.as_ref()?.file_ref.as_ref()?).ok()?;
+    Some((file_ref["mft_entry"].as_u64()?, file_ref["sequence_number"].as_u64()? as u16))
+  }
+
+  fn read_shell_items(id_list_data: &[u8]) -> Result<Vec<ShellItem>> {
+    let mut shell_items = vec![];
+    let mut offset = 0;
+    while offset + 2 <= id_list_data.len() {
+      let size = u16::from_le_bytes([id_list_data[offset], id_list_data[offset + 1]]) as usize;
+      if size == 0 || offset + size > id_list_data.len() {
+        break;
+      }
+      shell_items.push(ShellItem::from_buffer(&id_list_data[offset..offset + size])?);
+      offset += size;
+    }
+    Ok(shell_items)
   }
 }
 
@@ -0,0 +1,303 @@
+//! [Prefetch](https://github.com/libyal/libscca/blob/main/documentation/Windows%20Prefetch%20File%20(PF)%20format.asciidoc)
+//! (`*.pf`) parser, versions 17 (Windows XP), 23 (Vista and 7), 26 (8.1) and 30 (10 and 11).
+//!
+//! Windows 10 and 11 store prefetch files compressed in a `MAM` container, they are decompressed with [`xpress`]
+//! before they are parsed. The checksum of compressed files is not verified.
+
+pub mod xpress;
+
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  fmt, fs,
+  io::{Cursor, Error, ErrorKind, Result},
+};
+use winparsingtools::{date_time::FileTime, traits::Normalize, utils::read_utf16_string};
+
+const MAM_SIGNATURE: &[u8; 3] = b"MAM";
+const SCCA_SIGNATURE: &[u8; 4] = b"SCCA";
+const XPRESS_HUFFMAN: u32 = 4;
+const HEADER_SIZE: usize = 84;
+
+fn invalid(message: String) -> Error {
+  Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Bounds checked little endian reads.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+  fn bytes(&self, offset: usize, size: usize) -> Result<&'a [u8]> {
+    offset.checked_add(size).and_then(|end| self.0.get(offset..end)).ok_or_else(|| {
+      Error::new(ErrorKind::UnexpectedEof, format!("{} bytes at offset {} are out of bounds", size, offset))
+    })
+  }
+
+  fn u16(&self, offset: usize) -> Result<u16> {
+    let bytes = self.bytes(offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn u32(&self, offset: usize) -> Result<u32> {
+    let bytes = self.bytes(offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  fn u64(&self, offset: usize) -> Result<u64> {
+    Ok(self.u32(offset)? as u64 | (self.u32(offset + 4)? as u64) << 32)
+  }
+
+  /// `chars` UTF-16 characters, or up to the first NUL.
+  fn utf16(&self, offset: usize, chars: usize) -> Result<String> {
+    read_utf16_string(&mut Cursor::new(self.bytes(offset, chars * 2)?), Some(chars))
+  }
+}
+
+/// An NTFS file reference, the MFT entry and its sequence number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FileReference {
+  pub mft_entry: u64,
+  pub sequence_number: u16,
+}
+
+impl FileReference {
+  fn new(value: u64) -> Self {
+    Self {
+      mft_entry: value & 0xFFFF_FFFF_FFFF,
+      sequence_number: (value >> 48) as u16,
+    }
+  }
+}
+
+impl fmt::Display for FileReference {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}-{}", self.mft_entry, self.sequence_number)
+  }
+}
+
+/// A volume the executable loaded files from.
+#[derive(Debug, Serialize)]
+pub struct Volume {
+  /// The NT device path (`\VOLUME{...}` or `\DEVICE\HARDDISKVOLUME1`).
+  pub device_path: String,
+  pub creation_time: FileTime,
+  /// Formatted as `XXXX-XXXX`.
+  pub serial_number: String,
+  pub directories: Vec<String>,
+  /// The MFT references of the files and directories loaded from the volume.
+  pub file_references: Vec<FileReference>,
+}
+
+/// A parsed prefetch file.
+#[derive(Debug, Serialize)]
+pub struct Prefetch {
+  pub version: u32,
+  /// The executable name from the header, truncated to 29 characters by Windows.
+  pub executable_name: String,
+  /// Formatted as eight hexadecimal digits, the suffix of the file name.
+  pub prefetch_hash: String,
+  pub run_count: u32,
+  /// The last time the executable ran, then up to seven earlier runs for versions 26 and 30.
+  pub last_run_times: Vec<FileTime>,
+  pub volumes: Vec<Volume>,
+  /// The files loaded in the first ten seconds of the run.
+  pub filenames: Vec<String>,
+  /// The entry of `filenames` that ends with the executable name.
+  pub executable_path: Option<String>,
+  /// The path of the prefetch file, when it was read from a path.
+  pub pf_full_path: Option<String>,
+}
+
+impl Prefetch {
+  /// Parse a prefetch file from path.
+  pub fn from_path(path: &str) -> Result<Self> {
+    Ok(Self::from_buffer(&fs::read(path)?)?.located_at(path))
+  }
+
+  /// Set `pf_full_path` to the canonical form of the path the data was read from.
+  pub fn located_at(mut self, path: &str) -> Self {
+    self.pf_full_path = Some(match fs::canonicalize(path) {
+      Ok(path_buf) => path_buf.to_string_lossy().replace(r"\\?\", ""),
+      Err(_) => path.to_string(),
+    });
+    self
+  }
+
+  /// Parse a prefetch file from buffer, compressed (`MAM`) or not (`SCCA`).
+  pub fn from_buffer(buf: &[u8]) -> Result<Self> {
+    if buf.starts_with(MAM_SIGNATURE) {
+      Self::from_uncompressed(&Self::decompress(buf)?)
+    } else {
+      Self::from_uncompressed(buf)
+    }
+  }
+
+  /// Decompress a `MAM` container to the `SCCA` data it holds.
+  pub fn decompress(buf: &[u8]) -> Result<Vec<u8>> {
+    let r = Reader(buf);
+    let signature = r.u32(0)?;
+    if !buf.starts_with(MAM_SIGNATURE) {
+      return Err(invalid("not a MAM compressed prefetch file".to_string()));
+    }
+    let algorithm = (signature >> 24) & 0x0F;
+    if algorithm != XPRESS_HUFFMAN {
+      return Err(invalid(format!("unsupported MAM compression algorithm {}", algorithm)));
+    }
+    let size = r.u32(4)? as usize;
+    // The checksum follows the size when the high bits of the signature are set.
+    let start = if signature >> 28 != 0 { 12 } else { 8 };
+    let compressed = buf
+      .get(start..)
+      .ok_or_else(|| invalid("the MAM header is truncated".to_string()))?;
+    xpress::decompress(compressed, size)
+  }
+
+  /// Parse uncompressed (`SCCA`) prefetch data.
+  pub fn from_uncompressed(buf: &[u8]) -> Result<Self> {
+    let r = Reader(buf);
+    if r.bytes(4, 4)? != SCCA_SIGNATURE {
+      return Err(invalid("not a prefetch file, the SCCA signature is missing".to_string()));
+    }
+    let version = r.u32(0)?;
+    let executable_name = r.utf16(16, 30)?;
+    let prefetch_hash = format!("{:08X}", r.u32(76)?);
+
+    let info = HEADER_SIZE;
+    let metrics_offset = r.u32(info)?;
+    let (run_times_offset, run_times, run_count_offset, volume_size) = match version {
+      17 => (36, 1, 60, 40),
+      23 => (44, 1, 68, 104),
+      26 => (44, 8, 124, 104),
+      // Some Windows 10 files have a shorter file information section, it ends where the metrics start.
+      30 if metrics_offset == 304 => (44, 8, 124, 96),
+      30 => (44, 8, 116, 96),
+      version => return Err(invalid(format!("unsupported prefetch version {}", version))),
+    };
+    let run_count = r.u32(info + run_count_offset)?;
+    let mut last_run_times = vec![];
+    for i in 0..run_times {
+      let time = r.u64(info + run_times_offset + i * 8)?;
+      if time != 0 {
+        last_run_times.push(FileTime::new(time));
+      }
+    }
+
+    let filenames_offset = r.u32(info + 16)? as usize;
+    let filenames_size = r.u32(info + 20)? as usize;
+    let filenames_data = r.bytes(filenames_offset, filenames_size)?;
+    let filenames: Vec<String> = String::from_utf16_lossy(
+      &filenames_data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<u16>>(),
+    )
+    .split('\0')
+    .filter(|name| !name.is_empty())
+    .map(str::to_string)
+    .collect();
+    let suffix = format!("\\{}", executable_name.to_uppercase());
+    let executable_path = filenames
+      .iter()
+      .find(|name| !executable_name.is_empty() && name.to_uppercase().ends_with(&suffix))
+      .cloned();
+
+    let volumes_offset = r.u32(info + 24)? as usize;
+    let volumes_count = r.u32(info + 28)? as usize;
+    let mut volumes = vec![];
+    for i in 0..volumes_count {
+      volumes.push(Self::read_volume(&r, version, volumes_offset, volumes_offset + i * volume_size)?);
+    }
+
+    Ok(Self {
+      version,
+      executable_name,
+      prefetch_hash,
+      run_count,
+      last_run_times,
+      volumes,
+      filenames,
+      executable_path,
+      pf_full_path: None,
+    })
+  }
+
+  /// Read the volume entry at `entry`, its offsets are relative to the start of the volumes section.
+  fn read_volume(r: &Reader, version: u32, volumes_offset: usize, entry: usize) -> Result<Volume> {
+    let device_path = r.utf16(volumes_offset + r.u32(entry)? as usize, r.u32(entry + 4)? as usize)?;
+    let creation_time = FileTime::new(r.u64(entry + 8)?);
+    let serial = r.u32(entry + 16)?;
+    let serial_number = format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF);
+
+    let mut file_references = vec![];
+    if r.u32(entry + 24)? != 0 {
+      let references = volumes_offset + r.u32(entry + 20)? as usize;
+      let first = references + if version == 17 { 8 } else { 16 };
+      for i in 0..r.u32(references + 4)? as usize {
+        match r.u64(first + i * 8)? {
+          0 => {},
+          value => file_references.push(FileReference::new(value)),
+        }
+      }
+    }
+
+    let mut directories = vec![];
+    let mut offset = volumes_offset + r.u32(entry + 28)? as usize;
+    for _ in 0..r.u32(entry + 32)? {
+      let chars = r.u16(offset)? as usize;
+      directories.push(r.utf16(offset + 2, chars)?);
+      // Every string is followed by a NUL character.
+      offset += 2 + (chars + 1) * 2;
+    }
+
+    Ok(Volume {
+      device_path,
+      creation_time,
+      serial_number,
+      directories,
+      file_references,
+    })
+  }
+}
+
+/// The fields returned by `Prefetch::normalize`, in the order they are written to tabular outputs.
+/// Lists are joined with `|`.
+pub const PREFETCH_NORMALIZED_FIELDS: &[&str] = &[
+  "pf_full_path",
+  "version",
+  "executable_name",
+  "executable_path",
+  "prefetch_hash",
+  "run_count",
+  "last_run_time",
+  "last_run_times",
+  "volume_device_paths",
+  "volume_serial_numbers",
+  "volume_creation_times",
+  "file_names",
+];
+
+fn join<T: ToString>(items: impl Iterator<Item = T>) -> String {
+  items.map(|item| item.to_string()).collect::<Vec<_>>().join("|")
+}
+
+impl Normalize for Prefetch {
+  fn normalize(&self) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    fields.insert("pf_full_path".to_string(), self.pf_full_path.clone().unwrap_or_default());
+    fields.insert("version".to_string(), self.version.to_string());
+    fields.insert("executable_name".to_string(), self.executable_name.to_owned());
+    fields.insert("executable_path".to_string(), self.executable_path.clone().unwrap_or_default());
+    fields.insert("prefetch_hash".to_string(), self.prefetch_hash.to_owned());
+    fields.insert("run_count".to_string(), self.run_count.to_string());
+    fields.insert(
+      "last_run_time".to_string(),
+      self.last_run_times.first().map(|time| time.to_string()).unwrap_or_default(),
+    );
+    fields.insert("last_run_times".to_string(), join(self.last_run_times.iter()));
+    fields.insert("volume_device_paths".to_string(), join(self.volumes.iter().map(|volume| &volume.device_path)));
+    fields.insert("volume_serial_numbers".to_string(), join(self.volumes.iter().map(|volume| &volume.serial_number)));
+    fields.insert("volume_creation_times".to_string(), join(self.volumes.iter().map(|volume| &volume.creation_time)));
+    fields.insert("file_names".to_string(), join(self.filenames.iter()));
+    fields
+  }
+}
@@ -0,0 +1,173 @@
+//! [LZXPRESS Huffman](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-xca/a8b7cb0a-92a6-4187-a23b-5e14273b96f8)
+//! decompression (MS-XCA 2.1 and 2.2), the compression of the `MAM` prefetch files of Windows 10 and 11.
+//!
+//! The data is a sequence of blocks of up to 64 KiB of output. Every block starts with the 4-bit code lengths of
+//! the 512 symbols (256 literals and 256 match headers) followed by the codes, which are read most significant bit
+//! first from 16-bit little endian words.
+
+use std::io::{Error, ErrorKind, Result};
+
+const BLOCK_SIZE: usize = 65536;
+const SYMBOLS: usize = 512;
+const MAX_CODE_LENGTH: usize = 15;
+
+fn invalid(message: &str) -> Error {
+  Error::new(ErrorKind::InvalidData, format!("invalid LZXPRESS Huffman data: {}", message))
+}
+
+/// Symbol and code length of every 15-bit value, the canonical Huffman code of a block.
+struct DecodingTable {
+  entries: Vec<(u16, u8)>,
+}
+
+impl DecodingTable {
+  fn new(code_lengths: &[u8]) -> Result<Self> {
+    let length = |symbol: usize| (code_lengths[symbol / 2] >> (4 * (symbol % 2))) & 0xF;
+    let mut entries = vec![(0, 0); 1 << MAX_CODE_LENGTH];
+    let mut position = 0;
+    // Codes are assigned by increasing length, then by increasing symbol.
+    for code_length in 1..=MAX_CODE_LENGTH as u8 {
+      let span = 1 << (MAX_CODE_LENGTH - code_length as usize);
+      for symbol in (0..SYMBOLS).filter(|symbol| length(*symbol) == code_length) {
+        if position + span > entries.len() {
+          return Err(invalid("over-subscribed code lengths"));
+        }
+        entries[position..position + span].fill((symbol as u16, code_length));
+        position += span;
+      }
+    }
+    if position == 0 {
+      return Err(invalid("empty Huffman table"));
+    }
+    Ok(Self { entries })
+  }
+}
+
+/// Reads the codes, and the bytes of long match lengths that are stored between them.
+struct BitReader<'a> {
+  input: &'a [u8],
+  position: usize,
+  bits: u32,
+  /// Number of bits in `bits` beyond the 16 that are always available.
+  extra_bits: i32,
+}
+
+impl<'a> BitReader<'a> {
+  fn new(input: &'a [u8], position: usize) -> Self {
+    let mut reader = Self {
+      input,
+      position,
+      bits: 0,
+      extra_bits: 16,
+    };
+    reader.bits = (reader.word() << 16) | reader.word();
+    reader
+  }
+
+  /// The next 16-bit word, a stream may end before the last bits are refilled.
+  fn word(&mut self) -> u32 {
+    let word = match self.input.get(self.position..self.position + 2) {
+      Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+      None => 0,
+    };
+    self.position += 2;
+    word
+  }
+
+  fn peek(&self, count: u32) -> u32 {
+    match count {
+      0 => 0,
+      count => self.bits >> (32 - count),
+    }
+  }
+
+  fn consume(&mut self, count: u32) {
+    self.bits = self.bits.checked_shl(count).unwrap_or(0);
+    self.extra_bits -= count as i32;
+    if self.extra_bits < 0 {
+      self.bits |= self.word() << -self.extra_bits;
+      self.extra_bits += 16;
+    }
+  }
+
+  fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+    let bytes = self
+      .input
+      .get(self.position..self.position + count)
+      .ok_or_else(|| invalid("truncated match length"))?;
+    self.position += count;
+    Ok(bytes)
+  }
+}
+
+/// Decompress `input` to `output_size` bytes.
+/// # Example
+/// ```
+/// use lnk::prefetch::xpress::decompress;
+///
+/// // Every symbol has a 9-bit code, so the code of a literal is its value.
+/// let mut input = vec![0x99; 256];
+/// // 'a' (0_0110_0001) and 'b' (0_0110_0010) in the words 0x3098 and 0x8000, followed by padding.
+/// input.extend([0x98, 0x30, 0x00, 0x80, 0x00, 0x00]);
+/// assert_eq!(decompress(&input, 2).unwrap(), b"ab");
+/// ```
+pub fn decompress(input: &[u8], output_size: usize) -> Result<Vec<u8>> {
+  // Every block starts with its Huffman table and holds at most BLOCK_SIZE bytes of output.
+  if output_size > input.len() / (SYMBOLS / 2) * BLOCK_SIZE {
+    return Err(invalid("the decompressed size is larger than the data can hold"));
+  }
+  // The size comes from the file, only a bounded part of it is reserved before the data is decoded.
+  let mut output = Vec::with_capacity(output_size.min(input.len() * 8).min(BLOCK_SIZE * 16));
+  let mut position = 0;
+  while output.len() < output_size {
+    let code_lengths = input
+      .get(position..position + SYMBOLS / 2)
+      .ok_or_else(|| invalid("truncated Huffman table"))?;
+    let table = DecodingTable::new(code_lengths)?;
+    let mut reader = BitReader::new(input, position + SYMBOLS / 2);
+    let block_end = (output.len() + BLOCK_SIZE).min(output_size);
+    while output.len() < block_end {
+      if reader.position > input.len() + 4 {
+        return Err(invalid("truncated data"));
+      }
+      let (symbol, code_length) = table.entries[reader.peek(MAX_CODE_LENGTH as u32) as usize];
+      if code_length == 0 {
+        return Err(invalid("unassigned code"));
+      }
+      reader.consume(code_length as u32);
+      if symbol < 256 {
+        output.push(symbol as u8);
+        continue;
+      }
+
+      let symbol = symbol - 256;
+      let offset_bits = (symbol >> 4) as u32;
+      let mut match_length = (symbol & 0xF) as usize;
+      if match_length == 15 {
+        match_length = reader.bytes(1)?[0] as usize;
+        if match_length == 255 {
+          let bytes = reader.bytes(2)?;
+          match_length = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+          if match_length == 0 {
+            let bytes = reader.bytes(4)?;
+            match_length = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+          }
+          match_length = match_length.checked_sub(15).ok_or_else(|| invalid("match length"))?;
+        }
+        match_length += 15;
+      }
+      match_length += 3;
+      let offset = reader.peek(offset_bits) as usize + (1 << offset_bits);
+      reader.consume(offset_bits);
+      if offset > output.len() {
+        return Err(invalid("match offset before the start of the data"));
+      }
+      // The copy overlaps the bytes it writes when the offset is smaller than the length.
+      for _ in 0..match_length.min(output_size - output.len()) {
+        output.push(output[output.len() - offset]);
+      }
+    }
+    position = reader.position;
+  }
+  Ok(output)
+}
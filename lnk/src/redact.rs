@@ -0,0 +1,249 @@
+//! Replace personal data in parsed records with keyed pseudonyms.
+//!
+//! Every value is replaced by a token derived from HMAC-SHA256 of the value under a secret key, so the same user,
+//! host, share, volume or network card always gets the same token and redacted records can still be correlated, but
+//! the values can not be recovered or guessed without the key. Names are compared case-insensitively like Windows
+//! does, `Bob` and `BOB` get the same token.
+//!
+//! User names are found in paths (`...\Users\<name>\...`), host and share names in UNC paths (`\\host\share\...`),
+//! and the other values in the fields that hold them. Volume serial numbers and the MAC addresses of version 1
+//! droids keep their format so the records still fit the same schema.
+
+use hmac::{Hmac, KeyInit, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+
+/// The kind of a redacted value, values of different kinds get different tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  User,
+  Host,
+  Share,
+  VolumeSerial,
+  VolumeLabel,
+  Mac,
+}
+
+impl Kind {
+  fn name(self) -> &'static str {
+    match self {
+      Kind::User => "USER",
+      Kind::Host => "HOST",
+      Kind::Share => "SHARE",
+      Kind::VolumeSerial => "SERIAL",
+      Kind::VolumeLabel => "LABEL",
+      Kind::Mac => "MAC",
+    }
+  }
+}
+
+/// Profile directories that are not named after a user.
+const SHARED_PROFILES: &[&str] = &["public", "default", "default user", "all users", "defaultapppool"];
+
+/// The fields of the JSON records and of the normalized records holding a value that is not a path.
+const FIELDS: &[(&str, Kind)] = &[
+  ("user", Kind::User),
+  ("machine_id", Kind::Host),
+  ("target_hostname", Kind::Host),
+  ("serial_number", Kind::VolumeSerial),
+  ("volume_serial_number", Kind::VolumeSerial),
+  ("volume_lable", Kind::VolumeLabel),
+  ("volume_label", Kind::VolumeLabel),
+  ("file_droid", Kind::Mac),
+  ("file_droid_birth", Kind::Mac),
+  ("volume_droid", Kind::Mac),
+  ("volume_droid_birth", Kind::Mac),
+];
+
+/// Replaces personal data with pseudonyms keyed by a secret.
+/// # Example
+/// ```
+/// use lnk::redact::Redactor;
+///
+/// let redactor = Redactor::new(b"secret");
+/// let redacted = redactor.redact_path(r"\\fs01\Finance\Users\Bob\q3.xlsx");
+/// assert!(redacted.starts_with(r"\\HOST-"));
+/// assert!(!redacted.to_lowercase().contains("bob"));
+/// assert_eq!(redacted.to_lowercase(), redactor.redact_path(r"\\FS01\finance\users\BOB\Q3.xlsx").to_lowercase());
+/// ```
+#[derive(Clone)]
+pub struct Redactor {
+  key: Vec<u8>,
+}
+
+impl Redactor {
+  pub fn new(key: &[u8]) -> Self {
+    Self { key: key.to_vec() }
+  }
+
+  fn digest(&self, kind: Kind, value: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+    mac.update(kind.name().as_bytes());
+    mac.update(&[0]);
+    mac.update(value.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+  }
+
+  /// The token of a value: `<KIND>-<12 hex digits>`, `XXXX-XXXX` for volume serial numbers and 12 hex digits for
+  /// MAC addresses. Empty values stay empty.
+  pub fn pseudonym(&self, kind: Kind, value: &str) -> String {
+    if value.is_empty() {
+      return String::new();
+    }
+    let value = match kind {
+      Kind::VolumeSerial => value.to_uppercase(),
+      _ => value.to_lowercase(),
+    };
+    let hex: String = self.digest(kind, &value).iter().map(|b| format!("{:02x}", b)).collect();
+    match kind {
+      Kind::VolumeSerial => format!("{}-{}", &hex[..4], &hex[4..8]).to_uppercase(),
+      Kind::Mac => hex[..12].to_string(),
+      _ => format!("{}-{}", kind.name(), &hex[..12]),
+    }
+  }
+
+  /// Replace the node of a version 1 GUID, the MAC address of the machine that created it. Other GUIDs are random
+  /// and kept as they are.
+  pub fn redact_droid(&self, guid: &str) -> String {
+    let is_version_1 = guid.len() == 36 && guid.as_bytes()[14] == b'1' && guid.as_bytes()[23] == b'-';
+    if !is_version_1 {
+      return guid.to_string();
+    }
+    let node = self.pseudonym(Kind::Mac, &guid[24..]);
+    let node = match guid[24..].chars().any(|c| c.is_ascii_uppercase()) {
+      true => node.to_uppercase(),
+      false => node,
+    };
+    format!("{}{}", &guid[..24], node)
+  }
+
+  /// Replace a name at the start of a path component, a quote can end the path right after it.
+  fn redact_name(&self, kind: Kind, component: &str) -> String {
+    let end = component.find(['"', '\'']).unwrap_or(component.len());
+    let (name, suffix) = component.split_at(end);
+    format!("{}{}", self.pseudonym(kind, name), suffix)
+  }
+
+  /// Replace the user names (the component after `Users` or `Documents and Settings`) and the host and share
+  /// names of UNC paths in a string. The paths can be anywhere in the string, e.g. in command line arguments.
+  pub fn redact_path(&self, s: &str) -> String {
+    let mut redacted = String::with_capacity(s.len());
+    // The previous non-empty component, lowercase, and the number of separators after it.
+    let mut previous = String::new();
+    let mut separators = 0;
+    // A UNC path starts at the beginning of the string or after a space or a quote.
+    let mut unc_start = true;
+    let mut after_host = false;
+    let mut rest = s;
+    while !rest.is_empty() {
+      let end = rest.find(['\\', '/']).unwrap_or(rest.len());
+      let (component, after) = rest.split_at(end);
+      if !component.is_empty() {
+        let user_name = component.split(['"', '\'']).next().unwrap_or_default().to_lowercase();
+        let replacement = if separators == 2 && unc_start {
+          Some(self.redact_name(Kind::Host, component))
+        } else if separators == 1 && after_host && !component.ends_with('$') {
+          Some(self.redact_name(Kind::Share, component))
+        } else if separators > 0
+          && (previous == "users" || previous == "documents and settings")
+          && !user_name.is_empty()
+          && !SHARED_PROFILES.contains(&user_name.as_str())
+        {
+          Some(self.redact_name(Kind::User, component))
+        } else {
+          None
+        };
+        after_host = separators == 2 && unc_start;
+        unc_start = component.ends_with([' ', '\t', '"', '\'']);
+        previous = component.to_lowercase();
+        separators = 0;
+        redacted.push_str(&replacement.unwrap_or_else(|| component.to_string()));
+      }
+      match after.chars().next() {
+        Some(separator) => {
+          redacted.push(separator);
+          separators += 1;
+          rest = &after[1..];
+        },
+        None => rest = after,
+      }
+    }
+    redacted
+  }
+
+  fn redact_field(&self, field: &str, value: &str) -> String {
+    match FIELDS.iter().find(|(name, _)| *name == field) {
+      Some((_, Kind::Mac)) => self.redact_droid(value),
+      Some((_, kind)) => self.pseudonym(*kind, value),
+      None => self.redact_path(value),
+    }
+  }
+
+  /// Redact the fields of a normalized record.
+  pub fn redact_normalized(&self, fields: &mut HashMap<String, String>) {
+    for (field, value) in fields.iter_mut() {
+      *value = match field.as_str() {
+        // A JSON array, the escaped backslashes would look like UNC paths.
+        "argv" => match serde_json::from_str::<Vec<String>>(value) {
+          Ok(argv) => {
+            let argv: Vec<String> = argv.iter().map(|arg| self.redact_path(arg)).collect();
+            serde_json::to_string(&argv).unwrap()
+          },
+          Err(_) => self.redact_path(value),
+        },
+        field => self.redact_field(field, value),
+      };
+    }
+  }
+
+  /// Redact a JSON record: the strings of the known fields are replaced by their token and every other string is
+  /// redacted like a path. In the shell items of an IDList every component of the path is a separate item, the
+  /// names (`name` and `primary_name`) of an item following a `Users` item are replaced like user names.
+  pub fn redact_json(&self, value: &mut Value) {
+    match value {
+      Value::Object(object) => {
+        for (field, value) in object.iter_mut() {
+          match value {
+            Value::String(s) => *s = self.redact_field(field, s),
+            value => self.redact_json(value),
+          }
+        }
+      },
+      Value::Array(array) => {
+        let mut after_users = false;
+        for value in array.iter_mut() {
+          let mut names = vec![];
+          item_names(value, &mut names);
+          let is_users = names
+            .iter()
+            .any(|name| ["users", "documents and settings"].contains(&name.to_lowercase().as_str()));
+          if after_users {
+            for name in names {
+              if !SHARED_PROFILES.contains(&name.to_lowercase().as_str()) {
+                *name = self.pseudonym(Kind::User, name);
+              }
+            }
+          }
+          after_users = is_users;
+          self.redact_json(value);
+        }
+      },
+      Value::String(s) => *s = self.redact_path(s),
+      _ => {},
+    }
+  }
+}
+
+/// The `name` and `primary_name` strings of a shell item.
+fn item_names<'a>(value: &'a mut Value, names: &mut Vec<&'a mut String>) {
+  if let Value::Object(object) = value {
+    for (field, value) in object.iter_mut() {
+      match (field.as_str(), value) {
+        ("name" | "primary_name", Value::String(s)) => names.push(s),
+        (_, value @ Value::Object(_)) => item_names(value, names),
+        _ => {},
+      }
+    }
+  }
+}
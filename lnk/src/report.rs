@@ -0,0 +1,253 @@
+//! Aggregate a collection of shortcuts by the machines, volumes and network shares their targets were on.
+//!
+//! Every shortcut records the NetBIOS name of the machine its target was on (TrackerDataBlock), the volume
+//! (VolumeID) or the share (CommonNetworkRelativeLink). Grouping a collection by them shows which USB devices and file
+//! shares were used, when and for which files.
+
+use crate::{extra_data::ExtraDataTypes, Lnk};
+use serde::{Serialize, Serializer};
+use std::{
+  collections::BTreeMap,
+  fmt::{self, Display, Formatter},
+};
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// A shortcut referencing a machine, volume or share.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Shortcut {
+  pub lnk_full_path: String,
+  pub target_full_path: String,
+}
+
+/// When and by which shortcuts a machine, volume or share was seen. The times of a shortcut are the creation and
+/// modification times of the LNK file, the first and last time the target was opened, or the access time of the
+/// target recorded in the header when the LNK file times are unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Sightings {
+  pub first_seen: Option<String>,
+  pub last_seen: Option<String>,
+  pub shortcuts: Vec<Shortcut>,
+}
+
+impl Sightings {
+  fn add(&mut self, shortcut: &Shortcut, times: &[String]) {
+    for time in times {
+      if self.first_seen.as_ref().is_none_or(|first| time < first) {
+        self.first_seen = Some(time.clone());
+      }
+      if self.last_seen.as_ref().is_none_or(|last| time > last) {
+        self.last_seen = Some(time.clone());
+      }
+    }
+    self.shortcuts.push(shortcut.clone());
+  }
+
+  fn fmt_text(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    writeln!(
+      f,
+      "    first seen: {}, last seen: {}, {} shortcut(s)",
+      self.first_seen.as_deref().unwrap_or("-"),
+      self.last_seen.as_deref().unwrap_or("-"),
+      self.shortcuts.len()
+    )?;
+    for shortcut in &self.shortcuts {
+      writeln!(f, "      {} -> {}", shortcut.lnk_full_path, shortcut.target_full_path)?;
+    }
+    Ok(())
+  }
+}
+
+/// A machine from the `machine_id` of a TrackerDataBlock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Machine {
+  pub machine_id: String,
+  #[serde(flatten)]
+  pub sightings: Sightings,
+}
+
+/// A volume from the VolumeID of a LinkInfo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Volume {
+  pub serial_number: String,
+  pub label: String,
+  pub drive_type: String,
+  #[serde(flatten)]
+  pub sightings: Sightings,
+}
+
+/// A network share from the CommonNetworkRelativeLink of a LinkInfo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Share {
+  pub net_name: String,
+  /// The drive letters the share was mapped to.
+  pub device_names: Vec<String>,
+  #[serde(flatten)]
+  pub sightings: Sightings,
+}
+
+fn values<K, V: Serialize, S: Serializer>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.collect_seq(map.values())
+}
+
+/// The machines, volumes and shares referenced by a collection of shortcuts. Volumes are told apart by serial
+/// number, label and drive type, shares by their name in any case.
+/// # Example
+/// ```
+/// use lnk::{report::Report, Lnk};
+///
+/// let mut report = Report::new();
+/// report.add(&Lnk::from_path("samples/WIN10/1607_14393/windows_generated.lnk").unwrap());
+/// report.add(&Lnk::from_path("samples/WIN10/1607_14393/network_share.lnk").unwrap());
+/// assert_eq!(report.shortcuts, 2);
+/// assert_eq!(report.volumes().next().unwrap().serial_number, "E02E-8A93");
+/// assert_eq!(report.shares().next().unwrap().net_name, "\\\\127.0.0.1\\TEST");
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+  /// Number of shortcuts added.
+  pub shortcuts: usize,
+  #[serde(serialize_with = "values")]
+  machines: BTreeMap<String, Machine>,
+  #[serde(serialize_with = "values")]
+  volumes: BTreeMap<(String, String, String), Volume>,
+  #[serde(serialize_with = "values")]
+  shares: BTreeMap<String, Share>,
+}
+
+impl Report {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Build the report of `lnks`.
+  pub fn from_lnks<'a, I: IntoIterator<Item = &'a Lnk>>(lnks: I) -> Self {
+    let mut report = Self::new();
+    for lnk in lnks {
+      report.add(lnk);
+    }
+    report
+  }
+
+  /// Add the machine, volume and share referenced by a shortcut.
+  pub fn add(&mut self, lnk: &Lnk) {
+    self.shortcuts += 1;
+    let shortcut = Shortcut {
+      lnk_full_path: lnk
+        .lnk_file_metadata
+        .as_ref()
+        .map(|metadata| metadata.full_path.clone())
+        .unwrap_or_default(),
+      target_full_path: lnk.target_full_path.clone().unwrap_or_default(),
+    };
+    let times: Vec<String> = match &lnk.lnk_file_metadata {
+      Some(metadata) => [metadata.ctime, metadata.mtime]
+        .into_iter()
+        .flatten()
+        .map(|time| time.format(TIME_FORMAT).to_string())
+        .collect(),
+      None => vec![lnk.shell_link_header.atime.to_string()],
+    }
+    .into_iter()
+    // FILETIME 0, the time was not set.
+    .filter(|time| !time.starts_with("1601-01-01"))
+    .collect();
+
+    let machine_ids = lnk
+      .extra_data
+      .iter()
+      .flat_map(|extra_data| &extra_data.extra_data_blocks)
+      .map(|block| match block {
+        ExtraDataTypes::Tracker(tracker) => &tracker.machine_id,
+      })
+      .filter(|machine_id| !machine_id.is_empty());
+    for machine_id in machine_ids {
+      self
+        .machines
+        .entry(machine_id.to_lowercase())
+        .or_insert_with(|| Machine {
+          machine_id: machine_id.clone(),
+          sightings: Sightings::default(),
+        })
+        .sightings
+        .add(&shortcut, &times);
+    }
+
+    let Some(link_info) = &lnk.link_info else {
+      return;
+    };
+    if let Some(volume_id) = &link_info.volume_id {
+      let label = volume_id.volume_lable.clone().unwrap_or_default();
+      let drive_type = format!("{:?}", volume_id.drive_type);
+      self
+        .volumes
+        .entry((volume_id.serial_number.clone(), label.clone(), drive_type.clone()))
+        .or_insert_with(|| Volume {
+          serial_number: volume_id.serial_number.clone(),
+          label,
+          drive_type,
+          sightings: Sightings::default(),
+        })
+        .sightings
+        .add(&shortcut, &times);
+    }
+    let Some(link) = &link_info.common_network_relative_link else {
+      return;
+    };
+    if let Some(net_name) = &link.net_name {
+      let share = self.shares.entry(net_name.to_lowercase()).or_insert_with(|| Share {
+        net_name: net_name.clone(),
+        device_names: vec![],
+        sightings: Sightings::default(),
+      });
+      if let Some(device_name) = link
+        .device_name
+        .as_ref()
+        .filter(|d| !d.is_empty() && !share.device_names.contains(d))
+      {
+        share.device_names.push(device_name.clone());
+      }
+      share.sightings.add(&shortcut, &times);
+    }
+  }
+
+  /// The machines, ordered by name.
+  pub fn machines(&self) -> impl Iterator<Item = &Machine> {
+    self.machines.values()
+  }
+
+  /// The volumes, ordered by serial number.
+  pub fn volumes(&self) -> impl Iterator<Item = &Volume> {
+    self.volumes.values()
+  }
+
+  /// The shares, ordered by name.
+  pub fn shares(&self) -> impl Iterator<Item = &Share> {
+    self.shares.values()
+  }
+}
+
+impl Display for Report {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    writeln!(f, "Shortcuts: {}", self.shortcuts)?;
+    writeln!(f, "\nMachines ({})", self.machines.len())?;
+    for machine in self.machines() {
+      writeln!(f, "  {}", machine.machine_id)?;
+      machine.sightings.fmt_text(f)?;
+    }
+    writeln!(f, "\nVolumes ({})", self.volumes.len())?;
+    for volume in self.volumes() {
+      writeln!(f, "  {} {:?} {}", volume.serial_number, volume.label, volume.drive_type)?;
+      volume.sightings.fmt_text(f)?;
+    }
+    writeln!(f, "\nShares ({})", self.shares.len())?;
+    for share in self.shares() {
+      match share.device_names.is_empty() {
+        true => writeln!(f, "  {}", share.net_name)?,
+        false => writeln!(f, "  {} ({})", share.net_name, share.device_names.join(", "))?,
+      }
+      share.sightings.fmt_text(f)?;
+    }
+    Ok(())
+  }
+}
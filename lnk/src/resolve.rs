@@ -0,0 +1,256 @@
+//! Resolve shortcut targets against a mounted Windows volume.
+//!
+//! A [`Resolver`] maps the target path of a shortcut (`C:\...`, `\\server\share\...`, an administrative share or a
+//! known folder) to a path under the directory the volume is mounted on, matching every component
+//! case-insensitively. [`Lnk::resolve`](crate::Lnk::resolve) then compares the file found there with what the
+//! shortcut recorded about its target: a target that is gone was deleted or moved, and a target whose times or MFT
+//! entry changed was replaced or timestomped.
+
+use crate::{command_line::Profile, triage};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  fs::Metadata,
+  path::{Path, PathBuf},
+  time::SystemTime,
+};
+use winparsingtools::date_time::FileTime;
+
+/// Known folders, by the GUID the shell uses for them, and their location on a default Windows installation.
+const KNOWN_FOLDERS: &[(&str, &str)] = &[
+  ("5E6C858F-0E22-4760-9AFE-EA3317B67173", "%USERPROFILE%"),
+  ("59031A47-3F72-44A7-89C5-5595FE6B30EE", "%USERPROFILE%"),
+  ("B4BFCC3A-DB2C-424C-B029-7FE99A87C641", "%USERPROFILE%\\Desktop"),
+  ("FDD39AD0-238F-46AF-ADB4-6C85480369C7", "%USERPROFILE%\\Documents"),
+  ("450D8FBA-AD25-11D0-98A8-0800361B1103", "%USERPROFILE%\\Documents"),
+  ("374DE290-123F-4565-9164-39C4925E467B", "%USERPROFILE%\\Downloads"),
+  ("4BD8D571-6D19-48D3-BE97-422220080E43", "%USERPROFILE%\\Music"),
+  ("33E28130-4E1E-4676-835A-98395C3BC3BB", "%USERPROFILE%\\Pictures"),
+  ("18989B1D-99B5-455B-841C-AB7C74E4DDFC", "%USERPROFILE%\\Videos"),
+  ("1777F761-68AD-4D8A-87BD-30B759FA33DD", "%USERPROFILE%\\Favorites"),
+  ("3EB685DB-65F9-4CF6-A03A-E3EF65729F3D", "%APPDATA%"),
+  ("F1B32785-6FBA-4FCF-9D55-7B8E7F157091", "%LOCALAPPDATA%"),
+  ("AE50C081-EBD2-438A-8655-8A092E34987A", "%APPDATA%\\Microsoft\\Windows\\Recent"),
+  ("A77F5D77-2E2B-44C3-A6A2-ABA601054A51", "%APPDATA%\\Microsoft\\Windows\\Start Menu\\Programs"),
+  ("905E63B6-C1BF-494E-B29C-65B732D3D21A", "%ProgramFiles%"),
+  ("7C5A40EF-A0FB-4BFC-874A-C0F2E0B9FA8E", "%ProgramFiles(x86)%"),
+  ("62AB5D82-FDC1-4DC3-A9DD-070D1D495D97", "%ProgramData%"),
+  ("DFDF76A2-C82A-4D63-906A-5644AC457385", "%PUBLIC%"),
+  ("F38BF404-1D43-42F2-9305-67DE0B28FC23", "%SystemRoot%"),
+  ("1AC14E77-02E7-4E5D-B744-2EB1AE5198B7", "%SystemRoot%\\System32"),
+];
+
+/// Names the IDList gives to root shell items (see `RootShellItemSortIndex`) and the shell folders that only contain
+/// other locations.
+const ROOT_FOLDERS: &[(&str, &str)] = &[
+  ("MY_COMPUTER", ""),
+  ("20D04FE0-3AEA-1069-A2D8-08002B30309D", ""),
+  ("USERS", "%USERPROFILE%"),
+  ("MY_DOCUMENTS", "%USERPROFILE%\\Documents"),
+];
+
+/// Maps Windows paths to the directories the volumes and shares they are on are mounted on.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+  /// Mount point by uppercase drive letter.
+  drives: HashMap<char, PathBuf>,
+  /// Mount point by lowercase `server\share`.
+  shares: HashMap<String, PathBuf>,
+  user: Option<String>,
+}
+
+impl Resolver {
+  /// Resolve the paths of the `C:` volume mounted on `root`.
+  pub fn new<P: AsRef<Path>>(root: P) -> Self {
+    Self {
+      drives: HashMap::from([('C', root.as_ref().to_path_buf())]),
+      shares: HashMap::new(),
+      user: None,
+    }
+  }
+
+  /// Resolve the paths of the volume `letter` to `root`, the administrative share (`\\host\D$`) of a mounted
+  /// volume resolves to the same directory.
+  pub fn drive<P: AsRef<Path>>(mut self, letter: char, root: P) -> Self {
+    self.drives.insert(letter.to_ascii_uppercase(), root.as_ref().to_path_buf());
+    self
+  }
+
+  /// Resolve the paths under the UNC share `share` (`\\server\share`) to `root`.
+  pub fn share<P: AsRef<Path>>(mut self, share: &str, root: P) -> Self {
+    let share = share.trim_start_matches('\\').trim_end_matches('\\').to_lowercase();
+    self.shares.insert(share, root.as_ref().to_path_buf());
+    self
+  }
+
+  /// The user whose profile the known folders are in, when the shortcut does not tell. Without a user the known
+  /// folders of every profile are searched.
+  pub fn user(mut self, user: &str) -> Self {
+    self.user = Some(user.to_string());
+    self
+  }
+
+  /// Map a Windows path to the mount point and the components under it, without touching the file system.
+  fn locate(&self, windows_path: &str, user: Option<&str>) -> Option<(PathBuf, Vec<String>)> {
+    let windows_path = windows_path.replace('/', "\\");
+    let windows_path = windows_path.strip_prefix("\\\\?\\").unwrap_or(&windows_path);
+    let windows_path = match windows_path.strip_prefix("UNC\\") {
+      Some(unc) => format!("\\\\{}", unc),
+      None => windows_path.to_string(),
+    };
+
+    if let Some(unc) = windows_path.strip_prefix("\\\\") {
+      let mut components = unc.split('\\').filter(|c| !c.is_empty());
+      let server = components.next()?;
+      let share = components.next()?;
+      let rest: Vec<String> = components.map(str::to_string).collect();
+      if let Some(root) = self.shares.get(&format!("{}\\{}", server, share).to_lowercase()) {
+        return Some((root.clone(), rest));
+      }
+      // `\\host\C$` is the root of the `C:` volume of `host`.
+      let mut letter = share.chars();
+      return match (letter.next(), letter.next(), letter.next()) {
+        (Some(letter), Some('$'), None) => Some((self.drives.get(&letter.to_ascii_uppercase())?.clone(), rest)),
+        _ => None,
+      };
+    }
+
+    let components: Vec<&str> = windows_path.split('\\').filter(|c| !c.is_empty()).collect();
+    let (first, rest) = components.split_first()?;
+    let mut letter = first.chars();
+    if let (Some(letter), Some(':'), None) = (letter.next(), letter.next(), letter.next()) {
+      let rest = rest.iter().map(|c| c.to_string()).collect();
+      return Some((self.drives.get(&letter.to_ascii_uppercase())?.clone(), rest));
+    }
+    let rest = rest.join("\\");
+
+    // A known folder (`::{GUID}`, `{GUID}` or the GUID the IDList path uses) or a root shell item.
+    let guid = first.trim_start_matches("::").trim_start_matches('{').trim_end_matches('}');
+    let folder = KNOWN_FOLDERS
+      .iter()
+      .chain(ROOT_FOLDERS)
+      .find(|(name, _)| name.eq_ignore_ascii_case(guid))
+      .map(|(_, folder)| *folder)?;
+    let profile = Profile::windows_defaults(Some(user.or(self.user.as_deref()).unwrap_or("*")));
+    let folder = profile.expand(folder);
+    match folder.is_empty() {
+      true => self.locate(&rest, user),
+      false => self.locate(&format!("{}\\{}", folder, rest), user),
+    }
+  }
+
+  /// Map a Windows path to a path under the mount points, matching every component case-insensitively. When
+  /// the file does not exist, the components that were not found are appended as they are. `None` when the path is
+  /// not on a mounted volume or share.
+  /// # Example
+  /// ```
+  /// use lnk::resolve::Resolver;
+  ///
+  /// let resolver = Resolver::new("samples");
+  /// let (path, exists) = resolver.map_path("C:\\win10\\1607_14393\\Missing.lnk", None).unwrap();
+  /// assert_eq!(path, std::path::Path::new("samples/WIN10/1607_14393/Missing.lnk"));
+  /// assert!(!exists);
+  /// ```
+  pub fn map_path(&self, windows_path: &str, user: Option<&str>) -> Option<(PathBuf, bool)> {
+    let (root, components) = self.locate(windows_path, user)?;
+    let components: Vec<&str> = components.iter().map(String::as_str).collect();
+    if let Some(path) = triage::resolve(&root, &components).into_iter().next() {
+      return Some((path, true));
+    }
+    let mut path = root;
+    for (index, component) in components.iter().enumerate() {
+      match triage::resolve(&path, &[component]).into_iter().next() {
+        Some(found) => path = found,
+        None => {
+          path.extend(&components[index..]);
+          break;
+        },
+      }
+    }
+    Some((path, false))
+  }
+}
+
+/// What was found at the target of a shortcut. The comparisons are `None` when they can not be made, e.g. the
+/// target does not exist, the shortcut has no time recorded or the file system does not report the value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Resolution {
+  /// Where the target is, or would be, under the mount point.
+  pub path: PathBuf,
+  pub exists: bool,
+  /// The size of the target is `ShellLinkHeader.file_size`, which only holds the low 32 bits of the size.
+  pub size_matches: Option<bool>,
+  /// The modification, access and creation times of the target are the ones in `ShellLinkHeader`, to the second.
+  pub mtime_matches: Option<bool>,
+  pub atime_matches: Option<bool>,
+  pub ctime_matches: Option<bool>,
+  /// The MFT entry of the target recorded in the IDList.
+  pub mft_entry: Option<u64>,
+  /// Whether the inode number of the target is `mft_entry`. The NTFS drivers of Linux (ntfs-3g and ntfs3) use the MFT
+  /// entry as inode number, so this only makes sense when the image is mounted with one of them. Only the entry is
+  /// compared: the file system does not expose the sequence number, so an MFT entry reused by another file also
+  /// matches.
+  pub mft_entry_matches: Option<bool>,
+}
+
+/// `true` when `time` is `header_time`. The header times are compared to the second, a time that was not set
+/// (1601-01-01) can not be compared.
+fn time_matches(header_time: &FileTime, time: std::io::Result<SystemTime>) -> Option<bool> {
+  let header_time = header_time.to_string();
+  if header_time.starts_with("1601-01-01") {
+    return None;
+  }
+  let time: DateTime<Utc> = time.ok()?.into();
+  Some(time.format("%Y-%m-%dT%H:%M:%SZ").to_string() == header_time)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> Option<u64> {
+  use std::os::unix::fs::MetadataExt;
+  Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_: &Metadata) -> Option<u64> {
+  None
+}
+
+impl Resolution {
+  pub(crate) fn new(path: PathBuf, exists: bool, lnk: &crate::Lnk) -> Self {
+    let header = &lnk.shell_link_header;
+    let mft_entry = lnk.link_target_id_list.as_ref().and_then(|id_list| id_list.mft_entry());
+    let metadata = match exists {
+      true => std::fs::metadata(&path).ok(),
+      false => None,
+    };
+    let Some(metadata) = metadata else {
+      return Self {
+        path,
+        exists,
+        size_matches: None,
+        mtime_matches: None,
+        atime_matches: None,
+        ctime_matches: None,
+        mft_entry,
+        mft_entry_matches: None,
+      };
+    };
+    Self {
+      size_matches: match metadata.is_file() {
+        true => Some(metadata.len() as u32 == header.file_size),
+        false => None,
+      },
+      mtime_matches: time_matches(&header.mtime, metadata.modified()),
+      atime_matches: time_matches(&header.atime, metadata.accessed()),
+      ctime_matches: time_matches(&header.ctime, metadata.created()),
+      mft_entry_matches: mft_entry.zip(inode(&metadata)).map(|(entry, inode)| entry == inode),
+      path,
+      exists,
+      mft_entry,
+    }
+  }
+}
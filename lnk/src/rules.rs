@@ -0,0 +1,489 @@
+//! User-defined detection rules evaluated against parsed shortcuts.
+//!
+//! A rule is a YAML document with an `id`, a `title`, a `severity` (`informational`, `low`, `medium`, `high` or
+//! `critical`), optional `tags` and `description`, and a list of `conditions` that must all match (`match: any` for
+//! any of them). A condition tests a normalized field (see [`NORMALIZED_FIELDS`], except `rule_matches`) or the ExtraData blocks:
+//!
+//! | Condition | Matches when |
+//! |---|---|
+//! | `field: F` and `equals: V` | the field is `V` |
+//! | `field: F` and `contains: V` | the field contains `V` |
+//! | `field: F` and `regex: R` | the field matches the regular expression `R` |
+//! | `field: F` and `min: N` and/or `max: N` | the field is a number between `min` and `max`, inclusive |
+//! | `extra_data: B` | the LNK file has an ExtraData block `B` (`TrackerDataBlock`, `Tracker` or `0xA0000003`) |
+//!
+//! `ignore_case: true` compares `equals`, `contains` and `regex` case-insensitively and `not: true` negates the
+//! condition. Rules can list LNK files they should and should not match under `tests`, relative to the rule file,
+//! [`RuleSet::run_tests`] checks them:
+//!
+//! ```yaml
+//! id: lnk-removable-script
+//! title: Shortcut to a script on removable media
+//! severity: high
+//! tags: [attack.t1204.002, usb]
+//! conditions:
+//!   - field: drive_type
+//!     equals: DRIVE_REMOVABLE
+//!   - field: target_full_path
+//!     regex: '\.(bat|cmd|ps1|vbs|js)$'
+//!     ignore_case: true
+//! tests:
+//!   should_match: [samples/usb_script.lnk]
+//!   should_not_match: [samples/usb_document.lnk]
+//! ```
+//!
+//! A file can hold several rules separated by `---`, [`RuleSet::load`] reads every `.yml` and `.yaml` file under a
+//! directory.
+
+use crate::{extra_data, triage, Lnk, LnkFileMetaData, NORMALIZED_FIELDS};
+use regex::{Regex, RegexBuilder};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::{self, Display, Formatter},
+  fs,
+  io::{Error, ErrorKind, Result},
+  path::{Path, PathBuf},
+};
+use winparsingtools::traits::Normalize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Informational,
+  Low,
+  Medium,
+  High,
+  Critical,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Severity::Informational => "informational",
+      Severity::Low => "low",
+      Severity::Medium => "medium",
+      Severity::High => "high",
+      Severity::Critical => "critical",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// Whether all the conditions of a rule must match or any of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+  #[default]
+  All,
+  Any,
+}
+
+/// A condition as written in a rule file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConditionSpec {
+  field: Option<String>,
+  equals: Option<serde_yaml::Value>,
+  contains: Option<String>,
+  regex: Option<String>,
+  min: Option<f64>,
+  max: Option<f64>,
+  extra_data: Option<serde_yaml::Value>,
+  #[serde(default)]
+  ignore_case: bool,
+  #[serde(default)]
+  not: bool,
+}
+
+/// A rule as written in a rule file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+  id: String,
+  title: String,
+  #[serde(default)]
+  description: Option<String>,
+  severity: Severity,
+  #[serde(default)]
+  tags: Vec<String>,
+  #[serde(default, rename = "match")]
+  mode: MatchMode,
+  conditions: Vec<ConditionSpec>,
+  #[serde(default)]
+  tests: RuleTests,
+}
+
+/// LNK files a rule is checked against by [`RuleSet::run_tests`], relative to the rule file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleTests {
+  #[serde(default)]
+  pub should_match: Vec<PathBuf>,
+  #[serde(default)]
+  pub should_not_match: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+enum Test {
+  Equals(String),
+  Contains(String),
+  Regex(Regex),
+  Range(Option<f64>, Option<f64>),
+  ExtraData(String),
+}
+
+#[derive(Debug)]
+struct Condition {
+  field: String,
+  test: Test,
+  ignore_case: bool,
+  not: bool,
+}
+
+impl Condition {
+  fn new(spec: ConditionSpec) -> std::result::Result<Self, String> {
+    let tests = [
+      spec.equals.is_some(),
+      spec.contains.is_some(),
+      spec.regex.is_some(),
+      spec.min.is_some() || spec.max.is_some(),
+      spec.extra_data.is_some(),
+    ];
+    if tests.iter().filter(|t| **t).count() != 1 {
+      return Err(String::from("a condition needs exactly one of equals, contains, regex, min/max or extra_data"));
+    }
+    let field = match (spec.field, &spec.extra_data) {
+      (Some(_), Some(_)) => return Err(String::from("an extra_data condition does not take a field")),
+      (None, Some(_)) => String::new(),
+      (None, None) => return Err(String::from("the condition has no field")),
+      (Some(field), None) if !rule_fields().any(|f| f == field) => {
+        return Err(format!(
+          "unknown field '{}', valid fields are: {}",
+          field,
+          rule_fields().collect::<Vec<&str>>().join(",")
+        ))
+      },
+      (Some(field), None) => field,
+    };
+    let test = if let Some(value) = spec.equals {
+      Test::Equals(match value {
+        serde_yaml::Value::String(s) => s,
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        _ => return Err(String::from("equals takes a string, a number or a boolean")),
+      })
+    } else if let Some(value) = spec.contains {
+      Test::Contains(value)
+    } else if let Some(regex) = spec.regex {
+      let regex = RegexBuilder::new(&regex)
+        .case_insensitive(spec.ignore_case)
+        .build()
+        .map_err(|e| format!("invalid regex: {}", e))?;
+      Test::Regex(regex)
+    } else if let Some(block) = spec.extra_data {
+      // YAML reads an unquoted signature (0xA0000003) as a number.
+      let block = match block {
+        serde_yaml::Value::String(s) => s,
+        serde_yaml::Value::Number(n) => format!("0x{:X}", n.as_u64().unwrap_or(u64::MAX)),
+        _ => return Err(String::from("extra_data takes a block name or signature")),
+      };
+      Test::ExtraData(block_name(&block).ok_or(format!("unknown ExtraData block '{}'", block))?)
+    } else {
+      Test::Range(spec.min, spec.max)
+    };
+    Ok(Self {
+      field,
+      test,
+      ignore_case: spec.ignore_case,
+      not: spec.not,
+    })
+  }
+
+  fn matches(&self, subject: &Subject) -> bool {
+    let value = subject.fields.get(&self.field).map(String::as_str).unwrap_or_default();
+    let lowercase = |s: &str| match self.ignore_case {
+      true => s.to_lowercase(),
+      false => s.to_string(),
+    };
+    let matches = match &self.test {
+      Test::Equals(expected) => lowercase(value) == lowercase(expected),
+      Test::Contains(expected) => lowercase(value).contains(&lowercase(expected)),
+      Test::Regex(regex) => regex.is_match(value),
+      Test::Range(min, max) => value
+        .trim()
+        .parse::<f64>()
+        .is_ok_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)),
+      Test::ExtraData(block) => subject.extra_data_blocks.contains(block),
+    };
+    matches != self.not
+  }
+}
+
+/// The normalized fields a condition can test, the rules are evaluated before `rule_matches` is set.
+fn rule_fields() -> impl Iterator<Item = &'static str> {
+  NORMALIZED_FIELDS.iter().copied().filter(|field| *field != "rule_matches")
+}
+
+/// The name of an ExtraData block given by its name, its name without `DataBlock` or its signature.
+fn block_name(block: &str) -> Option<String> {
+  let signature = block
+    .strip_prefix("0x")
+    .or(block.strip_prefix("0X"))
+    .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+  if let Some(signature) = signature {
+    return Some(match extra_data::BLOCK_NAMES.iter().find(|(s, _)| *s == signature) {
+      Some((_, name)) => name.to_string(),
+      None => format!("0x{:08X}", signature),
+    });
+  }
+  extra_data::BLOCK_NAMES
+    .iter()
+    .map(|(_, name)| *name)
+    .find(|name| name.eq_ignore_ascii_case(block) || name.eq_ignore_ascii_case(&format!("{}DataBlock", block)))
+    .map(str::to_string)
+}
+
+/// What the conditions are evaluated against: the normalized fields and the ExtraData blocks of a LNK file.
+#[derive(Debug, Clone)]
+pub struct Subject {
+  pub fields: HashMap<String, String>,
+  /// Names of the ExtraData blocks, see [`extra_data::block_names`].
+  pub extra_data_blocks: Vec<String>,
+}
+
+impl Subject {
+  /// The subject of a parsed LNK file, `data` are the bytes it was parsed from.
+  pub fn new(lnk: &Lnk, data: &[u8]) -> Self {
+    Self {
+      fields: lnk.normalize(),
+      extra_data_blocks: extra_data::block_names(data),
+    }
+  }
+}
+
+/// A detection rule.
+#[derive(Debug)]
+pub struct Rule {
+  pub id: String,
+  pub title: String,
+  pub description: Option<String>,
+  pub severity: Severity,
+  pub tags: Vec<String>,
+  pub mode: MatchMode,
+  conditions: Vec<Condition>,
+  pub tests: RuleTests,
+  /// The file the rule was read from.
+  pub path: PathBuf,
+}
+
+impl Rule {
+  fn new(spec: RuleSpec, path: &Path) -> std::result::Result<Self, String> {
+    if spec.id.trim().is_empty() {
+      return Err(String::from("the rule has no id"));
+    }
+    if spec.conditions.is_empty() {
+      return Err(String::from("the rule has no conditions"));
+    }
+    let conditions = spec
+      .conditions
+      .into_iter()
+      .enumerate()
+      .map(|(index, condition)| Condition::new(condition).map_err(|e| format!("condition {}: {}", index + 1, e)))
+      .collect::<std::result::Result<Vec<Condition>, String>>()?;
+    Ok(Self {
+      id: spec.id,
+      title: spec.title,
+      description: spec.description,
+      severity: spec.severity,
+      tags: spec.tags,
+      mode: spec.mode,
+      conditions,
+      tests: spec.tests,
+      path: path.to_path_buf(),
+    })
+  }
+
+  pub fn matches(&self, subject: &Subject) -> bool {
+    match self.mode {
+      MatchMode::All => self.conditions.iter().all(|condition| condition.matches(subject)),
+      MatchMode::Any => self.conditions.iter().any(|condition| condition.matches(subject)),
+    }
+  }
+}
+
+/// A rule a record matched, as written in the output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct RuleMatch {
+  pub id: String,
+  pub title: String,
+  pub severity: Severity,
+  pub tags: Vec<String>,
+}
+
+/// The outcome of checking a rule against one of its test files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+  pub rule_id: String,
+  pub path: PathBuf,
+  pub should_match: bool,
+  /// Whether the rule matched, or why the file could not be parsed.
+  pub outcome: std::result::Result<bool, String>,
+}
+
+impl TestResult {
+  pub fn passed(&self) -> bool {
+    self.outcome == Ok(self.should_match)
+  }
+}
+
+/// A collection of rules with unique ids.
+/// # Example
+/// ```
+/// use lnk::{rules::RuleSet, Lnk};
+///
+/// let rules = RuleSet::parse(
+///   r#"
+/// id: tracker-win10
+/// title: Target on the machine win10
+/// severity: low
+/// conditions:
+///   - field: target_hostname
+///     equals: WIN10
+///     ignore_case: true
+///   - extra_data: Tracker
+/// "#,
+/// )
+/// .unwrap();
+/// let path = "samples/WIN10/1607_14393/windows_generated.lnk";
+/// let matches = rules.evaluate(&Lnk::from_path(path).unwrap(), &std::fs::read(path).unwrap());
+/// assert_eq!(matches[0].id, "tracker-win10");
+/// ```
+#[derive(Debug, Default)]
+pub struct RuleSet {
+  rules: Vec<Rule>,
+}
+
+impl RuleSet {
+  /// Parse the rules of a YAML string, the documents are separated by `---`.
+  pub fn parse(yaml: &str) -> Result<Self> {
+    let mut rule_set = Self::default();
+    rule_set.add_yaml(yaml, Path::new(""))?;
+    Ok(rule_set)
+  }
+
+  /// Read the rules of every `.yml` and `.yaml` file under `path`, or of the file `path`.
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let mut files = vec![];
+    match path.is_dir() {
+      true => rule_files(path, &mut files)?,
+      false => files.push(path.to_path_buf()),
+    }
+    files.sort();
+    let mut rule_set = Self::default();
+    for file in files {
+      rule_set.add_yaml(&fs::read_to_string(&file)?, &file)?;
+    }
+    Ok(rule_set)
+  }
+
+  fn add_yaml(&mut self, yaml: &str, path: &Path) -> Result<()> {
+    let invalid = |message: String| match path.as_os_str().is_empty() {
+      true => Error::new(ErrorKind::InvalidData, message),
+      false => Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), message)),
+    };
+    let mut ids: HashSet<String> = self.rules.iter().map(|rule| rule.id.clone()).collect();
+    for (index, document) in serde_yaml::Deserializer::from_str(yaml).enumerate() {
+      let value = serde_yaml::Value::deserialize(document).map_err(|e| invalid(e.to_string()))?;
+      if value.is_null() {
+        continue;
+      }
+      let spec: RuleSpec = serde_yaml::from_value(value).map_err(|e| invalid(format!("rule {}: {}", index + 1, e)))?;
+      let id = spec.id.clone();
+      let rule = Rule::new(spec, path).map_err(|e| invalid(format!("rule '{}': {}", id, e)))?;
+      if !ids.insert(rule.id.clone()) {
+        return Err(invalid(format!("duplicate rule id '{}'", rule.id)));
+      }
+      self.rules.push(rule);
+    }
+    Ok(())
+  }
+
+  pub fn rules(&self) -> &[Rule] {
+    &self.rules
+  }
+
+  /// The rules matched by a LNK file, `data` are the bytes it was parsed from.
+  pub fn evaluate(&self, lnk: &Lnk, data: &[u8]) -> Vec<RuleMatch> {
+    let subject = Subject::new(lnk, data);
+    self
+      .rules
+      .iter()
+      .filter(|rule| rule.matches(&subject))
+      .map(|rule| RuleMatch {
+        id: rule.id.clone(),
+        title: rule.title.clone(),
+        severity: rule.severity,
+        tags: rule.tags.clone(),
+      })
+      .collect()
+  }
+
+  /// Check every rule against the LNK files listed in its `tests`.
+  pub fn run_tests(&self) -> Vec<TestResult> {
+    let mut results = vec![];
+    for rule in &self.rules {
+      let dir = rule.path.parent().unwrap_or(Path::new(""));
+      let cases = rule
+        .tests
+        .should_match
+        .iter()
+        .map(|path| (path, true))
+        .chain(rule.tests.should_not_match.iter().map(|path| (path, false)));
+      for (path, should_match) in cases {
+        let path = dir.join(path);
+        let outcome = test_subject(&path)
+          .map(|(lnk, data)| rule.matches(&Subject::new(&lnk, &data)))
+          .map_err(|e| e.to_string());
+        results.push(TestResult {
+          rule_id: rule.id.clone(),
+          path,
+          should_match,
+          outcome,
+        });
+      }
+    }
+    results
+  }
+}
+
+/// Parse a test file the way `lnk_parser` parses its inputs, with the file metadata and the user taken from its path,
+/// so rules on `lnk_full_path`, `user` or the file times can be tested.
+fn test_subject(path: &Path) -> Result<(Lnk, Vec<u8>)> {
+  let data = fs::read(path)?;
+  let full_path = path.to_string_lossy();
+  let mut lnk = Lnk::from_buffer(&data)?;
+  let mut lnk_file_metadata = LnkFileMetaData::from_path(&full_path)?;
+  lnk_file_metadata.user = triage::user_from_path(&full_path);
+  lnk.lnk_file_metadata = Some(lnk_file_metadata);
+  Ok((lnk, data))
+}
+
+/// The rule files under `dir`, recursively. Symbolic links to directories are not followed, they can loop.
+fn rule_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let path = entry.path();
+    if entry.file_type()?.is_dir() {
+      rule_files(&path, files)?;
+    } else if path.extension().is_some_and(|e| e == "yml" || e == "yaml") {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,191 @@
+//! JSON Schema of the records written by `lnk_parser`.
+//!
+//! The schemas are generated from the Rust types, so they follow the serialization of [`Lnk`] and of the normalized
+//! records. Every record carries the [`SCHEMA_VERSION`] it conforms to. The minor version is bumped when fields are
+//! added and the major version when fields are removed, renamed or change type, consumers should check the major
+//! version.
+//!
+//! The shell items of an IDList are decoded by `winparsingtools`, their schema only lists the item types.
+
+use crate::{Lnk, DEFAULT_NORMALIZED_FIELDS, NORMALIZED_FIELDS};
+use schemars::{generate::SchemaSettings, json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{borrow::Cow, collections::HashMap};
+
+/// Version of the schema of the records.
+pub const SCHEMA_VERSION: &str = "2.0";
+
+/// A record tagged with the version of its schema, serialized as the record with a leading `schema_version` field.
+/// # Example
+/// ```
+/// use lnk::{schema::Versioned, Lnk};
+///
+/// let lnk = Lnk::from_path("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+/// let json = serde_json::to_value(Versioned::new(&lnk)).unwrap();
+/// assert_eq!(json["schema_version"], lnk::schema::SCHEMA_VERSION);
+/// assert_eq!(json["link_info"]["volume_id"]["serial_number"], "E02E-8A93");
+/// ```
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Versioned<T> {
+  #[schemars(schema_with = "schema_version")]
+  pub schema_version: &'static str,
+  #[serde(flatten)]
+  pub record: T,
+}
+
+impl<T> Versioned<T> {
+  pub fn new(record: T) -> Self {
+    Self {
+      schema_version: SCHEMA_VERSION,
+      record,
+    }
+  }
+}
+
+/// A normalized record, the fields of [`DEFAULT_NORMALIZED_FIELDS`] and optionally any other field of
+/// [`NORMALIZED_FIELDS`] as strings.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct Normalized(pub HashMap<String, String>);
+
+impl JsonSchema for Normalized {
+  fn schema_name() -> Cow<'static, str> {
+    Cow::Borrowed("Normalized")
+  }
+
+  fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    let properties: Map<String, Value> = NORMALIZED_FIELDS
+      .iter()
+      .map(|field| (field.to_string(), serde_json::json!({ "type": "string" })))
+      .collect();
+    json_schema!({
+      "description": "A LNK file flattened to strings, empty when the value is unknown.",
+      "type": "object",
+      "properties": properties,
+      "required": DEFAULT_NORMALIZED_FIELDS,
+      "additionalProperties": false,
+    })
+  }
+}
+
+fn schema_version(_: &mut SchemaGenerator) -> Schema {
+  json_schema!({
+    "description": "Version of the schema the record conforms to.",
+    "type": "string",
+    "const": SCHEMA_VERSION,
+  })
+}
+
+/// A time as written by the parser, UTC to the second (`2021-02-08T12:41:58Z`).
+pub(crate) fn date_time(_: &mut SchemaGenerator) -> Schema {
+  json_schema!({
+    "type": "string",
+    "format": "date-time",
+  })
+}
+
+/// A date and time, `null` when it is not known.
+pub(crate) fn optional_date_time(_: &mut SchemaGenerator) -> Schema {
+  json_schema!({
+    "type": ["string", "null"],
+    "format": "date-time",
+  })
+}
+
+/// A GUID, uppercase without braces (`BD4FAD74-6A0A-11EB-8ECF-5076AFA95947`).
+pub(crate) fn guid(_: &mut SchemaGenerator) -> Schema {
+  json_schema!({
+    "type": "string",
+    "pattern": "^[0-9A-F]{8}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{12}$",
+  })
+}
+
+/// File attributes, see `winparsingtools::file_system::FileAttributesFlags`.
+pub(crate) fn file_attributes(_: &mut SchemaGenerator) -> Schema {
+  json_schema!({
+    "type": "array",
+    "items": {
+      "type": "string",
+      "enum": [
+        "READONLY", "HIDDEN", "SYSTEM", "VOLUME_LABEL", "DIRECTORY", "ARCHIVE", "DEVICE", "NORMAL", "TEMPORARY",
+        "SPARSE_FILE", "REPARSE_POINT", "COMPRESSED", "OFFLINE", "NOT_CONTENT_INDEXED", "ENCRYPTED",
+        "INTEGRITY_STREAM", "VIRTUAL", "NO_SCRUB_DATA",
+      ],
+    },
+  })
+}
+
+/// The shell items of an IDList. Every item holds its decoded data under the name of its type, or `null` when the
+/// item could not be decoded.
+pub(crate) fn shell_items(_: &mut SchemaGenerator) -> Schema {
+  let item_types: Vec<Value> = [
+    "FileEntry",
+    "Volume",
+    "Root",
+    "NetworkLocation",
+    "UsersFilesFolder",
+    "URI",
+    "ControlPanelCategory",
+    "ControlPanelItem",
+  ]
+  .iter()
+  .map(|item_type| {
+    serde_json::json!({
+      "type": "object",
+      "properties": { *item_type: { "type": "object" } },
+      "required": [item_type],
+      "additionalProperties": false,
+    })
+  })
+  .chain([serde_json::json!({
+    "description": "The bytes of an item of an unknown type, in hex.",
+    "type": "object",
+    "properties": { "Unimpleminted": { "type": "string" } },
+    "required": ["Unimpleminted"],
+    "additionalProperties": false,
+  })])
+  .collect();
+  json_schema!({
+    "type": "array",
+    "items": {
+      "type": "object",
+      "properties": {
+        "shell_item_data": {
+          "anyOf": [{ "oneOf": item_types }, { "type": "null" }],
+        },
+      },
+      "required": ["shell_item_data"],
+    },
+  })
+}
+
+/// The root schema of the records of type `T`.
+fn root_schema<T: JsonSchema>(title: &str, description: &str) -> Schema {
+  let mut schema = SchemaSettings::draft2020_12()
+    .for_serialize()
+    .into_generator()
+    .into_root_schema_for::<Versioned<T>>();
+  schema.insert(String::from("title"), Value::from(title));
+  schema.insert(String::from("description"), Value::from(description));
+  schema
+}
+
+/// The schema of the JSON records, a [`Lnk`] with its schema version.
+/// # Example
+/// ```
+/// let schema = serde_json::to_value(lnk::schema::lnk_schema()).unwrap();
+/// assert_eq!(schema["properties"]["schema_version"]["const"], lnk::schema::SCHEMA_VERSION);
+/// assert!(schema["required"].as_array().unwrap().contains(&"shell_link_header".into()));
+/// ```
+pub fn lnk_schema() -> Schema {
+  root_schema::<Lnk>("lnk", "A LNK file parsed by lnk_parser, the records of the json and jsonl output formats.")
+}
+
+/// The schema of the normalized JSON records (`--normalize`).
+pub fn normalized_schema() -> Schema {
+  root_schema::<Normalized>(
+    "lnk normalized",
+    "A LNK file parsed by lnk_parser, the records of the json and jsonl output formats with --normalize.",
+  )
+}
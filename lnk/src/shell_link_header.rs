@@ -1,6 +1,8 @@
 //! [ShellLinkHeader](https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/c3376b21-0931-45e4-b2fc-a48ac0e60d15) related structs
 
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+#[cfg(feature = "schema")]
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::ser;
 use serde::Serialize;
 use std::fmt::{self, Display};
@@ -180,6 +182,7 @@ impl Serialize for LinkFlags {
 /* #region  ShellLinkHeader Struct Implementation */
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum ShowCommandOptions {
   SHOWNORMAL,
   SHOWMAXIMIZED,
@@ -267,21 +270,42 @@ impl Serialize for LinkHotKey {
   }
 }
 
+#[cfg(feature = "schema")]
+impl JsonSchema for LinkHotKey {
+  fn schema_name() -> std::borrow::Cow<'static, str> {
+    std::borrow::Cow::Borrowed("LinkHotKey")
+  }
+
+  fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+      "description": "The keys of the hot key joined with ` + ` (`CTRL + ALT + F1`), `null` when there is none.",
+      "type": ["string", "null"],
+    })
+  }
+}
+
 /// The ShellLinkHeader structure contains identification information, timestamps, and flags that specify the presence of optional structures
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ShellLinkHeader {
   #[serde(skip_serializing)]
   pub header_size: u32,
 
   #[serde(skip_serializing)]
+  #[cfg_attr(feature = "schema", schemars(skip))]
   pub guid: Guid,
 
   #[serde(skip_serializing)]
+  #[cfg_attr(feature = "schema", schemars(skip))]
   pub flags: LinkFlags,
 
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::file_attributes"))]
   pub file_attr: Vec<FileAttributesFlags>,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::date_time"))]
   pub mtime: FileTime,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::date_time"))]
   pub atime: FileTime,
+  #[cfg_attr(feature = "schema", schemars(schema_with = "crate::schema::date_time"))]
   pub ctime: FileTime,
   pub file_size: u32,
 
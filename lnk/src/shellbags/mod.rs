@@ -0,0 +1,241 @@
+//! [ShellBags](https://github.com/libyal/winreg-kb/blob/main/documentation/Shell%20bags.asciidoc) of offline
+//! `NTUSER.DAT` and `UsrClass.dat` hives, the folders a user browsed with Explorer.
+//!
+//! Every `BagMRU` key has a value per child folder (`0`, `1`, ...) holding the shell item of the folder, the same
+//! structure as the items of a LNK IDList, and a subkey with the same name for the children of that folder.
+//! `MRUListEx` orders the values from the most recently used.
+
+pub mod regf;
+
+use crate::triage;
+use regf::{Hive, Key};
+use serde::Serialize;
+use std::{
+  collections::{HashMap, HashSet},
+  io::Result,
+};
+use winparsingtools::{
+  date_time::FileTime,
+  structs::shell_items::{Name, ShellItem, ShellItemTypes},
+  traits::Normalize,
+};
+
+/// The `BagMRU` keys, relative to the root of `NTUSER.DAT` (the first two) or `UsrClass.dat` (Windows 7 and later).
+pub const BAG_MRU_KEYS: &[&str] = &[
+  "Software\\Microsoft\\Windows\\Shell\\BagMRU",
+  "Software\\Microsoft\\Windows\\ShellNoRoam\\BagMRU",
+  "Local Settings\\Software\\Microsoft\\Windows\\Shell\\BagMRU",
+  "Wow6432Node\\Local Settings\\Software\\Microsoft\\Windows\\Shell\\BagMRU",
+];
+
+/// Folders nested deeper than this are not walked, a corrupted hive can loop.
+const MAX_DEPTH: usize = 256;
+
+/// A folder found in a `BagMRU` key.
+#[derive(Debug, Serialize)]
+pub struct ShellBag {
+  /// The path of the hive file, when it was read from a path.
+  pub hive_path: Option<String>,
+  /// The profile the hive belongs to, from the hive path.
+  pub user: Option<String>,
+  /// The key of the folder, its children are stored under it (`...\BagMRU\0\3` is value `3` of `...\BagMRU\0`).
+  pub key_path: String,
+  /// The names of the shell items from the root of the `BagMRU` key to the folder.
+  pub path: String,
+  /// `FileEntry`, `Volume`, `Root`, ... or `Unparsed` when the shell item could not be parsed.
+  pub item_type: String,
+  /// The position of the folder in the `MRUListEx` of its parent, 0 is the most recently used.
+  pub mru_position: Option<usize>,
+  /// The `Bags` key holding the view settings of the folder.
+  pub node_slot: Option<u32>,
+  /// The last write time of the key of the folder, its children or their order last changed.
+  pub key_last_written: Option<FileTime>,
+  /// The last write time of the parent key, when the folder is the most recently used child.
+  pub last_interacted: Option<FileTime>,
+  pub shell_item: Option<ShellItem>,
+}
+
+/// The name of a shell item in a path, network locations have no name in `winparsingtools`.
+fn item_name(item: &ShellItem) -> String {
+  match &item.shell_item_data {
+    Some(ShellItemTypes::NetworkLocation(location)) => location.location.to_owned(),
+    _ => item.name(),
+  }
+}
+
+fn item_type(item: &ShellItem) -> &'static str {
+  match &item.shell_item_data {
+    Some(ShellItemTypes::FileEntry(_)) => "FileEntry",
+    Some(ShellItemTypes::Volume(_)) => "Volume",
+    Some(ShellItemTypes::Root(_)) => "Root",
+    Some(ShellItemTypes::NetworkLocation(_)) => "NetworkLocation",
+    Some(ShellItemTypes::UsersFilesFolder(_)) => "UsersFilesFolder",
+    Some(ShellItemTypes::URI(_)) => "URI",
+    Some(ShellItemTypes::ControlPanelCategory(_)) => "ControlPanelCategory",
+    Some(ShellItemTypes::ControlPanelItem(_)) => "ControlPanelItem",
+    Some(ShellItemTypes::Unimpleminted(_)) | None => "Unimplemented",
+  }
+}
+
+/// The shell item stored in a `BagMRU` value, followed by an empty item.
+fn parse_item(data: &[u8]) -> Option<ShellItem> {
+  let size = u16::from_le_bytes(data.get(..2)?.try_into().ok()?) as usize;
+  if size < 3 || size > data.len() {
+    return None;
+  }
+  ShellItem::from_buffer(&data[..size]).ok()
+}
+
+/// The ShellBags of a hive.
+#[derive(Debug)]
+pub struct ShellBags {
+  /// The folders in the order they are stored, every folder is followed by its children.
+  pub entries: Vec<ShellBag>,
+  /// The hive has changes that are only in its transaction logs, see [`Hive::is_dirty`].
+  pub dirty: bool,
+}
+
+impl ShellBags {
+  /// Read the ShellBags of the hive at `path`, the profile is taken from the path.
+  pub fn from_path(path: &str) -> Result<Self> {
+    Ok(Self::from_hive(&Hive::from_path(path)?)?.located_at(path))
+  }
+
+  /// Set the hive path and the profile of every entry to those of the path the hive was read from.
+  pub fn located_at(mut self, path: &str) -> Self {
+    let user = triage::user_from_path(path);
+    for entry in self.entries.iter_mut() {
+      entry.hive_path = Some(path.to_string());
+      entry.user = user.clone();
+    }
+    self
+  }
+
+  /// Walk every `BagMRU` key of the hive, a hive without them has no entries.
+  pub fn from_hive(hive: &Hive) -> Result<Self> {
+    let root = hive.root()?;
+    let mut entries = vec![];
+    let mut visited = HashSet::new();
+    for bag_mru in BAG_MRU_KEYS {
+      if let Some(key) = root.open(bag_mru)?.filter(|key| visited.insert(key.offset())) {
+        walk(&key, bag_mru, "", 0, &mut visited, &mut entries)?;
+      }
+    }
+    Ok(Self {
+      entries,
+      dirty: hive.is_dirty(),
+    })
+  }
+}
+
+/// Add the folders stored in `key` and, depth first, their children. A corrupted or crafted hive can list a key as
+/// its own subkey or under several parents, the keys in `visited` are not walked again.
+fn walk(
+  key: &Key,
+  key_path: &str,
+  parent_path: &str,
+  depth: usize,
+  visited: &mut HashSet<u32>,
+  entries: &mut Vec<ShellBag>,
+) -> Result<()> {
+  let values = key.values()?;
+  let subkeys = key.subkeys()?;
+  let mru_list: Vec<u32> = match values.iter().find(|value| value.name.eq_ignore_ascii_case("MRUListEx")) {
+    Some(mru_list) => mru_list
+      .data
+      .chunks_exact(4)
+      .map(|slot| u32::from_le_bytes([slot[0], slot[1], slot[2], slot[3]]))
+      .take_while(|slot| *slot != u32::MAX)
+      .collect(),
+    None => vec![],
+  };
+  let mut slots: Vec<(u32, &[u8])> = values
+    .iter()
+    .filter_map(|value| Some((value.name.parse().ok()?, value.data.as_slice())))
+    .collect();
+  slots.sort_by_key(|(slot, _)| *slot);
+
+  for (slot, data) in slots {
+    let shell_item = parse_item(data);
+    let name = shell_item.as_ref().map(item_name).unwrap_or_else(|| String::from("{UNPARSED}"));
+    let mut path = parent_path.to_string();
+    if !path.is_empty() && !path.ends_with('\\') && !name.is_empty() {
+      path.push('\\');
+    }
+    path.push_str(&name);
+    let item_key = subkeys.iter().find(|subkey| subkey.name == slot.to_string());
+    let node_slot = match item_key {
+      Some(item_key) => item_key.value("NodeSlot")?.and_then(|value| value.dword()),
+      None => None,
+    };
+    let mru_position = mru_list.iter().position(|mru_slot| *mru_slot == slot);
+    let item_key_path = format!("{}\\{}", key_path, slot);
+    entries.push(ShellBag {
+      hive_path: None,
+      user: None,
+      key_path: item_key_path.clone(),
+      path: path.clone(),
+      item_type: shell_item.as_ref().map(item_type).unwrap_or("Unparsed").to_string(),
+      mru_position,
+      node_slot,
+      key_last_written: item_key.map(Key::last_written),
+      last_interacted: (mru_position == Some(0)).then(|| key.last_written()),
+      shell_item,
+    });
+    if let Some(item_key) = item_key.filter(|key| depth < MAX_DEPTH && visited.insert(key.offset())) {
+      walk(item_key, &item_key_path, &path, depth + 1, visited, entries)?;
+    }
+  }
+  Ok(())
+}
+
+/// The fields returned by `ShellBag::normalize`, in the order they are written to tabular outputs.
+pub const SHELLBAG_NORMALIZED_FIELDS: &[&str] = &[
+  "hive_path",
+  "user",
+  "key_path",
+  "path",
+  "item_type",
+  "mru_position",
+  "node_slot",
+  "key_last_written",
+  "last_interacted",
+  "item_modification_time",
+  "item_creation_time",
+  "item_access_time",
+];
+
+impl Normalize for ShellBag {
+  fn normalize(&self) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    fields.insert("hive_path".to_string(), self.hive_path.clone().unwrap_or_default());
+    fields.insert("user".to_string(), self.user.clone().unwrap_or_default());
+    fields.insert("key_path".to_string(), self.key_path.to_owned());
+    fields.insert("path".to_string(), self.path.to_owned());
+    fields.insert("item_type".to_string(), self.item_type.to_owned());
+    fields.insert("mru_position".to_string(), self.mru_position.map(|p| p.to_string()).unwrap_or_default());
+    fields.insert("node_slot".to_string(), self.node_slot.map(|s| s.to_string()).unwrap_or_default());
+    fields.insert(
+      "key_last_written".to_string(),
+      self.key_last_written.as_ref().map(FileTime::to_string).unwrap_or_default(),
+    );
+    fields.insert(
+      "last_interacted".to_string(),
+      self.last_interacted.as_ref().map(FileTime::to_string).unwrap_or_default(),
+    );
+    let (mut mtime, mut ctime, mut atime) = (String::new(), String::new(), String::new());
+    if let Some(ShellItemTypes::FileEntry(file_entry)) =
+      self.shell_item.as_ref().and_then(|i| i.shell_item_data.as_ref())
+    {
+      mtime = file_entry.mtime.to_string();
+      if let Some(extension_block) = &file_entry.extention_block {
+        ctime = extension_block.ctime.to_string();
+        atime = extension_block.atime.to_string();
+      }
+    }
+    fields.insert("item_modification_time".to_string(), mtime);
+    fields.insert("item_creation_time".to_string(), ctime);
+    fields.insert("item_access_time".to_string(), atime);
+    fields
+  }
+}
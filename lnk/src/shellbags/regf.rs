@@ -0,0 +1,290 @@
+//! Read-only [registry hive](https://github.com/libyal/libregf/blob/main/documentation/Windows%20NT%20Registry%20File%20(REGF)%20format.asciidoc)
+//! (`regf`) reader, enough to walk the keys and read the values of an offline `NTUSER.DAT` or `UsrClass.dat`.
+//!
+//! The transaction logs (`*.LOG1`, `*.LOG2`) are not replayed, [`Hive::is_dirty`] tells when the hive has changes
+//! that were only written to them.
+
+use std::{
+  fs,
+  io::{Error, ErrorKind, Result},
+};
+use winparsingtools::date_time::FileTime;
+
+const BASE_BLOCK_SIZE: usize = 4096;
+/// Value data larger than this is split in `db` segments (hive version 1.4 and later).
+const MAX_VALUE_DATA_SIZE: usize = 16344;
+/// The key name is stored in an 8-bit encoding instead of UTF-16.
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+
+fn invalid(message: String) -> Error {
+  Error::new(ErrorKind::InvalidData, message)
+}
+
+/// A registry hive file read in memory.
+#[derive(Debug)]
+pub struct Hive {
+  data: Vec<u8>,
+  minor_version: u32,
+}
+
+impl Hive {
+  /// Read a hive from path.
+  pub fn from_path(path: &str) -> Result<Self> {
+    Self::from_buffer(fs::read(path)?)
+  }
+
+  /// Read a hive from its bytes, the base block and the first hive bin are checked.
+  pub fn from_buffer(data: Vec<u8>) -> Result<Self> {
+    if !data.starts_with(b"regf") {
+      return Err(invalid("not a registry hive, the regf signature is missing".to_string()));
+    }
+    if data.get(BASE_BLOCK_SIZE..BASE_BLOCK_SIZE + 4) != Some(b"hbin") {
+      return Err(invalid("the first hive bin is missing".to_string()));
+    }
+    let hive = Self { data, minor_version: 0 };
+    Ok(Self {
+      minor_version: hive.u32(24)?,
+      ..hive
+    })
+  }
+
+  /// The primary and secondary sequence numbers differ when the last write was not completed, the changes are in
+  /// the transaction logs.
+  pub fn is_dirty(&self) -> bool {
+    self.u32(4).ok() != self.u32(8).ok()
+  }
+
+  /// The root key of the hive.
+  pub fn root(&self) -> Result<Key<'_>> {
+    Key::new(self, self.u32(36)?)
+  }
+
+  fn bytes(&self, offset: usize, size: usize) -> Result<&[u8]> {
+    offset
+      .checked_add(size)
+      .and_then(|end| self.data.get(offset..end))
+      .ok_or_else(|| invalid(format!("{} bytes at offset {} are out of bounds", size, offset)))
+  }
+
+  fn u32(&self, offset: usize) -> Result<u32> {
+    let bytes = self.bytes(offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  /// The data of the cell at `offset` (relative to the first hive bin).
+  fn cell(&self, offset: u32) -> Result<&[u8]> {
+    let start = BASE_BLOCK_SIZE + offset as usize;
+    let size = (self.u32(start)? as i32).unsigned_abs() as usize;
+    if size < 4 {
+      return Err(invalid(format!("invalid cell size {} at offset {}", size, offset)));
+    }
+    self.bytes(start + 4, size - 4)
+  }
+
+  /// The cell at `offset`, which must start with `signature`.
+  fn signed_cell(&self, offset: u32, signature: &[u8; 2]) -> Result<&[u8]> {
+    let cell = self.cell(offset)?;
+    match cell.starts_with(signature) {
+      true => Ok(cell),
+      false => Err(invalid(format!("expected a {} cell at offset {}", String::from_utf8_lossy(signature), offset))),
+    }
+  }
+}
+
+fn le_u16(buf: &[u8], offset: usize) -> Result<u16> {
+  match buf.get(offset..offset + 2) {
+    Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+    None => Err(invalid(format!("cell too small to read offset {}", offset))),
+  }
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> Result<u32> {
+  match buf.get(offset..offset + 4) {
+    Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+    None => Err(invalid(format!("cell too small to read offset {}", offset))),
+  }
+}
+
+/// Key and value names are either Latin-1 or UTF-16.
+fn name(bytes: &[u8], compressed: bool) -> String {
+  match compressed {
+    true => bytes.iter().map(|b| *b as char).collect(),
+    false => String::from_utf16_lossy(
+      &bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>(),
+    ),
+  }
+}
+
+/// A key (`nk` cell) of a hive.
+#[derive(Debug, Clone)]
+pub struct Key<'a> {
+  hive: &'a Hive,
+  offset: u32,
+  pub name: String,
+  last_written: u64,
+  subkeys_count: u32,
+  subkeys_offset: u32,
+  values_count: u32,
+  values_offset: u32,
+}
+
+impl<'a> Key<'a> {
+  fn new(hive: &'a Hive, offset: u32) -> Result<Self> {
+    let cell = hive.signed_cell(offset, b"nk")?;
+    let flags = le_u16(cell, 2)?;
+    let name_length = le_u16(cell, 72)? as usize;
+    let name_bytes = cell
+      .get(76..76 + name_length)
+      .ok_or_else(|| invalid(format!("key name out of the cell at offset {}", offset)))?;
+    Ok(Self {
+      hive,
+      offset,
+      name: name(name_bytes, flags & KEY_COMP_NAME != 0),
+      last_written: le_u32(cell, 4)? as u64 | (le_u32(cell, 8)? as u64) << 32,
+      subkeys_count: le_u32(cell, 20)?,
+      subkeys_offset: le_u32(cell, 28)?,
+      values_count: le_u32(cell, 36)?,
+      values_offset: le_u32(cell, 40)?,
+    })
+  }
+
+  /// The offset of the `nk` cell, it identifies the key in the hive.
+  pub fn offset(&self) -> u32 {
+    self.offset
+  }
+
+  /// The last time the key or one of its values was written.
+  pub fn last_written(&self) -> FileTime {
+    FileTime::new(self.last_written)
+  }
+
+  /// The subkeys, in the order they are stored (sorted by name by Windows).
+  pub fn subkeys(&self) -> Result<Vec<Key<'a>>> {
+    let mut offsets = vec![];
+    if self.subkeys_count > 0 {
+      self.list_offsets(self.subkeys_offset, true, &mut offsets)?;
+    }
+    offsets.into_iter().map(|offset| Key::new(self.hive, offset)).collect()
+  }
+
+  /// Collect the key offsets of a `lf`, `lh`, `li` or `ri` list, `ri` lists hold other lists.
+  fn list_offsets(&self, offset: u32, indirect: bool, offsets: &mut Vec<u32>) -> Result<()> {
+    let cell = self.hive.cell(offset)?;
+    let count = le_u16(cell, 2)? as usize;
+    let entry_size = match cell.get(..2) {
+      Some(b"lf" | b"lh") => 8,
+      Some(b"li") => 4,
+      // An index root only points to leaves.
+      Some(b"ri") if indirect => 4,
+      _ => return Err(invalid(format!("invalid subkey list at offset {}", offset))),
+    };
+    for i in 0..count {
+      let entry = le_u32(cell, 4 + i * entry_size)?;
+      match cell.starts_with(b"ri") {
+        true => self.list_offsets(entry, false, offsets)?,
+        false => offsets.push(entry),
+      }
+    }
+    Ok(())
+  }
+
+  /// The subkey named `name`, compared case-insensitively like Windows does.
+  pub fn subkey(&self, name: &str) -> Result<Option<Key<'a>>> {
+    Ok(self.subkeys()?.into_iter().find(|key| key.name.eq_ignore_ascii_case(name)))
+  }
+
+  /// The key at `path` (`\` separated) under this key.
+  pub fn open(&self, path: &str) -> Result<Option<Key<'a>>> {
+    let mut key = self.clone();
+    for name in path.split('\\').filter(|name| !name.is_empty()) {
+      key = match key.subkey(name)? {
+        Some(subkey) => subkey,
+        None => return Ok(None),
+      };
+    }
+    Ok(Some(key))
+  }
+
+  /// The values, in the order they are stored.
+  pub fn values(&self) -> Result<Vec<Value>> {
+    let mut values = vec![];
+    if self.values_count == 0 {
+      return Ok(values);
+    }
+    let list = self.hive.cell(self.values_offset)?;
+    for i in 0..self.values_count as usize {
+      values.push(self.value_at(le_u32(list, i * 4)?)?);
+    }
+    Ok(values)
+  }
+
+  /// The value named `name`, compared case-insensitively.
+  pub fn value(&self, name: &str) -> Result<Option<Value>> {
+    Ok(self.values()?.into_iter().find(|value| value.name.eq_ignore_ascii_case(name)))
+  }
+
+  fn value_at(&self, offset: u32) -> Result<Value> {
+    let cell = self.hive.signed_cell(offset, b"vk")?;
+    let name_length = le_u16(cell, 2)? as usize;
+    let size = le_u32(cell, 4)?;
+    let data_offset = le_u32(cell, 8)?;
+    let flags = le_u16(cell, 16)?;
+    let name_bytes = cell
+      .get(20..20 + name_length)
+      .ok_or_else(|| invalid(format!("value name out of the cell at offset {}", offset)))?;
+
+    // The most significant bit of the size is set when the data (up to 4 bytes) is stored in the offset field.
+    let data = if size & 0x80000000 != 0 {
+      data_offset.to_le_bytes()[..(size & 0x7FFFFFFF).min(4) as usize].to_vec()
+    } else if size as usize > MAX_VALUE_DATA_SIZE && self.hive.minor_version >= 4 {
+      self.big_data(data_offset, size as usize)?
+    } else {
+      let data = self.hive.cell(data_offset)?;
+      data
+        .get(..size as usize)
+        .ok_or_else(|| invalid(format!("value data out of the cell at offset {}", data_offset)))?
+        .to_vec()
+    };
+    Ok(Value {
+      name: name(name_bytes, flags & VALUE_COMP_NAME != 0),
+      data_type: le_u32(cell, 12)?,
+      data,
+    })
+  }
+
+  /// Concatenate the segments of a `db` cell.
+  fn big_data(&self, offset: u32, size: usize) -> Result<Vec<u8>> {
+    let cell = self.hive.signed_cell(offset, b"db")?;
+    let segments = self.hive.cell(le_u32(cell, 4)?)?;
+    let count = le_u16(cell, 2)? as usize;
+    // The size comes from the value cell, the segments can not hold more than their count allows.
+    let mut data = Vec::with_capacity(size.min(count * MAX_VALUE_DATA_SIZE));
+    for i in 0..count {
+      let segment = self.hive.cell(le_u32(segments, i * 4)?)?;
+      let length = segment.len().min(MAX_VALUE_DATA_SIZE).min(size - data.len());
+      data.extend_from_slice(&segment[..length]);
+    }
+    Ok(data)
+  }
+}
+
+/// A value (`vk` cell) of a key.
+#[derive(Debug, Clone)]
+pub struct Value {
+  /// Empty for the default value of the key.
+  pub name: String,
+  /// `REG_BINARY` (3), `REG_DWORD` (4), ...
+  pub data_type: u32,
+  pub data: Vec<u8>,
+}
+
+impl Value {
+  /// The data as a little endian `REG_DWORD`.
+  pub fn dword(&self) -> Option<u32> {
+    Some(u32::from_le_bytes(self.data.get(..4)?.try_into().ok()?))
+  }
+}
@@ -0,0 +1,382 @@
+//! Byte spans of the parsed fields, to show which bytes every value was read from.
+//!
+//! The spans are keyed by the path of the field in the serialized [`Lnk`](crate::Lnk), e.g.
+//! `link_info.volume_id.serial_number` or `extra_data.extra_data_blocks.0.Tracker.machine_id`. Fields that are not
+//! serialized use the struct field name (`shell_link_header.header_size`, `link_info.volume_id_offset`, ...) and a
+//! structure has a span under its own path. Every ExtraData block, decoded or not, is listed as `extra_data.blocks.N`
+//! in file order.
+//!
+//! The offsets follow the layout of the specification, strings include their terminating NUL character.
+
+use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The bytes a value was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Span {
+  pub offset: usize,
+  pub length: usize,
+}
+
+impl Span {
+  /// Whether the bytes of `other` are all in this span.
+  pub fn contains(&self, other: &Span) -> bool {
+    self.offset <= other.offset && other.offset + other.length <= self.offset + self.length
+  }
+}
+
+/// Spans keyed by field path.
+pub type Spans = BTreeMap<String, Span>;
+
+/// A span and the spans of the fields read from its bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+  pub path: String,
+  pub span: Span,
+  pub children: Vec<Node>,
+}
+
+const TRACKER_SIGNATURE: usize = 0xA0000003;
+
+struct Collector<'a> {
+  data: &'a [u8],
+  spans: Spans,
+}
+
+impl<'a> Collector<'a> {
+  /// Record a span, the spans that do not fit in the data are left out.
+  fn add(&mut self, path: &str, offset: usize, length: usize) -> bool {
+    if offset.checked_add(length).is_none_or(|end| end > self.data.len()) {
+      return false;
+    }
+    self.spans.insert(path.to_string(), Span { offset, length });
+    true
+  }
+
+  fn u16(&self, offset: usize) -> Option<usize> {
+    self
+      .data
+      .get(offset..offset.checked_add(2)?)
+      .map(|b| LittleEndian::read_u16(b) as usize)
+  }
+
+  fn u32(&self, offset: usize) -> Option<usize> {
+    self
+      .data
+      .get(offset..offset.checked_add(4)?)
+      .map(|b| LittleEndian::read_u32(b) as usize)
+  }
+
+  /// Record a NUL terminated string starting at `offset` and ending before `end`.
+  fn string(&mut self, path: &str, offset: usize, end: usize, unicode: bool) {
+    let end = end.min(self.data.len());
+    let char_size = if unicode { 2 } else { 1 };
+    let mut position = offset;
+    while position + char_size <= end {
+      let terminator = self.data[position..position + char_size].iter().all(|b| *b == 0);
+      position += char_size;
+      if terminator {
+        break;
+      }
+    }
+    if position > offset {
+      self.add(path, offset, position - offset);
+    }
+  }
+
+  fn fields(&mut self, prefix: &str, offset: usize, fields: &[(&str, usize, usize)]) {
+    for (name, field_offset, length) in fields {
+      self.add(&format!("{}.{}", prefix, name), offset + field_offset, *length);
+    }
+  }
+
+  fn collect(&mut self) -> Option<()> {
+    if !self.add("shell_link_header", 0, 0x4C) {
+      return None;
+    }
+    self.fields(
+      "shell_link_header",
+      0,
+      &[
+        ("header_size", 0, 4),
+        ("guid", 4, 16),
+        ("flags", 0x14, 4),
+        ("file_attr", 0x18, 4),
+        ("ctime", 0x1C, 8),
+        ("atime", 0x24, 8),
+        ("mtime", 0x2C, 8),
+        ("file_size", 0x34, 4),
+        ("icon_index", 0x38, 4),
+        ("sc", 0x3C, 4),
+        ("hot_key", 0x40, 2),
+        ("reserved0", 0x42, 2),
+        ("reserved1", 0x44, 4),
+        ("reserved2", 0x48, 4),
+      ],
+    );
+    let flags = self.u32(0x14)?;
+    let mut offset = 0x4C;
+
+    if flags & 0x1 != 0 {
+      offset = self.link_target_id_list(offset)?;
+    }
+    if flags & 0x2 != 0 {
+      offset = self.link_info(offset)?;
+    }
+    // The parser reads every StringData as UTF-16.
+    for (flag, name) in [
+      (0x4, "name_string"),
+      (0x8, "relative_path"),
+      (0x10, "working_dir"),
+      (0x20, "command_line_arguments"),
+      (0x40, "icon_location"),
+    ] {
+      if flags & flag != 0 {
+        let length = 2 + self.u16(offset)? * 2;
+        if !self.add(name, offset, length) {
+          return None;
+        }
+        offset += length;
+      }
+    }
+    self.extra_data(offset);
+    Some(())
+  }
+
+  fn link_target_id_list(&mut self, offset: usize) -> Option<usize> {
+    let size = self.u16(offset)?;
+    if !self.add("link_target_id_list", offset, 2 + size) {
+      return None;
+    }
+    self.add("link_target_id_list.size", offset, 2);
+    let end = offset + 2 + size;
+    let mut position = offset + 2;
+    let mut index = 0;
+    while let Some(item_size) = self.u16(position) {
+      if item_size == 0 || position + item_size > end {
+        break;
+      }
+      self.add(&format!("link_target_id_list.shell_items.{}", index), position, item_size);
+      position += item_size;
+      index += 1;
+    }
+    Some(end)
+  }
+
+  fn link_info(&mut self, offset: usize) -> Option<usize> {
+    let size = self.u32(offset)?;
+    if !self.add("link_info", offset, size) {
+      return None;
+    }
+    let end = offset + size;
+    let header_size = self.u32(offset + 4)?;
+    let mut fields = vec![
+      ("size", 0, 4),
+      ("header_size", 4, 4),
+      ("flags", 8, 4),
+      ("volume_id_offset", 0xC, 4),
+      ("local_base_path_offset", 0x10, 4),
+      ("common_network_relative_link_offset", 0x14, 4),
+      ("common_path_suffix_offset", 0x18, 4),
+    ];
+    if header_size >= 0x24 {
+      fields.push(("local_base_path_offset_unicode", 0x1C, 4));
+      fields.push(("common_path_suffix_offset_unicode", 0x20, 4));
+    }
+    self.fields("link_info", offset, &fields);
+
+    let link_info_flags = self.u32(offset + 8)?;
+    let field = |name_offset: usize| self.u32(offset + name_offset).unwrap_or_default();
+    let (volume_id, local_base_path, network_link, suffix) = (field(0xC), field(0x10), field(0x14), field(0x18));
+    let (local_base_path_unicode, suffix_unicode) = match header_size >= 0x24 {
+      true => (field(0x1C), field(0x20)),
+      false => (0, 0),
+    };
+
+    if link_info_flags & 0x1 != 0 {
+      if volume_id != 0 {
+        self.volume_id(offset + volume_id, end);
+      }
+      match (local_base_path_unicode, local_base_path) {
+        (0, 0) => {},
+        (0, ansi) => self.string("link_info.local_base_path", offset + ansi, end, false),
+        (unicode, _) => self.string("link_info.local_base_path", offset + unicode, end, true),
+      }
+    }
+    if link_info_flags & 0x2 != 0 && network_link != 0 {
+      self.common_network_relative_link(offset + network_link, end);
+    }
+    match (suffix_unicode, suffix) {
+      (0, 0) => {},
+      (0, ansi) => self.string("link_info.common_path_suffix", offset + ansi, end, false),
+      (unicode, _) => self.string("link_info.common_path_suffix", offset + unicode, end, true),
+    }
+    Some(end)
+  }
+
+  fn volume_id(&mut self, offset: usize, link_info_end: usize) {
+    let prefix = "link_info.volume_id";
+    let Some(size) = self.u32(offset) else {
+      return;
+    };
+    if offset + size > link_info_end || !self.add(prefix, offset, size) {
+      return;
+    }
+    let label_offset = self.u32(offset + 0xC).unwrap_or_default();
+    let mut fields = vec![
+      ("size", 0, 4),
+      ("drive_type", 4, 4),
+      ("serial_number", 8, 4),
+      ("volume_lable_offset", 0xC, 4),
+    ];
+    if label_offset == 0x14 {
+      fields.push(("volume_lable_offset_unicode", 0x10, 4));
+    }
+    self.fields(prefix, offset, &fields);
+    match label_offset {
+      0x14 => {
+        let unicode = self.u32(offset + 0x10).unwrap_or_default();
+        if unicode != 0 {
+          self.string("link_info.volume_id.volume_lable", offset + unicode, offset + size, true);
+        }
+      },
+      0 => {},
+      ansi => self.string("link_info.volume_id.volume_lable", offset + ansi, offset + size, false),
+    }
+  }
+
+  fn common_network_relative_link(&mut self, offset: usize, link_info_end: usize) {
+    let prefix = "link_info.common_network_relative_link";
+    let Some(size) = self.u32(offset) else {
+      return;
+    };
+    if offset + size > link_info_end || !self.add(prefix, offset, size) {
+      return;
+    }
+    let end = offset + size;
+    let flags = self.u32(offset + 4).unwrap_or_default();
+    let net_name = self.u32(offset + 8).unwrap_or_default();
+    let device_name = self.u32(offset + 0xC).unwrap_or_default();
+    let mut fields = vec![
+      ("size", 0, 4),
+      ("flags", 4, 4),
+      ("net_name_offset", 8, 4),
+      ("device_name_offset", 0xC, 4),
+      ("network_provider_type", 0x10, 4),
+    ];
+    let unicode = net_name > 0x14;
+    if unicode {
+      fields.push(("net_name_offset_unicode", 0x14, 4));
+      fields.push(("device_name_offset_unicode", 0x18, 4));
+    }
+    self.fields(prefix, offset, &fields);
+
+    let (net_name, device_name) = match unicode {
+      true => (self.u32(offset + 0x14).unwrap_or_default(), self.u32(offset + 0x18).unwrap_or_default()),
+      false => (net_name, device_name),
+    };
+    if net_name != 0 {
+      self.string(&format!("{}.net_name", prefix), offset + net_name, end, unicode);
+    }
+    if flags & 0x1 != 0 && device_name != 0 {
+      self.string(&format!("{}.device_name", prefix), offset + device_name, end, unicode);
+    }
+  }
+
+  fn extra_data(&mut self, mut offset: usize) {
+    let mut index = 0;
+    // Index of the block in `ExtraData::extra_data_blocks`, only the decoded blocks are in it.
+    let mut decoded = 0;
+    while let Some(size) = self.u32(offset) {
+      if size < 8 {
+        if size < 4 {
+          self.add("extra_data.terminal_block", offset, 4);
+        }
+        break;
+      }
+      let prefix = format!("extra_data.blocks.{}", index);
+      if !self.add(&prefix, offset, size) {
+        break;
+      }
+      self.fields(&prefix, offset, &[("size", 0, 4), ("signature", 4, 4)]);
+      if self.u32(offset + 4) == Some(TRACKER_SIGNATURE) && size >= 0x60 {
+        let prefix = format!("extra_data.extra_data_blocks.{}.Tracker", decoded);
+        self.fields(
+          &prefix,
+          offset,
+          &[
+            ("size", 8, 4),
+            ("version", 0xC, 4),
+            ("machine_id", 0x10, 16),
+            ("volume_droid", 0x20, 16),
+            ("file_droid", 0x30, 16),
+            ("volume_droid_birth", 0x40, 16),
+            ("file_droid_birth", 0x50, 16),
+          ],
+        );
+        decoded += 1;
+      }
+      offset += size;
+      index += 1;
+    }
+  }
+}
+
+/// Compute the spans of the fields of the LNK data. Data that can not be located (truncated or inconsistent
+/// structures) has no span, the spans found before it are still returned.
+/// # Example
+/// ```
+/// use lnk::spans::{spans, Span};
+///
+/// let data = std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+/// let spans = spans(&data);
+/// assert_eq!(spans["shell_link_header.file_size"], Span { offset: 0x34, length: 4 });
+/// ```
+pub fn spans(data: &[u8]) -> Spans {
+  let mut collector = Collector {
+    data,
+    spans: Spans::new(),
+  };
+  collector.collect();
+  collector.spans
+}
+
+/// Nest the spans by the bytes they cover, a span is a child of the smallest span containing it. The nodes are in
+/// file order, so the top-level nodes are the sections of the file (header, IDList, LinkInfo, every StringData and
+/// every ExtraData block).
+/// # Example
+/// ```
+/// use lnk::spans::{spans, tree};
+///
+/// let data = std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+/// let sections = tree(&spans(&data));
+/// assert_eq!(sections[0].path, "shell_link_header");
+/// assert_eq!(sections[0].children[0].path, "shell_link_header.header_size");
+/// ```
+pub fn tree(spans: &Spans) -> Vec<Node> {
+  fn insert(nodes: &mut Vec<Node>, node: Node) {
+    match nodes.last_mut() {
+      Some(last) if last.span.contains(&node.span) => insert(&mut last.children, node),
+      _ => nodes.push(node),
+    }
+  }
+  let mut sorted: Vec<(&String, &Span)> = spans.iter().collect();
+  // Containing spans first, a field with the same bytes as its structure has a longer path.
+  sorted.sort_by_key(|(path, span)| (span.offset, std::cmp::Reverse(span.length), path.len()));
+  let mut nodes = vec![];
+  for (path, span) in sorted {
+    insert(
+      &mut nodes,
+      Node {
+        path: path.clone(),
+        span: *span,
+        children: vec![],
+      },
+    );
+  }
+  nodes
+}
@@ -0,0 +1,246 @@
+//! Locate LNK related artifacts inside a mounted Windows volume.
+//!
+//! Windows paths are case-insensitive while the file system holding the evidence (ext4, an
+//! `ewfmount`/`ntfs-3g` mount, an extracted triage folder, etc) usually is not, so every path
+//! component is matched case-insensitively against the directory entries found under the root.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// The kind of artifact file found under a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+  /// A plain shell link (`.lnk`) file.
+  Lnk,
+  /// An `*.automaticDestinations-ms` jump list (OLE compound file holding LNK streams).
+  AutomaticDestinations,
+  /// An `*.customDestinations-ms` jump list (concatenated LNK entries).
+  CustomDestinations,
+}
+
+impl ArtifactKind {
+  /// Guess the artifact kind from the file extension.
+  pub fn from_path<P: AsRef<Path>>(path: P) -> ArtifactKind {
+    let extension = path
+      .as_ref()
+      .extension()
+      .and_then(|e| e.to_str())
+      .map(|e| e.to_lowercase())
+      .unwrap_or_default();
+    match extension.as_str() {
+      "automaticdestinations-ms" => ArtifactKind::AutomaticDestinations,
+      "customdestinations-ms" => ArtifactKind::CustomDestinations,
+      _ => ArtifactKind::Lnk,
+    }
+  }
+}
+
+/// An artifact file found under the root and the user profile it belongs to.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+  pub path: PathBuf,
+  pub kind: ArtifactKind,
+  /// Name of the profile directory the artifact was found in (`None` for machine wide locations).
+  pub user: Option<String>,
+}
+
+/// A location relative to a profile directory that may contain artifacts.
+struct Location {
+  components: &'static [&'static str],
+  extension: &'static str,
+  recursive: bool,
+}
+
+const PROFILE_LOCATIONS: &[Location] = &[
+  // Recent items
+  Location {
+    components: &["AppData", "Roaming", "Microsoft", "Windows", "Recent"],
+    extension: "lnk",
+    recursive: false,
+  },
+  // Jump lists
+  Location {
+    components: &[
+      "AppData",
+      "Roaming",
+      "Microsoft",
+      "Windows",
+      "Recent",
+      "AutomaticDestinations",
+    ],
+    extension: "automaticDestinations-ms",
+    recursive: false,
+  },
+  Location {
+    components: &[
+      "AppData",
+      "Roaming",
+      "Microsoft",
+      "Windows",
+      "Recent",
+      "CustomDestinations",
+    ],
+    extension: "customDestinations-ms",
+    recursive: false,
+  },
+  // Office recent items
+  Location {
+    components: &["AppData", "Roaming", "Microsoft", "Office", "Recent"],
+    extension: "lnk",
+    recursive: false,
+  },
+  // Start Menu
+  Location {
+    components: &["AppData", "Roaming", "Microsoft", "Windows", "Start Menu"],
+    extension: "lnk",
+    recursive: true,
+  },
+  // Desktop
+  Location {
+    components: &["Desktop"],
+    extension: "lnk",
+    recursive: false,
+  },
+];
+
+/// Profile directories relative to the volume root, `*` matches any directory name.
+const PROFILE_DIRECTORIES: &[&[&str]] = &[
+  &["Users", "*"],
+  &["Windows", "System32", "config", "systemprofile"],
+  &["Windows", "SysWOW64", "config", "systemprofile"],
+  &["Windows", "ServiceProfiles", "*"],
+];
+
+/// Machine wide locations that do not belong to a profile.
+const COMMON_LOCATIONS: &[Location] = &[Location {
+  components: &["ProgramData", "Microsoft", "Windows", "Start Menu"],
+  extension: "lnk",
+  recursive: true,
+}];
+
+/// Find every artifact under `root`, which is the mount point of a Windows volume (the directory that contains
+/// `Users` and `Windows`). The result is sorted by path so the order is stable between runs.
+pub fn collect<P: AsRef<Path>>(root: P) -> Vec<Artifact> {
+  let root = root.as_ref();
+  let mut artifacts = vec![];
+
+  for profile_components in PROFILE_DIRECTORIES {
+    for profile in resolve(root, profile_components) {
+      if !profile.is_dir() {
+        continue;
+      }
+      let user = profile.file_name().map(|name| name.to_string_lossy().to_string());
+      for location in PROFILE_LOCATIONS {
+        artifacts.extend(find_in_location(&profile, location, &user));
+      }
+    }
+  }
+
+  for location in COMMON_LOCATIONS {
+    artifacts.extend(find_in_location(root, location, &None));
+  }
+
+  artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+  artifacts.dedup_by(|a, b| a.path == b.path);
+  artifacts
+}
+
+/// Extract the profile name from a Windows (or mounted) path.
+/// # Example
+/// ```
+/// use lnk::triage::user_from_path;
+///
+/// assert_eq!(
+///   user_from_path(r"C:\Users\u0041\AppData\Roaming\Microsoft\Windows\Recent\a.lnk"),
+///   Some(String::from("u0041"))
+/// );
+/// assert_eq!(
+///   user_from_path("/mnt/evidence/Windows/System32/config/systemprofile/Desktop/a.lnk"),
+///   Some(String::from("systemprofile"))
+/// );
+/// ```
+pub fn user_from_path(path: &str) -> Option<String> {
+  let components: Vec<&str> = path.split(['\\', '/']).filter(|c| !c.is_empty()).collect();
+  // The last profile marker wins, so a mount point such as `/home/x/Users/...` does not confuse the lookup.
+  let position = components.iter().rposition(|c| {
+    c.eq_ignore_ascii_case("Users")
+      || c.eq_ignore_ascii_case("Documents and Settings")
+      || c.eq_ignore_ascii_case("ServiceProfiles")
+      || c.eq_ignore_ascii_case("systemprofile")
+  })?;
+  if components[position].eq_ignore_ascii_case("systemprofile") {
+    return Some(components[position].to_string());
+  }
+  // The component after the marker must be a directory, not the file itself.
+  if position + 2 < components.len() {
+    Some(components[position + 1].to_string())
+  } else {
+    None
+  }
+}
+
+/// Resolve `components` under `base` matching every component case-insensitively. `*` matches every entry.
+/// More than one path can be returned, e.g. when both `Users` and `users` exist on a case-sensitive file system.
+pub fn resolve<P: AsRef<Path>>(base: P, components: &[&str]) -> Vec<PathBuf> {
+  let mut current = vec![base.as_ref().to_path_buf()];
+  for component in components {
+    let mut next = vec![];
+    for dir in &current {
+      let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => continue,
+      };
+      for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if *component == "*" || name.to_lowercase() == component.to_lowercase() {
+          next.push(entry.path());
+        }
+      }
+    }
+    next.sort();
+    current = next;
+  }
+  current
+}
+
+fn find_in_location(base: &Path, location: &Location, user: &Option<String>) -> Vec<Artifact> {
+  let mut artifacts = vec![];
+  for dir in resolve(base, location.components) {
+    collect_files(&dir, location.extension, location.recursive, &mut |path| {
+      artifacts.push(Artifact {
+        kind: ArtifactKind::from_path(&path),
+        path,
+        user: user.clone(),
+      })
+    });
+  }
+  artifacts
+}
+
+fn collect_files(dir: &Path, extension: &str, recursive: bool, found: &mut dyn FnMut(PathBuf)) {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(_) => return,
+  };
+  // Symbolic links are not followed into directories, a link to a parent directory would loop forever.
+  let mut paths: Vec<(PathBuf, bool)> = entries
+    .flatten()
+    .map(|e| (e.path(), e.file_type().is_ok_and(|t| t.is_dir())))
+    .collect();
+  paths.sort();
+  for (path, is_dir) in paths {
+    if is_dir {
+      if recursive {
+        collect_files(&path, extension, recursive, found);
+      }
+    } else if path
+      .extension()
+      .and_then(|e| e.to_str())
+      .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+    {
+      found(path);
+    }
+  }
+}
@@ -0,0 +1,471 @@
+//! Check LNK data against the [MS-SHLLINK](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/)
+//! specification.
+//!
+//! The parser accepts a lot of files Windows would never write. [`validate`] reports every deviation from the
+//! specification with the offset of the field, crafted files usually break at least one of these rules.
+
+use crate::jump_list::LNK_SIGNATURE;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+use std::fmt;
+
+const HEADER_SIZE: usize = 0x4C;
+
+// LinkFlags, as stored in the file (little endian).
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const IS_UNICODE: u32 = 0x80;
+const FORCE_NO_LINK_INFO: u32 = 0x100;
+const HAS_EXP_STRING: u32 = 0x200;
+const HAS_DARWIN_ID: u32 = 0x1000;
+const HAS_EXP_ICON: u32 = 0x4000;
+const RUN_WITH_SHIM_LAYER: u32 = 0x20000;
+/// Bits above KeepLocalIDListForUNCTarget are not defined.
+const UNDEFINED_LINK_FLAGS: u32 = 0xF800_0000;
+/// HasName, HasRelativePath, HasWorkingDir, HasArguments and HasIconLocation, in the StringData order.
+const STRING_DATA: [(u32, &str); 5] = [
+  (0x4, "NAME_STRING"),
+  (0x8, "RELATIVE_PATH"),
+  (0x10, "WORKING_DIR"),
+  (0x20, "COMMAND_LINE_ARGUMENTS"),
+  (0x40, "ICON_LOCATION"),
+];
+
+// FileAttributes bits that must be zero.
+const FILE_ATTRIBUTE_RESERVED1: u32 = 0x8;
+const FILE_ATTRIBUTE_RESERVED2: u32 = 0x40;
+
+// LinkInfoFlags.
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+// CommonNetworkRelativeLinkFlags.
+const VALID_DEVICE: u32 = 0x1;
+const VALID_NET_TYPE: u32 = 0x2;
+
+/// ExtraData block signatures with their name, minimum size and whether the size is fixed.
+const EXTRA_DATA_BLOCKS: [(u32, &str, u32, bool); 11] = [
+  (0xA0000001, "EnvironmentVariableDataBlock", 0x314, true),
+  (0xA0000002, "ConsoleDataBlock", 0xCC, true),
+  (0xA0000003, "TrackerDataBlock", 0x60, true),
+  (0xA0000004, "ConsoleFEDataBlock", 0xC, true),
+  (0xA0000005, "SpecialFolderDataBlock", 0x10, true),
+  (0xA0000006, "DarwinDataBlock", 0x314, true),
+  (0xA0000007, "IconEnvironmentDataBlock", 0x314, true),
+  (0xA0000008, "ShimDataBlock", 0x88, false),
+  (0xA0000009, "PropertyStoreDataBlock", 0xC, false),
+  (0xA000000B, "KnownFolderDataBlock", 0x1C, true),
+  (0xA000000C, "VistaAndAboveIDListDataBlock", 0xA, false),
+];
+
+/// A rule of the specification the data does not follow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Deviation {
+  /// Offset of the field from the start of the data.
+  pub offset: usize,
+  /// The structure and field name used by the specification, e.g. `LinkInfo.LinkInfoHeaderSize`.
+  pub field: String,
+  pub message: String,
+}
+
+impl fmt::Display for Deviation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "0x{:X} {}: {}", self.offset, self.field, self.message)
+  }
+}
+
+struct Validator<'a> {
+  data: &'a [u8],
+  deviations: Vec<Deviation>,
+}
+
+impl<'a> Validator<'a> {
+  fn report(&mut self, offset: usize, field: &str, message: String) {
+    self.deviations.push(Deviation {
+      offset,
+      field: field.to_string(),
+      message,
+    });
+  }
+
+  fn u16(&self, offset: usize) -> Option<u32> {
+    self
+      .data
+      .get(offset..offset.checked_add(2)?)
+      .map(|b| LittleEndian::read_u16(b) as u32)
+  }
+
+  fn u32(&self, offset: usize) -> Option<u32> {
+    self.data.get(offset..offset.checked_add(4)?).map(LittleEndian::read_u32)
+  }
+
+  /// Check the structures in order, stops at the first one that can not be located.
+  fn check(&mut self) -> Option<()> {
+    let link_flags = self.header()?;
+    let mut offset = HEADER_SIZE;
+    if link_flags & HAS_LINK_TARGET_ID_LIST != 0 {
+      offset = self.id_list(offset)?;
+    }
+    if link_flags & HAS_LINK_INFO != 0 {
+      offset = self.link_info(offset)?;
+    }
+    offset = self.string_data(offset, link_flags)?;
+    self.extra_data(offset, link_flags);
+    Some(())
+  }
+
+  /// Check the ShellLinkHeader, returns the LinkFlags.
+  fn header(&mut self) -> Option<u32> {
+    if self.data.len() < HEADER_SIZE {
+      self.report(
+        0,
+        "ShellLinkHeader",
+        format!("the header needs 0x{:X} bytes, the data is 0x{:X} bytes", HEADER_SIZE, self.data.len()),
+      );
+      return None;
+    }
+    let header_size = self.u32(0)?;
+    if header_size != HEADER_SIZE as u32 {
+      self.report(0, "HeaderSize", format!("0x{:X}, expected 0x4C", header_size));
+    }
+    if self.data[4..20] != LNK_SIGNATURE[4..] {
+      self.report(4, "LinkCLSID", String::from("not 00021401-0000-0000-C000-000000000046"));
+    }
+    let link_flags = self.u32(0x14)?;
+    if link_flags & UNDEFINED_LINK_FLAGS != 0 {
+      self.report(0x14, "LinkFlags", format!("undefined bits set (0x{:08X})", link_flags & UNDEFINED_LINK_FLAGS));
+    }
+    if link_flags & HAS_LINK_INFO != 0 && link_flags & FORCE_NO_LINK_INFO != 0 {
+      self.report(
+        0x14,
+        "LinkFlags",
+        String::from("HasLinkInfo and ForceNoLinkInfo are both set, the LinkInfo is ignored"),
+      );
+    }
+    let file_attributes = self.u32(0x18)?;
+    if file_attributes & (FILE_ATTRIBUTE_RESERVED1 | FILE_ATTRIBUTE_RESERVED2) != 0 {
+      self.report(
+        0x18,
+        "FileAttributes",
+        format!(
+          "reserved bits set (0x{:08X})",
+          file_attributes & (FILE_ATTRIBUTE_RESERVED1 | FILE_ATTRIBUTE_RESERVED2)
+        ),
+      );
+    }
+    let show_command = self.u32(0x3C)?;
+    if ![1, 3, 7].contains(&show_command) {
+      self.report(0x3C, "ShowCommand", format!("{}, expected 1, 3 or 7", show_command));
+    }
+    let (key, modifiers) = (self.data[0x40], self.data[0x41]);
+    let valid_key = matches!(key, 0 | 0x30..=0x39 | 0x41..=0x5A | 0x70..=0x87 | 0x90 | 0x91);
+    if !valid_key || modifiers & !0x7 != 0 {
+      self.report(0x40, "HotKey", format!("invalid key 0x{:02X} with modifiers 0x{:02X}", key, modifiers));
+    }
+    for (offset, field, value) in [
+      (0x42, "Reserved1", self.u16(0x42)?),
+      (0x44, "Reserved2", self.u32(0x44)?),
+      (0x48, "Reserved3", self.u32(0x48)?),
+    ] {
+      if value != 0 {
+        self.report(offset, field, format!("0x{:X}, must be zero", value));
+      }
+    }
+    Some(link_flags)
+  }
+
+  /// Check the LinkTargetIDList at `offset`, returns the offset of the next structure.
+  fn id_list(&mut self, offset: usize) -> Option<usize> {
+    let Some(id_list_size) = self.u16(offset) else {
+      self.report(offset, "LinkTargetIDList.IDListSize", String::from("beyond the end of the data"));
+      return None;
+    };
+    let end = offset + 2 + id_list_size as usize;
+    if end > self.data.len() {
+      self.report(
+        offset,
+        "LinkTargetIDList.IDListSize",
+        format!("0x{:X} bytes, beyond the end of the data", id_list_size),
+      );
+      return None;
+    }
+    if id_list_size < 2 {
+      self.report(offset, "LinkTargetIDList.IDListSize", format!("0x{:X}, no room for the TerminalID", id_list_size));
+      return Some(end);
+    }
+    let mut position = offset + 2;
+    loop {
+      match self.u16(position) {
+        Some(0) if position + 2 == end => break,
+        Some(0) => {
+          self.report(
+            position,
+            "LinkTargetIDList.TerminalID",
+            format!("found 0x{:X} bytes before the end of the IDList", end - position - 2),
+          );
+          break;
+        },
+        Some(size) if size >= 2 && position + (size as usize) <= end.saturating_sub(2) => position += size as usize,
+        _ => {
+          self.report(position, "LinkTargetIDList.ItemIDSize", String::from("the ItemID exceeds the IDList"));
+          break;
+        },
+      }
+    }
+    Some(end)
+  }
+
+  /// Check the LinkInfo at `offset`, returns the offset of the next structure.
+  fn link_info(&mut self, offset: usize) -> Option<usize> {
+    let (Some(size), Some(header_size)) = (self.u32(offset), self.u32(offset + 4)) else {
+      self.report(offset, "LinkInfo", String::from("beyond the end of the data"));
+      return None;
+    };
+    let (size, header_size) = (size as usize, header_size as usize);
+    let end = offset.saturating_add(size);
+    if end > self.data.len() {
+      self.report(offset, "LinkInfo.LinkInfoSize", format!("0x{:X}, beyond the end of the data", size));
+      return None;
+    }
+    if header_size != 0x1C && header_size < 0x24 {
+      self.report(
+        offset + 4,
+        "LinkInfo.LinkInfoHeaderSize",
+        format!("0x{:X}, expected 0x1C or at least 0x24", header_size),
+      );
+    }
+    if header_size > size || size < 0x1C {
+      self.report(offset + 4, "LinkInfo.LinkInfoHeaderSize", format!("0x{:X} exceeds the LinkInfoSize", header_size));
+      return Some(end);
+    }
+    let flags = self.u32(offset + 8)?;
+    if flags & !(VOLUME_ID_AND_LOCAL_BASE_PATH | COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX) != 0 {
+      self.report(offset + 8, "LinkInfo.LinkInfoFlags", format!("undefined bits set (0x{:08X})", flags));
+    }
+
+    let mut fields = vec![
+      (0xC, "VolumeIDOffset", flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0),
+      (0x10, "LocalBasePathOffset", flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0),
+      (0x14, "CommonNetworkRelativeLinkOffset", flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0),
+      (0x18, "CommonPathSuffixOffset", true),
+    ];
+    if header_size >= 0x24 {
+      fields.push((0x1C, "LocalBasePathOffsetUnicode", flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0));
+      fields.push((0x20, "CommonPathSuffixOffsetUnicode", true));
+    }
+    for (field_offset, field, present) in fields {
+      let value = self.u32(offset + field_offset)? as usize;
+      let field = format!("LinkInfo.{}", field);
+      if !present {
+        if value != 0 {
+          self.report(offset + field_offset, &field, format!("0x{:X}, must be zero for the LinkInfoFlags", value));
+        }
+      } else if value < header_size || value >= size {
+        self.report(
+          offset + field_offset,
+          &field,
+          format!("0x{:X}, outside the LinkInfo data (0x{:X}..0x{:X})", value, header_size, size),
+        );
+      } else if field_offset == 0xC {
+        self.volume_id(offset + value, end);
+      } else if field_offset == 0x14 {
+        self.common_network_relative_link(offset + value, end);
+      }
+    }
+    Some(end)
+  }
+
+  fn volume_id(&mut self, offset: usize, link_info_end: usize) {
+    let (Some(size), Some(drive_type)) = (self.u32(offset), self.u32(offset + 4)) else {
+      return;
+    };
+    let size = size as usize;
+    if size <= 0x10 || offset + size > link_info_end {
+      self.report(
+        offset,
+        "VolumeID.VolumeIDSize",
+        format!("0x{:X}, must be greater than 0x10 and within the LinkInfo", size),
+      );
+      return;
+    }
+    if drive_type > 6 {
+      self.report(offset + 4, "VolumeID.DriveType", format!("{}, not a DRIVE_* value", drive_type));
+    }
+    let Some(label_offset) = self.u32(offset + 0xC) else {
+      return;
+    };
+    let (field, label_offset) = match label_offset {
+      0x14 => ("VolumeID.VolumeLabelOffsetUnicode", self.u32(offset + 0x10).unwrap_or_default()),
+      _ => ("VolumeID.VolumeLabelOffset", label_offset),
+    };
+    if label_offset as usize >= size {
+      self.report(offset + 0xC, field, format!("0x{:X}, outside the VolumeID", label_offset));
+    }
+  }
+
+  fn common_network_relative_link(&mut self, offset: usize, link_info_end: usize) {
+    let (Some(size), Some(flags)) = (self.u32(offset), self.u32(offset + 4)) else {
+      return;
+    };
+    let size = size as usize;
+    if size < 0x14 || offset + size > link_info_end {
+      self.report(
+        offset,
+        "CommonNetworkRelativeLink.CommonNetworkRelativeLinkSize",
+        format!("0x{:X}, must be at least 0x14 and within the LinkInfo", size),
+      );
+      return;
+    }
+    if flags & !(VALID_DEVICE | VALID_NET_TYPE) != 0 {
+      self.report(
+        offset + 4,
+        "CommonNetworkRelativeLink.CommonNetworkRelativeLinkFlags",
+        format!("undefined bits set (0x{:08X})", flags),
+      );
+    }
+    let net_name_offset = self.u32(offset + 8).unwrap_or_default() as usize;
+    let device_name_offset = self.u32(offset + 0xC).unwrap_or_default() as usize;
+    let provider_type = self.u32(offset + 0x10).unwrap_or_default();
+    if net_name_offset < 0x14 || net_name_offset >= size {
+      self.report(
+        offset + 8,
+        "CommonNetworkRelativeLink.NetNameOffset",
+        format!("0x{:X}, outside the CommonNetworkRelativeLink", net_name_offset),
+      );
+    }
+    if flags & VALID_DEVICE == 0 && device_name_offset != 0 {
+      self.report(
+        offset + 0xC,
+        "CommonNetworkRelativeLink.DeviceNameOffset",
+        format!("0x{:X} without ValidDevice, must be zero", device_name_offset),
+      );
+    } else if flags & VALID_DEVICE != 0 && (device_name_offset < 0x14 || device_name_offset >= size) {
+      self.report(
+        offset + 0xC,
+        "CommonNetworkRelativeLink.DeviceNameOffset",
+        format!("0x{:X}, outside the CommonNetworkRelativeLink", device_name_offset),
+      );
+    }
+    if flags & VALID_NET_TYPE == 0 && provider_type != 0 {
+      self.report(
+        offset + 0x10,
+        "CommonNetworkRelativeLink.NetworkProviderType",
+        format!("0x{:X} without ValidNetType, must be zero", provider_type),
+      );
+    }
+  }
+
+  /// Check the StringData structures at `offset`, returns the offset of the next structure.
+  fn string_data(&mut self, mut offset: usize, link_flags: u32) -> Option<usize> {
+    let char_size = if link_flags & IS_UNICODE != 0 { 2 } else { 1 };
+    for (flag, name) in STRING_DATA {
+      if link_flags & flag == 0 {
+        continue;
+      }
+      let Some(count) = self.u16(offset) else {
+        self.report(offset, &format!("{}.CountCharacters", name), String::from("beyond the end of the data"));
+        return None;
+      };
+      let end = offset + 2 + count as usize * char_size;
+      if end > self.data.len() {
+        self.report(
+          offset,
+          &format!("{}.CountCharacters", name),
+          format!("{} characters, beyond the end of the data", count),
+        );
+        return None;
+      }
+      offset = end;
+    }
+    Some(offset)
+  }
+
+  /// Check the ExtraData blocks at `offset` and the LinkFlags that announce some of them.
+  fn extra_data(&mut self, mut offset: usize, link_flags: u32) {
+    let mut signatures = vec![];
+    loop {
+      let Some(size) = self.u32(offset) else {
+        self.report(offset, "TerminalBlock", String::from("missing, the data ends without a terminal block"));
+        break;
+      };
+      if size < 4 {
+        if size != 0 {
+          self.report(offset, "TerminalBlock", format!("0x{:X}, expected 0", size));
+        }
+        if offset + 4 < self.data.len() {
+          self.report(
+            offset + 4,
+            "ExtraData",
+            format!("0x{:X} bytes after the terminal block", self.data.len() - offset - 4),
+          );
+        }
+        break;
+      }
+      if offset + size as usize > self.data.len() || size < 8 {
+        self.report(offset, "ExtraData.BlockSize", format!("0x{:X}, beyond the end of the data", size));
+        break;
+      }
+      let signature = self.u32(offset + 4).unwrap_or_default();
+      match EXTRA_DATA_BLOCKS.iter().find(|(s, ..)| *s == signature) {
+        Some(&(_, name, minimum, fixed)) => {
+          if (fixed && size != minimum) || size < minimum {
+            let expected = if fixed { "" } else { "at least " };
+            self.report(
+              offset,
+              &format!("{}.BlockSize", name),
+              format!("0x{:X}, expected {}0x{:X}", size, expected, minimum),
+            );
+          } else if signature == 0xA0000003 {
+            let (length, version) =
+              (self.u32(offset + 8).unwrap_or_default(), self.u32(offset + 12).unwrap_or_default());
+            if length != 0x58 {
+              self.report(offset + 8, "TrackerDataBlock.Length", format!("0x{:X}, expected 0x58", length));
+            }
+            if version != 0 {
+              self.report(offset + 12, "TrackerDataBlock.Version", format!("{}, expected 0", version));
+            }
+          }
+          if signatures.contains(&signature) {
+            self.report(offset + 4, &format!("{}.BlockSignature", name), String::from("duplicate block"));
+          }
+        },
+        None => self.report(offset + 4, "ExtraData.BlockSignature", format!("unknown signature 0x{:08X}", signature)),
+      }
+      signatures.push(signature);
+      offset += size as usize;
+    }
+
+    for (flag, flag_name, signature, block_name) in [
+      (HAS_EXP_STRING, "HasExpString", 0xA0000001, "EnvironmentVariableDataBlock"),
+      (HAS_DARWIN_ID, "HasDarwinID", 0xA0000006, "DarwinDataBlock"),
+      (HAS_EXP_ICON, "HasExpIcon", 0xA0000007, "IconEnvironmentDataBlock"),
+      (RUN_WITH_SHIM_LAYER, "RunWithShimLayer", 0xA0000008, "ShimDataBlock"),
+    ] {
+      match (link_flags & flag != 0, signatures.contains(&signature)) {
+        (true, false) => self.report(0x14, "LinkFlags", format!("{} is set without a {}", flag_name, block_name)),
+        (false, true) => self.report(0x14, "LinkFlags", format!("{} is present without {}", block_name, flag_name)),
+        _ => {},
+      }
+    }
+  }
+}
+
+/// Check LNK data against the specification. Every deviation is reported, in the order of the structures, an empty
+/// list means the data is well-formed. Checking stops at the first structure that goes beyond the end of the data.
+/// # Example
+/// ```
+/// let mut data = std::fs::read("samples/WIN10/1607_14393/windows_generated.lnk").unwrap();
+/// assert!(lnk::validate(&data).is_empty());
+///
+/// data[0x48] = 1;
+/// let deviations = lnk::validate(&data);
+/// assert_eq!(deviations[0].to_string(), "0x48 Reserved3: 0x1, must be zero");
+/// ```
+pub fn validate(data: &[u8]) -> Vec<Deviation> {
+  let mut validator = Validator {
+    data,
+    deviations: vec![],
+  };
+  validator.check();
+  validator.deviations
+}
@@ -0,0 +1,529 @@
+//! Create LNK files from a specification and edit existing ones.
+//!
+//! The specification uses the names of the normalized fields (see [`NORMALIZED_FIELDS`](crate::NORMALIZED_FIELDS)),
+//! so a record written by `lnk_parser --normalize` can be used to create a similar file. Strings are always
+//! written as UTF-16 (`IsUnicode`) and the target is stored in the LinkInfo structure, no LinkTargetIDList is
+//! written.
+
+use crate::link_info::LinkInfo;
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Deserializer};
+use std::io::{Error, ErrorKind, Result};
+
+const HEADER_SIZE: usize = 0x4C;
+const LINK_CLSID: [u8; 16] = [
+  0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// LinkFlags as stored in the file (little endian).
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const IS_UNICODE: u32 = 0x80;
+const FORCE_NO_LINK_INFO: u32 = 0x100;
+const HAS_EXP_STRING: u32 = 0x200;
+/// The flags of the StringData structures, in the order they are stored.
+const STRING_DATA_FLAGS: [u32; 5] = [0x4, 0x8, 0x10, 0x20, 0x40];
+const ARGUMENTS: usize = 3;
+const RELATIVE_PATH: usize = 1;
+
+const TRACKER_SIGNATURE: u32 = 0xA0000003;
+/// ExtraData blocks that describe the target, they are dropped when the target changes.
+const TARGET_BLOCK_SIGNATURES: &[u32] = &[
+  0xA0000001, // EnvironmentVariableDataBlock
+  TRACKER_SIGNATURE,
+  0xA0000005, // SpecialFolderDataBlock
+  0xA0000009, // PropertyStoreDataBlock
+  0xA000000B, // KnownFolderDataBlock
+  0xA000000C, // VistaAndAboveIDListDataBlock
+];
+
+const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x20;
+const SW_SHOWNORMAL: u32 = 1;
+const WNNC_NET_LANMAN: u32 = 0x00020000;
+/// Seconds between 1601-01-01 and 1970-01-01.
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
+/// Description of a LNK file to create, empty strings are the same as missing fields.
+/// # Example
+/// ```
+/// use lnk::{writer::LnkSpec, Lnk};
+///
+/// let spec: LnkSpec = serde_json::from_str(r#"{
+///   "target_full_path": "C:\\Windows\\System32\\cmd.exe",
+///   "command_line_arguments": "/c whoami",
+///   "target_hostname": "ws01"
+/// }"#).unwrap();
+/// let lnk = Lnk::from_buffer(&spec.to_bytes().unwrap()).unwrap();
+/// assert_eq!(lnk.target_full_path.as_deref(), Some(r"C:\Windows\System32\cmd.exe"));
+/// assert_eq!(lnk.command_line_arguments.unwrap().string, "/c whoami");
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LnkSpec {
+  /// A local (`C:\...`) or UNC (`\\server\share\...`) path.
+  pub target_full_path: String,
+  pub command_line_arguments: Option<String>,
+  pub working_dir: Option<String>,
+  pub name_string: Option<String>,
+  pub relative_path: Option<String>,
+  pub icon_location: Option<String>,
+  #[serde(deserialize_with = "number")]
+  pub icon_index: Option<u32>,
+  /// `SHOWNORMAL` (default), `SHOWMAXIMIZED` or `SHOWMINNOACTIVE`.
+  pub show_command: Option<String>,
+  #[serde(deserialize_with = "number")]
+  pub file_attributes: Option<u32>,
+  #[serde(deserialize_with = "number")]
+  pub target_size: Option<u32>,
+  /// Times are ISO 8601 (`2021-02-08T12:41:03Z`), UTC.
+  pub target_creation_time: Option<String>,
+  pub target_access_time: Option<String>,
+  pub target_modification_time: Option<String>,
+  /// `DRIVE_FIXED` (default for local targets), `DRIVE_REMOVABLE`, ...
+  pub drive_type: Option<String>,
+  /// `XXXX-XXXX`
+  pub volume_serial_number: Option<String>,
+  pub volume_label: Option<String>,
+  /// The mapped drive letter of a UNC target (`Z:`).
+  pub device_name: Option<String>,
+  /// Adds a TrackerDataBlock with this NetBIOS name.
+  pub target_hostname: Option<String>,
+  pub file_droid: Option<String>,
+  pub file_droid_birth: Option<String>,
+  pub volume_droid: Option<String>,
+  pub volume_droid_birth: Option<String>,
+}
+
+/// Accept numbers written as numbers or as strings, the normalized output writes every value as a string.
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<u32>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Number {
+    Number(u32),
+    String(String),
+  }
+  match Option::<Number>::deserialize(deserializer)? {
+    Some(Number::Number(n)) => Ok(Some(n)),
+    Some(Number::String(s)) if s.is_empty() => Ok(None),
+    Some(Number::String(s)) => match s.strip_prefix("0x") {
+      Some(hex) => u32::from_str_radix(hex, 16),
+      None => s.parse(),
+    }
+    .map(Some)
+    .map_err(serde::de::Error::custom),
+    None => Ok(None),
+  }
+}
+
+fn non_empty(s: &Option<String>) -> Option<&str> {
+  s.as_deref().filter(|s| !s.is_empty())
+}
+
+fn invalid(message: String) -> Error {
+  Error::new(ErrorKind::InvalidInput, message)
+}
+
+impl LnkSpec {
+  /// Build the LNK file data.
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    if self.target_full_path.is_empty() {
+      return Err(invalid(String::from("target_full_path is required")));
+    }
+    let mut header = [0; HEADER_SIZE];
+    LittleEndian::write_u32(&mut header, HEADER_SIZE as u32);
+    header[4..20].copy_from_slice(&LINK_CLSID);
+    LittleEndian::write_u32(&mut header[0x18..], self.file_attributes.unwrap_or(FILE_ATTRIBUTE_ARCHIVE));
+    for (offset, time) in [
+      (0x1C, &self.target_creation_time),
+      (0x24, &self.target_access_time),
+      (0x2C, &self.target_modification_time),
+    ] {
+      let filetime = match non_empty(time) {
+        Some(time) => to_filetime(time)?,
+        None => 0,
+      };
+      LittleEndian::write_u64(&mut header[offset..], filetime);
+    }
+    LittleEndian::write_u32(&mut header[0x34..], self.target_size.unwrap_or_default());
+    LittleEndian::write_u32(&mut header[0x38..], self.icon_index.unwrap_or_default());
+    let show_command = match non_empty(&self.show_command) {
+      None | Some("SHOWNORMAL") => SW_SHOWNORMAL,
+      Some("SHOWMAXIMIZED") => 3,
+      Some("SHOWMINNOACTIVE") => 7,
+      Some(other) => return Err(invalid(format!("unknown show_command '{}'", other))),
+    };
+    LittleEndian::write_u32(&mut header[0x3C..], show_command);
+
+    let volume = Volume {
+      drive_type: non_empty(&self.drive_type).unwrap_or("DRIVE_FIXED").to_string(),
+      serial_number: non_empty(&self.volume_serial_number).map(String::from),
+      label: non_empty(&self.volume_label).map(String::from),
+    };
+    let mut parts = Parts {
+      header,
+      id_list: None,
+      link_info: Some(link_info(&self.target_full_path, &volume, non_empty(&self.device_name))?),
+      strings: Default::default(),
+      extra_data_blocks: vec![],
+      trailing: vec![],
+    };
+    parts.set_flag(IS_UNICODE, true);
+    let strings = [
+      &self.name_string,
+      &self.relative_path,
+      &self.working_dir,
+      &self.command_line_arguments,
+      &self.icon_location,
+    ];
+    for (index, string) in strings.iter().enumerate() {
+      parts.strings[index] = non_empty(string).map(|s| string_data(s, true)).transpose()?;
+    }
+    if let Some(machine_id) = non_empty(&self.target_hostname) {
+      parts.extra_data_blocks.push(tracker_block(
+        machine_id,
+        [
+          &self.volume_droid,
+          &self.file_droid,
+          &self.volume_droid_birth,
+          &self.file_droid_birth,
+        ],
+      )?);
+    }
+    Ok(parts.into_bytes())
+  }
+}
+
+/// Change the target of a LNK file. The LinkTargetIDList, the relative path and the ExtraData blocks that
+/// describe the old target (environment variables, tracker, known and special folders, property store) are
+/// removed so they do not point Windows back to it. The volume information of the old target is kept for local
+/// targets, the times and size in the header are not changed.
+pub fn set_target(data: &[u8], target: &str) -> Result<Vec<u8>> {
+  let mut parts = Parts::from_bytes(data)?;
+  let volume = match &parts.link_info {
+    Some(link_info) => LinkInfo::from_buffer(link_info)?.volume_id.map(|volume_id| Volume {
+      drive_type: format!("{:?}", volume_id.drive_type),
+      serial_number: Some(volume_id.serial_number),
+      label: volume_id.volume_lable,
+    }),
+    None => None,
+  };
+  let volume = volume.unwrap_or(Volume {
+    drive_type: String::from("DRIVE_FIXED"),
+    serial_number: None,
+    label: None,
+  });
+  parts.id_list = None;
+  parts.link_info = Some(link_info(target, &volume, None)?);
+  parts.strings[RELATIVE_PATH] = None;
+  parts.set_flag(FORCE_NO_LINK_INFO | HAS_EXP_STRING, false);
+  parts
+    .extra_data_blocks
+    .retain(|block| !TARGET_BLOCK_SIGNATURES.contains(&LittleEndian::read_u32(&block[4..])));
+  Ok(parts.into_bytes())
+}
+
+/// Replace the command line arguments of a LNK file, `None` removes them.
+pub fn set_arguments(data: &[u8], arguments: Option<&str>) -> Result<Vec<u8>> {
+  let mut parts = Parts::from_bytes(data)?;
+  let unicode = parts.flags() & IS_UNICODE != 0;
+  parts.strings[ARGUMENTS] = arguments.map(|a| string_data(a, unicode)).transpose()?;
+  Ok(parts.into_bytes())
+}
+
+/// The structures of a LNK file as raw bytes, the presence flags are updated from the fields when written.
+struct Parts {
+  header: [u8; HEADER_SIZE],
+  /// Including the IDListSize field.
+  id_list: Option<Vec<u8>>,
+  link_info: Option<Vec<u8>>,
+  /// Including the CountCharacters field.
+  strings: [Option<Vec<u8>>; 5],
+  /// Every block including the size and signature, without the terminal block.
+  extra_data_blocks: Vec<Vec<u8>>,
+  /// Data found after the terminal block.
+  trailing: Vec<u8>,
+}
+
+impl Parts {
+  fn from_bytes(data: &[u8]) -> Result<Self> {
+    let eof = || Error::new(ErrorKind::UnexpectedEof, "LNK data is truncated");
+    if data.len() < HEADER_SIZE || LittleEndian::read_u32(data) != HEADER_SIZE as u32 || data[4..20] != LINK_CLSID {
+      return Err(Error::new(ErrorKind::InvalidData, "Not a LNK file"));
+    }
+    let mut header = [0; HEADER_SIZE];
+    header.copy_from_slice(&data[..HEADER_SIZE]);
+    let flags = LittleEndian::read_u32(&data[0x14..]);
+    let mut offset = HEADER_SIZE;
+    let mut take = |size: usize| -> Result<Vec<u8>> {
+      let bytes = data.get(offset..offset + size).ok_or_else(eof)?.to_vec();
+      offset += size;
+      Ok(bytes)
+    };
+
+    let mut id_list = None;
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+      let size = LittleEndian::read_u16(data.get(HEADER_SIZE..HEADER_SIZE + 2).ok_or_else(eof)?) as usize;
+      id_list = Some(take(size + 2)?);
+    }
+    let mut link_info = None;
+    if flags & HAS_LINK_INFO != 0 {
+      let start = HEADER_SIZE + id_list.as_ref().map_or(0, Vec::len);
+      let size = LittleEndian::read_u32(data.get(start..start + 4).ok_or_else(eof)?) as usize;
+      link_info = Some(take(size)?);
+    }
+    let mut strings: [Option<Vec<u8>>; 5] = Default::default();
+    let mut position = HEADER_SIZE + id_list.as_ref().map_or(0, Vec::len) + link_info.as_ref().map_or(0, Vec::len);
+    for (index, flag) in STRING_DATA_FLAGS.iter().enumerate() {
+      if flags & flag != 0 {
+        let count = LittleEndian::read_u16(data.get(position..position + 2).ok_or_else(eof)?) as usize;
+        let size = 2 + if flags & IS_UNICODE != 0 { count * 2 } else { count };
+        strings[index] = Some(take(size)?);
+        position += size;
+      }
+    }
+
+    let mut extra_data_blocks = vec![];
+    while position + 4 <= data.len() {
+      let size = LittleEndian::read_u32(&data[position..]) as usize;
+      if size < 8 {
+        position += 4;
+        break;
+      }
+      extra_data_blocks.push(data.get(position..position + size).ok_or_else(eof)?.to_vec());
+      position += size;
+    }
+    Ok(Self {
+      header,
+      id_list,
+      link_info,
+      strings,
+      extra_data_blocks,
+      trailing: data.get(position..).unwrap_or_default().to_vec(),
+    })
+  }
+
+  fn flags(&self) -> u32 {
+    LittleEndian::read_u32(&self.header[0x14..])
+  }
+
+  fn set_flag(&mut self, flag: u32, value: bool) {
+    let flags = if value {
+      self.flags() | flag
+    } else {
+      self.flags() & !flag
+    };
+    LittleEndian::write_u32(&mut self.header[0x14..], flags);
+  }
+
+  fn into_bytes(mut self) -> Vec<u8> {
+    self.set_flag(HAS_LINK_TARGET_ID_LIST, self.id_list.is_some());
+    self.set_flag(HAS_LINK_INFO, self.link_info.is_some());
+    for (index, flag) in STRING_DATA_FLAGS.iter().enumerate() {
+      self.set_flag(*flag, self.strings[index].is_some());
+    }
+    let mut data = self.header.to_vec();
+    data.extend(self.id_list.into_iter().flatten());
+    data.extend(self.link_info.into_iter().flatten());
+    data.extend(self.strings.into_iter().flatten().flatten());
+    data.extend(self.extra_data_blocks.into_iter().flatten());
+    // TerminalBlock
+    data.extend([0; 4]);
+    data.extend(self.trailing);
+    data
+  }
+}
+
+struct Volume {
+  drive_type: String,
+  serial_number: Option<String>,
+  label: Option<String>,
+}
+
+fn drive_type(name: &str) -> Result<u32> {
+  let types = [
+    "DRIVE_UNKNOWN",
+    "DRIVE_NO_ROOT_DIR",
+    "DRIVE_REMOVABLE",
+    "DRIVE_FIXED",
+    "DRIVE_REMOTE",
+    "DRIVE_CDROM",
+    "DRIVE_RAMDISK",
+  ];
+  types
+    .iter()
+    .position(|t| t.eq_ignore_ascii_case(name))
+    .map(|p| p as u32)
+    .ok_or_else(|| invalid(format!("unknown drive_type '{}'", name)))
+}
+
+/// Build a LinkInfo structure (with the optional Unicode fields) for a local or UNC target.
+fn link_info(target: &str, volume: &Volume, device_name: Option<&str>) -> Result<Vec<u8>> {
+  const LINK_INFO_HEADER_SIZE: u32 = 0x24;
+  let mut body = vec![];
+  let mut offsets = [0u32; 6];
+  let mut link_info_flags = 0;
+
+  let (local_base_path, common_path_suffix) = match target.strip_prefix(r"\\") {
+    Some(unc) => {
+      // \\server\share is the net name, the rest is the common path suffix.
+      let mut components = unc.splitn(3, '\\');
+      let server = components.next().unwrap_or_default();
+      let share = components.next().unwrap_or_default();
+      if server.is_empty() || share.is_empty() {
+        return Err(invalid(format!("'{}' is not a valid UNC path", target)));
+      }
+      let net_name = format!(r"\\{}\{}", server, share);
+      link_info_flags |= 2;
+      offsets[2] = LINK_INFO_HEADER_SIZE + body.len() as u32;
+      body.extend(common_network_relative_link(&net_name, device_name)?);
+      (None, components.next().unwrap_or_default().to_string())
+    },
+    None => {
+      link_info_flags |= 1;
+      offsets[0] = LINK_INFO_HEADER_SIZE;
+      body.extend(volume_id(volume)?);
+      (Some(target.to_string()), String::new())
+    },
+  };
+  if let Some(local_base_path) = &local_base_path {
+    offsets[1] = LINK_INFO_HEADER_SIZE + body.len() as u32;
+    body.extend(ansi(local_base_path));
+  }
+  offsets[3] = LINK_INFO_HEADER_SIZE + body.len() as u32;
+  body.extend(ansi(&common_path_suffix));
+  if let Some(local_base_path) = &local_base_path {
+    offsets[4] = LINK_INFO_HEADER_SIZE + body.len() as u32;
+    body.extend(utf16(local_base_path));
+  }
+  offsets[5] = LINK_INFO_HEADER_SIZE + body.len() as u32;
+  body.extend(utf16(&common_path_suffix));
+
+  let mut data = vec![];
+  data.write_u32::<LittleEndian>(LINK_INFO_HEADER_SIZE + body.len() as u32)?;
+  data.write_u32::<LittleEndian>(LINK_INFO_HEADER_SIZE)?;
+  data.write_u32::<LittleEndian>(link_info_flags)?;
+  for offset in offsets {
+    data.write_u32::<LittleEndian>(offset)?;
+  }
+  data.extend(body);
+  Ok(data)
+}
+
+fn volume_id(volume: &Volume) -> Result<Vec<u8>> {
+  let serial_number = match &volume.serial_number {
+    Some(serial) => u32::from_str_radix(&serial.replace('-', ""), 16)
+      .map_err(|_| invalid(format!("'{}' is not a valid volume_serial_number", serial)))?,
+    None => 0,
+  };
+  let label = ansi(volume.label.as_deref().unwrap_or_default());
+  let mut data = vec![];
+  data.write_u32::<LittleEndian>(0x10 + label.len() as u32)?;
+  data.write_u32::<LittleEndian>(drive_type(&volume.drive_type)?)?;
+  data.write_u32::<LittleEndian>(serial_number)?;
+  data.write_u32::<LittleEndian>(0x10)?;
+  data.extend(label);
+  Ok(data)
+}
+
+fn common_network_relative_link(net_name: &str, device_name: Option<&str>) -> Result<Vec<u8>> {
+  let net_name = ansi(net_name);
+  let device_name = device_name.map(ansi);
+  let mut data = vec![];
+  let size = 0x14 + net_name.len() + device_name.as_ref().map_or(0, Vec::len);
+  data.write_u32::<LittleEndian>(size as u32)?;
+  // ValidNetType, and ValidDevice when a device name is given.
+  data.write_u32::<LittleEndian>(2 | device_name.is_some() as u32)?;
+  data.write_u32::<LittleEndian>(0x14)?;
+  data.write_u32::<LittleEndian>(match device_name {
+    Some(_) => 0x14 + net_name.len() as u32,
+    None => 0,
+  })?;
+  data.write_u32::<LittleEndian>(WNNC_NET_LANMAN)?;
+  data.extend(net_name);
+  data.extend(device_name.into_iter().flatten());
+  Ok(data)
+}
+
+fn tracker_block(machine_id: &str, droids: [&Option<String>; 4]) -> Result<Vec<u8>> {
+  if machine_id.len() > 15 || !machine_id.is_ascii() {
+    return Err(invalid(format!("'{}' is not a valid NetBIOS name", machine_id)));
+  }
+  let mut data = vec![];
+  data.write_u32::<LittleEndian>(0x60)?;
+  data.write_u32::<LittleEndian>(TRACKER_SIGNATURE)?;
+  data.write_u32::<LittleEndian>(0x58)?;
+  data.write_u32::<LittleEndian>(0)?;
+  let mut machine_id_bytes = [0; 16];
+  machine_id_bytes[..machine_id.len()].copy_from_slice(machine_id.as_bytes());
+  data.extend(machine_id_bytes);
+  for droid in droids {
+    data.extend(guid(non_empty(droid).unwrap_or("00000000-0000-0000-0000-000000000000"))?);
+  }
+  Ok(data)
+}
+
+/// GUID string (`BD4FAD74-6A0A-11EB-8ECF-5076AFA95947`) to its binary form.
+fn guid(guid: &str) -> Result<[u8; 16]> {
+  let error = || invalid(format!("'{}' is not a valid GUID", guid));
+  let hex: String = guid.trim_matches(['{', '}']).replace('-', "");
+  if hex.len() != 32 {
+    return Err(error());
+  }
+  let mut bytes = [0; 16];
+  for (i, byte) in bytes.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2).ok_or_else(error)?, 16).map_err(|_| error())?;
+  }
+  // The first three groups are stored little endian.
+  bytes[..4].reverse();
+  bytes[4..6].reverse();
+  bytes[6..8].reverse();
+  Ok(bytes)
+}
+
+fn to_filetime(time: &str) -> Result<u64> {
+  let error = || invalid(format!("'{}' is not a valid time", time));
+  let time = match DateTime::parse_from_rfc3339(time) {
+    Ok(time) => time.naive_utc(),
+    Err(_) => NaiveDateTime::parse_from_str(time.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S")
+      .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S"))
+      .or_else(|_| NaiveDate::parse_from_str(time, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+      .map_err(|_| error())?,
+  };
+  let time = time.and_utc();
+  let seconds = time.timestamp() + FILETIME_UNIX_EPOCH;
+  if seconds <= 0 {
+    return Ok(0);
+  }
+  Ok(seconds as u64 * 10_000_000 + time.timestamp_subsec_nanos() as u64 / 100)
+}
+
+/// StringData structure, `CountCharacters` followed by the characters without a terminator.
+fn string_data(s: &str, unicode: bool) -> Result<Vec<u8>> {
+  let (count, characters) = if unicode {
+    let characters: Vec<u16> = s.encode_utf16().collect();
+    let bytes = characters.iter().flat_map(|c| c.to_le_bytes()).collect::<Vec<u8>>();
+    (characters.len(), bytes)
+  } else {
+    let mut bytes = ansi(s);
+    bytes.pop();
+    (bytes.len(), bytes)
+  };
+  let count = u16::try_from(count).map_err(|_| invalid(String::from("string is longer than 65535 characters")))?;
+  let mut data = count.to_le_bytes().to_vec();
+  data.extend(characters);
+  Ok(data)
+}
+
+/// NUL terminated string in the system code page, only ASCII is written so the code page does not matter.
+fn ansi(s: &str) -> Vec<u8> {
+  s.chars()
+    .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+    .chain([0])
+    .collect()
+}
+
+/// NUL terminated UTF-16 string.
+fn utf16(s: &str) -> Vec<u8> {
+  s.encode_utf16().chain([0]).flat_map(|c| c.to_le_bytes()).collect()
+}
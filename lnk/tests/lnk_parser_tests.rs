@@ -1,7 +1,100 @@
 #[cfg(test)]
 mod test {
   use glob::glob;
-  use lnk::Lnk;
+  use lnk::{
+    filter::Filter,
+    jump_list::JumpList,
+    triage::{self, ArtifactKind},
+    Lnk,
+  };
+  use std::{collections::HashMap, fs, path::PathBuf, process::Command};
+  use winparsingtools::traits::Normalize;
+
+  const SAMPLE: &str = "samples/WIN10/1607_14393/windows_generated.lnk";
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lnk_parser_tests_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  /// Build a version 3 compound file with a single stream stored in the mini stream.
+  fn compound_file(stream_name: &str, stream: &[u8]) -> Vec<u8> {
+    fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+      buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+    fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+      buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    fn dir_entry(buf: &mut [u8], name: &str, object_type: u8, start: u32, size: u32) {
+      let name: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+      for (i, c) in name.iter().enumerate() {
+        put_u16(buf, i * 2, *c);
+      }
+      put_u16(buf, 64, (name.len() * 2) as u16);
+      buf[66] = object_type;
+      put_u32(buf, 68, 0xFFFFFFFF);
+      put_u32(buf, 72, 0xFFFFFFFF);
+      put_u32(buf, 76, if object_type == 5 { 1 } else { 0xFFFFFFFF });
+      put_u32(buf, 116, start);
+      put_u32(buf, 120, size);
+    }
+
+    let mini_sectors = stream.len().div_ceil(64);
+    let mini_stream_sectors = (mini_sectors * 64).div_ceil(512);
+    let mut file = vec![0; 512 * (4 + mini_stream_sectors)];
+    // header
+    file[..8].copy_from_slice(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]);
+    put_u16(&mut file, 0x1A, 3);
+    put_u16(&mut file, 0x1C, 0xFFFE);
+    put_u16(&mut file, 0x1E, 9);
+    put_u16(&mut file, 0x20, 6);
+    put_u32(&mut file, 0x2C, 1);
+    put_u32(&mut file, 0x30, 1);
+    put_u32(&mut file, 0x38, 4096);
+    put_u32(&mut file, 0x3C, 2);
+    put_u32(&mut file, 0x40, 1);
+    put_u32(&mut file, 0x44, 0xFFFFFFFE);
+    for i in 0..109 {
+      put_u32(&mut file, 0x4C + i * 4, if i == 0 { 0 } else { 0xFFFFFFFF });
+    }
+    // sector 0: FAT
+    let fat = 512;
+    for i in 0..128 {
+      put_u32(&mut file, fat + i * 4, 0xFFFFFFFF);
+    }
+    put_u32(&mut file, fat, 0xFFFFFFFD);
+    put_u32(&mut file, fat + 4, 0xFFFFFFFE);
+    put_u32(&mut file, fat + 8, 0xFFFFFFFE);
+    for i in 0..mini_stream_sectors {
+      let next = if i + 1 == mini_stream_sectors {
+        0xFFFFFFFE
+      } else {
+        (4 + i) as u32
+      };
+      put_u32(&mut file, fat + (3 + i) * 4, next);
+    }
+    // sector 1: directory
+    dir_entry(&mut file[1024..1152], "Root Entry", 5, 3, (mini_sectors * 64) as u32);
+    dir_entry(&mut file[1152..1280], stream_name, 2, 0, stream.len() as u32);
+    // sector 2: mini FAT
+    let mini_fat = 1536;
+    for i in 0..128 {
+      put_u32(&mut file, mini_fat + i * 4, 0xFFFFFFFF);
+    }
+    for i in 0..mini_sectors {
+      let next = if i + 1 == mini_sectors {
+        0xFFFFFFFE
+      } else {
+        (i + 1) as u32
+      };
+      put_u32(&mut file, mini_fat + i * 4, next);
+    }
+    // sector 3..: mini stream
+    file[2048..2048 + stream.len()].copy_from_slice(stream);
+    file
+  }
 
   #[test]
   fn test_win7() {
@@ -49,4 +142,1957 @@ mod test {
       }
     }
   }
+
+  #[test]
+  fn test_triage_root() {
+    let root = temp_dir("root");
+    let recent = root.join("USERS/u0041/appdata/Roaming/Microsoft/Windows/Recent");
+    let start_menu = root.join("Users/u0042/AppData/Roaming/Microsoft/Windows/Start Menu/Programs/Tools");
+    let system = root.join("Windows/System32/config/systemprofile/Desktop");
+    for dir in [&recent, &start_menu, &system] {
+      fs::create_dir_all(dir).unwrap();
+    }
+    fs::copy(SAMPLE, recent.join("a.LNK")).unwrap();
+    fs::copy(SAMPLE, start_menu.join("b.lnk")).unwrap();
+    fs::copy(SAMPLE, system.join("c.lnk")).unwrap();
+    fs::write(recent.join("not_a_link.txt"), b"").unwrap();
+    // A link back to a parent directory in a recursive location is not followed.
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("../..", start_menu.join("loop")).unwrap();
+
+    let artifacts = triage::collect(&root);
+    let found: Vec<(String, Option<String>)> = artifacts
+      .iter()
+      .map(|a| (a.path.file_name().unwrap().to_string_lossy().to_string(), a.user.clone()))
+      .collect();
+    assert_eq!(
+      found,
+      vec![
+        ("a.LNK".to_string(), Some("u0041".to_string())),
+        ("b.lnk".to_string(), Some("u0042".to_string())),
+        ("c.lnk".to_string(), Some("systemprofile".to_string())),
+      ]
+    );
+    assert!(artifacts.iter().all(|a| a.kind == ArtifactKind::Lnk));
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_jump_lists() {
+    let lnk_data = fs::read(SAMPLE).unwrap();
+    let expected = Lnk::from_buffer(&lnk_data).unwrap().target_full_path;
+
+    let mut custom = vec![0xAB; 24];
+    custom.extend_from_slice(&lnk_data);
+    custom.extend_from_slice(&lnk_data);
+    let jump_list = JumpList::from_buffer(&custom).unwrap();
+    assert_eq!(jump_list.entries.len(), 2);
+    assert_eq!(jump_list.entries[0].entry, "0x18");
+    assert_eq!(jump_list.entries[1].lnk.target_full_path, expected);
+
+    let automatic = compound_file("1", &lnk_data);
+    let jump_list = JumpList::from_buffer(&automatic).unwrap();
+    assert_eq!(jump_list.entries.len(), 1);
+    assert_eq!(jump_list.entries[0].entry, "1");
+    assert_eq!(jump_list.entries[0].lnk.target_full_path, expected);
+  }
+
+  #[test]
+  fn test_threads_keep_order() {
+    let run = |threads: &str, format: &str| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args([
+          "-p",
+          "samples/*/*/*.lnk",
+          "--threads",
+          threads,
+          "--output-format",
+          format,
+        ])
+        .output()
+        .unwrap();
+      assert!(output.status.success());
+      String::from_utf8(output.stdout).unwrap()
+    };
+    for format in ["csv", "json"] {
+      let sequential = run("1", format);
+      assert_eq!(sequential, run("4", format));
+      assert_eq!(sequential, run("0", format));
+    }
+    let json: serde_json::Value = serde_json::from_str(&run("3", "json")).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 20);
+
+    // A file the parser panics on (a CommonNetworkRelativeLink smaller than its size field) fails on its own, the
+    // workers keep parsing the other files.
+    let dir = temp_dir("threads_panic");
+    let mut data = fs::read("samples/WIN10/1607_14393/network_share.lnk").unwrap();
+    data[0x68..0x6C].copy_from_slice(&[0; 4]);
+    fs::write(dir.join("a.lnk"), data).unwrap();
+    for i in 0..8 {
+      fs::copy(SAMPLE, dir.join(format!("b{}.lnk", i))).unwrap();
+    }
+    for threads in ["1", "4"] {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", &dir.join("*.lnk").to_string_lossy(), "--threads", threads])
+        .output()
+        .unwrap();
+      assert_eq!(output.status.code(), Some(4));
+      assert!(String::from_utf8_lossy(&output.stderr).contains("the parser panicked"));
+      assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 9);
+    }
+
+    // A shell item time the writer panics on when it formats it (0xFFFF is not a valid DOS time) is not written, the
+    // other files are.
+    let mut data = fs::read(SAMPLE).unwrap();
+    data[166..168].copy_from_slice(&[0xFF; 2]);
+    fs::write(dir.join("a.lnk"), data).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", &dir.join("*.lnk").to_string_lossy(), "--output-format", "jsonl"])
+      .output()
+      .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the record could not be serialized"));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 8);
+    assert!(stdout
+      .lines()
+      .all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()));
+  }
+
+  // Writing to /dev/full fails with ENOSPC.
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn test_write_error_stops_the_run() {
+    let dir = temp_dir("write_error");
+    for i in 0..40 {
+      fs::copy(SAMPLE, dir.join(format!("a{:02}.lnk", i))).unwrap();
+    }
+    fs::write(dir.join("z.lnk"), b"not a shortcut").unwrap();
+    for threads in ["1", "4"] {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", &dir.join("*.lnk").to_string_lossy(), "--output-format", "jsonl"])
+        .args(["--threads", threads, "-o", "/dev/full"])
+        .output()
+        .unwrap();
+      assert_eq!(output.status.code(), Some(5));
+      // The run ended before z.lnk was parsed.
+      assert!(!String::from_utf8_lossy(&output.stderr).contains("z.lnk"));
+    }
+  }
+
+  // `"` and new lines are not allowed in Windows file names.
+  #[cfg(unix)]
+  #[test]
+  fn test_csv_escaping_and_columns() {
+    let dir = temp_dir("csv");
+    let tricky = dir.join("quote\"comma,new\nline.lnk");
+    fs::copy(SAMPLE, &tricky).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", &format!("{}/*.lnk", dir.display())])
+      .args([
+        "--columns",
+        "user,lnk_full_path,target_size",
+        "--delimiter",
+        ";",
+        "--excel",
+      ])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"\xEF\xBB\xBF"));
+
+    let mut reader = csv::ReaderBuilder::new().delimiter(b';').from_reader(&output.stdout[3..]);
+    assert_eq!(reader.headers().unwrap(), vec!["user", "lnk_full_path", "target_size"]);
+    let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][1], tricky.to_str().unwrap());
+    assert_eq!(&rows[0][2], "4");
+
+    // The default columns are those of the first versions, --all-fields writes every normalized field.
+    for (args, columns) in [
+      (vec![], lnk::DEFAULT_NORMALIZED_FIELDS),
+      (vec!["--all-fields"], lnk::NORMALIZED_FIELDS),
+    ] {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", SAMPLE])
+        .args(args)
+        .output()
+        .unwrap();
+      let mut reader = csv::Reader::from_reader(output.stdout.as_slice());
+      assert_eq!(reader.headers().unwrap(), columns);
+    }
+    assert_eq!(lnk::DEFAULT_NORMALIZED_FIELDS.len(), 11);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_sqlite_upsert() {
+    let dir = temp_dir("sqlite");
+    let database = dir.join("lnk.db");
+    for _ in 0..2 {
+      let status = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", "samples/WIN10/*/*.lnk", "--output-format", "sqlite", "-o"])
+        .arg(&database)
+        .status()
+        .unwrap();
+      assert!(status.success());
+    }
+
+    let connection = rusqlite::Connection::open(&database).unwrap();
+    let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+    assert_eq!(count("SELECT COUNT(*) FROM lnk_records"), 6);
+    assert_eq!(count("SELECT COUNT(DISTINCT sha256) FROM lnk_records"), 6);
+    assert_eq!(
+      count("SELECT COUNT(*) FROM extra_data_blocks WHERE block_type = 'Tracker'"),
+      count("SELECT COUNT(*) FROM lnk_records WHERE file_droid != ''")
+    );
+    let (serial, machine_id, items): (String, String, i64) = connection
+      .query_row(
+        "SELECT volume_serial_number, target_hostname, (SELECT COUNT(*) FROM shell_items WHERE record_id = id)
+         FROM lnk_records WHERE lnk_full_path LIKE '%windows_generated.lnk'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+      )
+      .unwrap();
+    assert_eq!((serial.as_str(), machine_id.as_str(), items), ("E02E-8A93", "win10", 2));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_ecs_bulk_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", SAMPLE, "--output-format", "ecs", "--ecs-index", "evidence"])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+    // Three target times and three LNK file times.
+    assert_eq!(lines.len(), 12);
+
+    let mut ids = vec![];
+    for pair in lines.chunks(2) {
+      let (action, event) = (&pair[0]["index"], &pair[1]);
+      assert_eq!(action["_index"], "evidence");
+      ids.push(action["_id"].as_str().unwrap().to_string());
+      assert!(event["@timestamp"].is_string());
+      assert_eq!(event["host"]["name"], "win10");
+      assert_eq!(event["file"]["target_path"], r"C:\Users\u0041\Desktop\test\test.txt");
+      assert_eq!(event["process"]["working_directory"], r"C:\Users\u0041\Desktop\test");
+      assert_eq!(event["lnk"]["volume_serial_number"], "E02E-8A93");
+    }
+    assert_eq!(lines[1]["@timestamp"], "2021-02-08T12:41:03Z");
+    assert_eq!(lines[1]["event"]["action"], "lnk-target-created");
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 6);
+
+    // Copies of the same shortcut at different paths are different documents, parsing them again gives the same IDs.
+    let dir = temp_dir("ecs_ids");
+    fs::copy(SAMPLE, dir.join("a.lnk")).unwrap();
+    fs::copy(SAMPLE, dir.join("b.lnk")).unwrap();
+    let ecs_ids = || {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", &dir.join("*.lnk").to_string_lossy(), "--output-format", "ecs"])
+        .output()
+        .unwrap();
+      let mut ids: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .step_by(2)
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["index"]["_id"].to_string())
+        .collect();
+      ids.sort();
+      ids
+    };
+    let first = ecs_ids();
+    assert_eq!(first.len(), 12);
+    assert!(first.windows(2).all(|pair| pair[0] != pair[1]));
+    assert_eq!(ecs_ids(), first);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_filter_expressions() {
+    let mut fields = HashMap::new();
+    for (name, value) in [
+      ("drive_type", "DRIVE_REMOVABLE"),
+      ("target_hostname", "ws02"),
+      ("target_full_path", r"E:\Tools\run.exe"),
+      ("target_size", "630"),
+      ("target_creation_time", "2021-02-08T12:41:03Z"),
+      ("user", "Infinity"),
+    ] {
+      fields.insert(name.to_string(), value.to_string());
+    }
+    let matches = |expression: &str| Filter::parse(expression).unwrap().matches(&fields);
+    assert!(matches(r#"drive_type == "DRIVE_REMOVABLE" && target_hostname != "WS01""#));
+    assert!(matches(r#"target_full_path =~ '(?i)^e:\\tools\\' && target_full_path contains '\Tools\'"#));
+    assert!(matches(r#"target_creation_time in "2021-02-08".."2021-02-09""#));
+    assert!(matches(r#"target_creation_time >= "2021-02-08 12:41:03" && target_size > 99"#));
+    assert!(matches(r#"!(drive_type == "DRIVE_FIXED" || net_name != "")"#));
+    assert!(!matches(r#"target_creation_time < "2021-01-01" || volume_label > "A""#));
+    assert!(!matches("target_size <= 99 || target_size == 630.5"));
+    // Text that parses as a special float is compared as text.
+    assert!(!matches(r#"user == "inf""#));
+    assert!(matches(r#"user > "Inf" && user < "NaN""#));
+
+    for invalid in [
+      "machine == \"a\"",
+      "drive_type ==",
+      "drive_type == \"a",
+      "(drive_type == a",
+      "user =~ \"(\"",
+    ] {
+      assert!(Filter::parse(invalid).is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn test_filter_option() {
+    let run = |filter: &str| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args([
+          "-p",
+          "samples/**/*.lnk",
+          "--output-format",
+          "jsonl",
+          "--normalize",
+          "--filter",
+          filter,
+        ])
+        .output()
+        .unwrap();
+      assert!(output.status.success());
+      String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>()
+    };
+    let records = run(r#"target_hostname == "win7" && target_full_path =~ "\\test\.txt$""#);
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|r| r["target_hostname"] == "win7"));
+    assert!(run(r#"drive_type == "DRIVE_REMOVABLE""#).is_empty());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", SAMPLE, "--filter", "no_such_field == 1"])
+      .output()
+      .unwrap()
+      .status;
+    assert!(!status.success());
+  }
+
+  #[test]
+  fn test_archives() {
+    use std::io::{Read, Write};
+
+    let dir = temp_dir("archives");
+    let lnk = fs::read(SAMPLE).unwrap();
+    let recent = "C/Users/bob/AppData/Roaming/Microsoft/Windows/Recent";
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(dir.join("WS01.zip")).unwrap());
+    let options = zip::write::SimpleFileOptions::default()
+      .last_modified_time(zip::DateTime::from_date_and_time(2022, 3, 4, 5, 6, 8).unwrap());
+    for name in [format!("{}/a.lnk", recent), String::from("C/Users/bob/Desktop/b.lnk")] {
+      zip.start_file(name, options).unwrap();
+      zip.write_all(&lnk).unwrap();
+    }
+    zip.start_file("C/Users/bob/Desktop/notes.txt", options).unwrap();
+    zip.write_all(b"not a shortcut").unwrap();
+    zip.finish().unwrap();
+
+    let gz = flate2::write::GzEncoder::new(fs::File::create(dir.join("WS02.tar.gz")).unwrap(), Default::default());
+    let mut tar = tar::Builder::new(gz);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(lnk.len() as u64);
+    header.set_mtime(1_600_000_000);
+    header.set_mode(0o644);
+    tar
+      .append_data(&mut header, format!("./{}/c.lnk", recent), lnk.as_slice())
+      .unwrap();
+    tar.into_inner().unwrap().finish().unwrap();
+
+    let run = |path: String| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", &path, "--output-format", "jsonl", "--normalize"])
+        .output()
+        .unwrap();
+      assert!(output.status.success());
+      String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>()
+    };
+
+    // The member glob is case-insensitive and `*` does not cross directories.
+    let records = run(format!("{}/WS01.zip/c/users/*/appdata/roaming/microsoft/windows/recent/*.LNK", dir.display()));
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["lnk_full_path"], format!("{}/WS01.zip/{}/a.lnk", dir.display(), recent));
+    assert_eq!(records[0]["lnk_modification_time"], "2022-03-04T05:06:08Z");
+    // The archive does not store these times, they are not made up from the modification time.
+    assert_eq!(records[0]["lnk_access_time"], "");
+    assert_eq!(records[0]["lnk_creation_time"], "");
+    assert_eq!(records[0]["user"], "bob");
+    assert_eq!(records[0]["target_hostname"], "win10");
+    let status = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", &format!("{}/WS01.zip/C/*/*.lnk", dir.display())])
+      .status()
+      .unwrap();
+    assert_eq!(status.code(), Some(3));
+
+    // Without a member pattern every LNK file and jump list in the archive is parsed.
+    let records = run(format!("{}/*.tar.gz", dir.display()));
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["lnk_full_path"], format!("{}/WS02.tar.gz/{}/c.lnk", dir.display(), recent));
+    assert_eq!(records[0]["lnk_modification_time"], "2020-09-13T12:26:40Z");
+    assert_eq!(records[0]["lnk_access_time"], "");
+    assert_eq!(run(format!("{}/WS01.zip", dir.display())).len(), 2);
+
+    // A member larger than the limit is skipped with a warning, the other members are read.
+    let mut tar = tar::Builder::new(fs::File::create(dir.join("WS03.tar")).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(lnk::archive::MAX_MEMBER_SIZE + 1);
+    header.set_mode(0o644);
+    let big = std::io::repeat(0).take(lnk::archive::MAX_MEMBER_SIZE + 1);
+    tar.append_data(&mut header, "big.lnk", big).unwrap();
+    header.set_size(lnk.len() as u64);
+    tar.append_data(&mut header, "c.lnk", lnk.as_slice()).unwrap();
+    tar.finish().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "-p",
+        &dir.join("WS03.tar").to_string_lossy(),
+        "--output-format",
+        "jsonl",
+      ])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("big.lnk' is larger than"));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_create_edit_diff() {
+    let dir = temp_dir("create");
+    let lnk_parser = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_lnk_parser")).args(args).output().unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+    fs::write(
+      dir.join("spec.toml"),
+      "target_full_path = 'C:\\Tools\\run.exe'\ncommand_line_arguments = '-x'\ntarget_hostname = 'ws01'\n",
+    )
+    .unwrap();
+    let output = lnk_parser(&["create", &path("spec.toml"), "-o", &path("a.lnk")]);
+    assert!(output.status.success());
+    let normalized = Lnk::from_path(&path("a.lnk")).unwrap().normalize();
+    assert_eq!(normalized["target_full_path"], "C:\\Tools\\run.exe");
+    assert_eq!(normalized["command_line_arguments"], "-x");
+    assert_eq!(normalized["target_hostname"], "ws01");
+
+    let output = lnk_parser(&[
+      "edit",
+      &path("a.lnk"),
+      "--target",
+      "D:\\x.exe",
+      "--remove-arguments",
+      "-o",
+      &path("b.lnk"),
+    ]);
+    assert!(output.status.success());
+    let output = lnk_parser(&["diff", &path("a.lnk"), &path("b.lnk")]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("target_full_path: \"C:\\\\Tools\\\\run.exe\" -> \"D:\\\\x.exe\""));
+    assert!(stdout.contains("command_line_arguments: \"-x\" -> \"\""));
+    assert!(!stdout.contains("lnk_full_path"));
+    assert_eq!(lnk_parser(&["diff", &path("a.lnk"), &path("a.lnk")]).status.code(), Some(0));
+
+    // Invalid specifications and missing edits are usage errors.
+    fs::write(dir.join("bad.json"), "{\"working_dir\": \"C:\\\\\"}").unwrap();
+    assert_eq!(lnk_parser(&["create", &path("bad.json"), "-o", &path("c.lnk")]).status.code(), Some(2));
+    assert_eq!(lnk_parser(&["edit", &path("a.lnk"), "-o", &path("c.lnk")]).status.code(), Some(2));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_carve_validate() {
+    let dir = temp_dir("carve");
+    let lnk_parser = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_lnk_parser")).args(args).output().unwrap();
+    let mut image = vec![0u8; 4096];
+    image.extend(fs::read(SAMPLE).unwrap());
+    image.extend(vec![0u8; 100]);
+    fs::write(dir.join("image.bin"), &image).unwrap();
+    let image_path = dir.join("image.bin").to_string_lossy().to_string();
+    let carved_dir = dir.join("carved").to_string_lossy().to_string();
+
+    let output = lnk_parser(&[
+      "carve",
+      &image_path,
+      "--output-dir",
+      &carved_dir,
+      "--output-format",
+      "jsonl",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(fs::read(dir.join("carved/0x1000.lnk")).unwrap(), fs::read(SAMPLE).unwrap());
+
+    let output = lnk_parser(&["validate", &format!("{}/carved/*.lnk", dir.display())]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("OK "));
+    fs::write(dir.join("notes.txt"), "not a shortcut").unwrap();
+    let notes_path = dir.join("notes.txt").to_string_lossy().to_string();
+    assert_eq!(lnk_parser(&["validate", &notes_path]).status.code(), Some(4));
+    // A directory can not be read, it is reported and the remaining files are still checked.
+    let carved = dir.join("carved").to_string_lossy().to_string();
+    let output = lnk_parser(&["validate", &carved, &format!("{}/*.lnk", carved)]);
+    assert_eq!(output.status.code(), Some(5));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("FAIL ") && stdout.contains("\nOK "));
+    assert_eq!(lnk_parser(&["-p", &carved]).status.code(), Some(5));
+    assert_eq!(lnk_parser(&["validate", &format!("{}/*.none", dir.display())]).status.code(), Some(3));
+    assert_eq!(lnk_parser(&["carve", SAMPLE, "--unknown"]).status.code(), Some(2));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn test_watch() {
+    use std::{
+      io::{BufRead, BufReader, Write},
+      process::Stdio,
+      thread,
+      time::{Duration, Instant},
+    };
+
+    let dir = temp_dir("watch");
+    let watched = dir.join("profiles");
+    fs::create_dir_all(&watched).unwrap();
+    let output = dir.join("out.jsonl");
+    fs::write(&output, "{}\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "--watch",
+        &watched.to_string_lossy(),
+        "--debounce",
+        "200",
+        "--normalize",
+        "-o",
+      ])
+      .arg(&output)
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    assert!(line.starts_with("Watching"));
+
+    // A file written in two parts is parsed once, after the writes stopped.
+    let lnk = fs::read(SAMPLE).unwrap();
+    let mut file = fs::File::create(watched.join("a.lnk")).unwrap();
+    file.write_all(&lnk[..100]).unwrap();
+    file.flush().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    file.write_all(&lnk[100..]).unwrap();
+    drop(file);
+    fs::write(watched.join("notes.txt"), "not a shortcut").unwrap();
+    // A shortcut the parser panics on is reported, the watcher keeps going.
+    let mut panicking = fs::read("samples/WIN10/1607_14393/network_share.lnk").unwrap();
+    panicking[0x68..0x6C].copy_from_slice(&[0; 4]);
+    fs::write(watched.join("c.lnk"), panicking).unwrap();
+    // The content of a new directory is picked up even if it was written before the directory was watched.
+    let recent = watched.join("Users/bob/AppData/Roaming/Microsoft/Windows/Recent");
+    fs::create_dir_all(&recent).unwrap();
+    fs::write(recent.join("b.lnk"), &lnk).unwrap();
+
+    let lines = |path: &PathBuf| fs::read_to_string(path).unwrap().lines().map(String::from).collect::<Vec<_>>();
+    let start = Instant::now();
+    while lines(&output).len() < 3 && start.elapsed() < Duration::from_secs(10) {
+      thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(Duration::from_millis(500));
+    let lines = lines(&output);
+    assert_eq!(lines.len(), 3);
+    // The output is appended to.
+    assert_eq!(lines[0], "{}");
+    let records: Vec<serde_json::Value> = lines[1..].iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert!(records
+      .iter()
+      .all(|r| r["target_full_path"] == "C:\\Users\\u0041\\Desktop\\test\\test.txt"));
+    assert!(records.iter().any(|r| r["user"] == "bob"));
+
+    // The watcher stops when the watched directory is removed.
+    fs::remove_dir_all(&watched).unwrap();
+    assert!(child.wait().unwrap().success());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_validate() {
+    let sample = fs::read(SAMPLE).unwrap();
+    let link_info = 0x4C + 2 + u16::from_le_bytes([sample[0x4C], sample[0x4D]]) as usize;
+    let deviations = |patch: &dyn Fn(&mut Vec<u8>)| {
+      let mut data = sample.clone();
+      patch(&mut data);
+      lnk::validate(&data)
+        .iter()
+        .map(|d| (d.offset, d.field.clone()))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(deviations(&|d| d[0] = 0x50), vec![(0, String::from("HeaderSize"))]);
+    assert_eq!(deviations(&|d| d[0x13] = 0), vec![(4, String::from("LinkCLSID"))]);
+    assert_eq!(deviations(&|d| d[0x44] = 1), vec![(0x44, String::from("Reserved2"))]);
+    assert_eq!(deviations(&|d| d[0x3C] = 2), vec![(0x3C, String::from("ShowCommand"))]);
+    // LocalBasePathOffset pointing past the LinkInfo.
+    assert_eq!(
+      deviations(&|d| d[link_info + 0x10..link_info + 0x14].copy_from_slice(&0x1000u32.to_le_bytes())),
+      vec![(link_info + 0x10, String::from("LinkInfo.LocalBasePathOffset"))]
+    );
+    // HasExpString without an EnvironmentVariableDataBlock.
+    assert_eq!(deviations(&|d| d[0x15] |= 0x2), vec![(0x14, String::from("LinkFlags"))]);
+    // Shrinking the TrackerDataBlock leaves bytes that are not a valid block after it.
+    let tracker = sample.windows(4).position(|w| w == [0x03, 0x00, 0x00, 0xA0]).unwrap() - 4;
+    assert_eq!(deviations(&|d| d[tracker] = 0x5C)[0], (tracker, String::from("TrackerDataBlock.BlockSize")));
+    assert_eq!(deviations(&|d| d.extend_from_slice(b"payload")), vec![(sample.len(), String::from("ExtraData"))]);
+    assert_eq!(deviations(&|d| d.truncate(0x40)), vec![(0, String::from("ShellLinkHeader"))]);
+
+    // Files written by the LNK writer follow the specification.
+    let spec: lnk::writer::LnkSpec = serde_json::from_str(
+      r#"{"target_full_path": "\\\\fs01\\share\\a.exe", "working_dir": "C:\\", "target_hostname": "ws01"}"#,
+    )
+    .unwrap();
+    assert_eq!(lnk::validate(&spec.to_bytes().unwrap()), vec![]);
+
+    let dir = temp_dir("validate");
+    let mut data = sample.clone();
+    data[0x48] = 1;
+    fs::write(dir.join("crafted.lnk"), &data).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["validate", &dir.join("crafted.lnk").to_string_lossy()])
+      .output()
+      .unwrap();
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8(output.stdout)
+      .unwrap()
+      .contains("  0x48 Reserved3: 0x1, must be zero"));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_spans() {
+    for path in glob("samples/*/*/*.lnk").unwrap().flatten() {
+      let data = fs::read(&path).unwrap();
+      let lnk = Lnk::from_buffer_with_spans(&data).unwrap();
+      let spans = lnk.spans.as_ref().unwrap();
+      assert!(spans.values().all(|span| span.offset + span.length <= data.len()));
+      // The StringData spans hold the count followed by the UTF-16 characters of the value.
+      if let (Some(working_dir), Some(span)) = (&lnk.working_dir, spans.get("working_dir")) {
+        let chars: Vec<u16> = data[span.offset + 2..span.offset + span.length]
+          .chunks(2)
+          .map(|c| u16::from_le_bytes([c[0], c[1]]))
+          .collect();
+        assert_eq!(String::from_utf16(&chars).unwrap(), working_dir.to_string());
+      }
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["-p", SAMPLE, "--output-format", "jsonl", "--spans"])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["spans"]["shell_link_header.guid"], serde_json::json!({"offset": 4, "length": 16}));
+    assert_eq!(record["spans"]["extra_data.extra_data_blocks.0.Tracker.machine_id"]["length"], 16);
+  }
+
+  #[test]
+  fn test_command_line() {
+    use lnk::command_line::{command_line_to_argv, Profile};
+
+    for (command_line, argv) in [
+      (r#"x.exe "a b" c"#, vec!["x.exe", "a b", "c"]),
+      (r#""C:\a b\x.exe"c d"#, vec![r"C:\a b\x.exe", "c", "d"]),
+      (r#"x.exe a\\\"b \\"c d" e\\f"#, vec!["x.exe", r#"a\"b"#, r"\c d", r"e\\f"]),
+      (r#"x.exe """c""" d"#, vec!["x.exe", r#""c""#, "d"]),
+      // A doubled quote inside quotes is a literal quote and ends the quoting.
+      (r#"x.exe "a""b c" d"#, vec!["x.exe", r#"a"b"#, "c d"]),
+      ("x.exe \"\" \t", vec!["x.exe", ""]),
+    ] {
+      assert_eq!(command_line_to_argv(command_line), argv, "{}", command_line);
+    }
+    let mut profile = Profile::new();
+    profile.set("Tools", r"D:\Tools");
+    assert_eq!(profile.expand("%tools%%x%tools%"), r"D:\Tools%xD:\Tools");
+
+    let dir = temp_dir("command_line");
+    let recent = dir.join("Users/bob/AppData/Roaming/Microsoft/Windows/Recent");
+    fs::create_dir_all(&recent).unwrap();
+    let spec: lnk::writer::LnkSpec = serde_json::from_value(serde_json::json!({
+      "target_full_path": r"%ProgramFiles%\App\app.exe",
+      "command_line_arguments": r#"/c "%TEMP%\a b.txt" -v"#,
+      "working_dir": r"%USERPROFILE%",
+    }))
+    .unwrap();
+    fs::write(recent.join("app.lnk"), spec.to_bytes().unwrap()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "-p",
+        &recent.join("*.lnk").to_string_lossy(),
+        "--output-format",
+        "jsonl",
+        "--normalize",
+        "--all-fields",
+      ])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+      record["command_line"],
+      r#""C:\Program Files\App\app.exe" /c "C:\Users\bob\AppData\Local\Temp\a b.txt" -v"#
+    );
+    let argv: Vec<String> = serde_json::from_str(record["argv"].as_str().unwrap()).unwrap();
+    assert_eq!(
+      argv,
+      vec![
+        r"C:\Program Files\App\app.exe",
+        "/c",
+        r"C:\Users\bob\AppData\Local\Temp\a b.txt",
+        "-v"
+      ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_resolve() {
+    use lnk::resolve::Resolver;
+    use std::time::{Duration, SystemTime};
+
+    let root = temp_dir("resolve");
+    let desktop = root.join("Users/Bob/Desktop");
+    fs::create_dir_all(&desktop).unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1612788123);
+    let target = fs::File::create(desktop.join("Report.TXT")).unwrap();
+    target.set_len(4).unwrap();
+    target.set_modified(modified).unwrap();
+
+    let resolver = Resolver::new(&root).share(r"\\fs01\data", root.join("share"));
+    let expected = desktop.join("Report.TXT");
+    for (windows_path, user) in [
+      (r"C:\users\bob\DESKTOP\report.txt", None),
+      (r"\\WS01\c$\Users\bob\Desktop\report.txt", None),
+      (r"B4BFCC3A-DB2C-424C-B029-7FE99A87C641\report.txt", Some("bob")),
+      (r"::{B4BFCC3A-DB2C-424C-B029-7FE99A87C641}\report.txt", None),
+    ] {
+      assert_eq!(resolver.map_path(windows_path, user), Some((expected.clone(), true)), "{}", windows_path);
+    }
+    assert_eq!(resolver.map_path(r"\\FS01\Data\a.txt", None), Some((root.join("share").join("a.txt"), false)));
+    assert_eq!(resolver.map_path(r"D:\a.txt", None), None);
+
+    for (name, target, size) in [
+      ("same", r"C:\Users\bob\Desktop\report.txt", 4),
+      ("changed", r"C:\Users\bob\Desktop\report.txt", 5),
+      ("deleted", r"C:\Users\bob\Desktop\old.txt", 4),
+    ] {
+      let spec: lnk::writer::LnkSpec = serde_json::from_value(serde_json::json!({
+        "target_full_path": target,
+        "target_size": size,
+        "target_modification_time": "2021-02-08T12:42:03Z",
+      }))
+      .unwrap();
+      fs::write(root.join(format!("{}.lnk", name)), spec.to_bytes().unwrap()).unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "-p",
+        &root.join("*.lnk").to_string_lossy(),
+        "--output-format",
+        "jsonl",
+        "--resolve",
+        &root.to_string_lossy(),
+      ])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    let records: Vec<serde_json::Value> = output
+      .stdout
+      .split(|b| *b == b'\n')
+      .filter(|line| !line.trim_ascii().is_empty())
+      .map(|line| serde_json::from_slice(line).unwrap())
+      .collect();
+    let resolutions: Vec<_> = records
+      .iter()
+      .map(|r| {
+        let resolution = &r["resolution"];
+        (resolution["exists"].clone(), resolution["size_matches"].clone(), resolution["mtime_matches"].clone())
+      })
+      .collect();
+    // The records are in path order: changed, deleted, same.
+    assert_eq!(
+      resolutions,
+      vec![
+        (true.into(), false.into(), true.into()),
+        (false.into(), serde_json::Value::Null, serde_json::Value::Null),
+        (true.into(), true.into(), true.into()),
+      ]
+    );
+    assert_eq!(records[0]["resolution"]["path"], expected.to_string_lossy().as_ref());
+
+    // The MFT entry recorded in the IDList is compared with the inode number of the target.
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::MetadataExt;
+      let test = root.join("Users/u0041/Desktop/test");
+      fs::create_dir_all(&test).unwrap();
+      fs::write(test.join("test.txt"), "test").unwrap();
+      let inode = fs::metadata(test.join("test.txt")).unwrap().ino();
+      let mft_entry_matches = |data: &[u8]| {
+        Lnk::from_buffer(data)
+          .unwrap()
+          .resolve(&Resolver::new(&root))
+          .unwrap()
+          .mft_entry_matches
+      };
+      let mut data = fs::read(SAMPLE).unwrap();
+      assert_eq!(mft_entry_matches(&data), Some(inode == 90070));
+      // The file reference of the last shell item, its MFT entry is 90070.
+      data[200..206].copy_from_slice(&inode.to_le_bytes()[..6]);
+      assert_eq!(mft_entry_matches(&data), Some(true));
+    }
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_report() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "report",
+        "-p",
+        "samples/WIN10/1607_14393/*.lnk",
+        "--output-format",
+        "json",
+      ])
+      .output()
+      .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["shortcuts"], 6);
+    assert_eq!(report["machines"][0]["machine_id"], "win10");
+    assert_eq!(report["machines"][0]["shortcuts"].as_array().unwrap().len(), 4);
+    assert_eq!(report["volumes"][0]["serial_number"], "E02E-8A93");
+    assert_eq!(report["volumes"][0]["drive_type"], "DRIVE_FIXED");
+    assert_eq!(report["shares"][0]["net_name"], r"\\127.0.0.1\TEST");
+    assert_eq!(report["shares"][0]["shortcuts"].as_array().unwrap().len(), 2);
+    assert!(report["shares"][0]["first_seen"].as_str().unwrap() <= report["shares"][0]["last_seen"].as_str().unwrap());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["report", "-p", "samples/WIN10/1607_14393/network_share.lnk"])
+      .output()
+      .unwrap();
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("Shares (1)\n  \\\\127.0.0.1\\TEST\n"), "{}", text);
+
+    // A file the parser panics on is a parse failure, a directory is an I/O failure.
+    let dir = temp_dir("report");
+    let mut data = fs::read("samples/WIN10/1607_14393/network_share.lnk").unwrap();
+    data[0x68..0x6C].copy_from_slice(&[0; 4]);
+    fs::write(dir.join("a.lnk"), data).unwrap();
+    let status = || {
+      Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["report", "-p", &dir.join("*.lnk").to_string_lossy()])
+        .output()
+        .unwrap()
+        .status
+        .code()
+    };
+    assert_eq!(status(), Some(4));
+    fs::create_dir(dir.join("b.lnk")).unwrap();
+    assert_eq!(status(), Some(5));
+  }
+
+  #[test]
+  fn test_redact() {
+    let dir = temp_dir("redact");
+    let recent = dir.join("Users/Bob/AppData/Roaming/Microsoft/Windows/Recent");
+    fs::create_dir_all(&recent).unwrap();
+    let spec: lnk::writer::LnkSpec = serde_json::from_value(serde_json::json!({
+      "target_full_path": r"C:\Users\BOB\Desktop\plan.docx",
+      "command_line_arguments": r#"/copy "\\fs01\finance\q3.xlsx""#,
+      "volume_serial_number": "1234-ABCD",
+      "volume_label": "BOBS USB",
+      "target_hostname": "ws01",
+      "file_droid": "88D989A6-6A0F-11EB-80B8-000C292F659F",
+    }))
+    .unwrap();
+    fs::write(recent.join("plan.lnk"), spec.to_bytes().unwrap()).unwrap();
+    let key = dir.join("key");
+    let redact = |key_content: &str, format: &[&str]| {
+      fs::write(&key, key_content).unwrap();
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args([
+          "-p",
+          &recent.join("*.lnk").to_string_lossy(),
+          "--redact",
+          &key.to_string_lossy(),
+        ])
+        .args(format)
+        .output()
+        .unwrap();
+      assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+      String::from_utf8(output.stdout).unwrap()
+    };
+
+    let normalized = redact("secret\n", &["--output-format", "jsonl", "--normalize", "--all-fields"]);
+    let record: serde_json::Value = serde_json::from_str(&normalized).unwrap();
+    let user = record["user"].as_str().unwrap();
+    assert!(user.starts_with("USER-"));
+    assert_eq!(record["target_full_path"], format!(r"C:\Users\{}\Desktop\plan.docx", user));
+    assert!(record["lnk_full_path"]
+      .as_str()
+      .unwrap()
+      .contains(&format!("Users/{}/AppData", user)));
+    assert!(record["command_line_arguments"]
+      .as_str()
+      .unwrap()
+      .starts_with(r#"/copy "\\HOST-"#));
+    assert_ne!(record["volume_serial_number"], "1234-ABCD");
+    assert!(record["file_droid"].as_str().unwrap().starts_with("88D989A6-6A0F-11EB-80B8-"));
+    assert_ne!(record["file_droid"], "88D989A6-6A0F-11EB-80B8-000C292F659F");
+
+    let json = redact("secret", &["--output-format", "jsonl"]);
+    for output in [&normalized, &json] {
+      let lowercase = output.to_lowercase();
+      for value in ["bob", "ws01", "fs01", "finance", "1234-abcd", "000c292f659f"] {
+        assert!(!lowercase.contains(value), "{} in {}", value, output);
+      }
+    }
+    // The same value always gets the same pseudonym, a different key gives different ones.
+    assert!(json.contains(user));
+    let again: serde_json::Value =
+      serde_json::from_str(&redact("secret", &["--output-format", "jsonl", "--normalize", "--all-fields"])).unwrap();
+    assert_eq!(again, record);
+    assert!(!redact("other", &["--output-format", "jsonl", "--normalize"]).contains(user));
+
+    fs::write(&key, "secret").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args([
+        "-p",
+        &recent.join("*.lnk").to_string_lossy(),
+        "--redact",
+        &key.to_string_lossy(),
+      ])
+      .args(["--output-format", "ecs"])
+      .output()
+      .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  /// Check `value` against the subset of JSON Schema the generated schemas use.
+  fn conforms(value: &serde_json::Value, schema: &serde_json::Value, root: &serde_json::Value) -> Result<(), String> {
+    use serde_json::Value;
+    if let Some(reference) = schema["$ref"].as_str() {
+      return conforms(value, &root["$defs"][reference.trim_start_matches("#/$defs/")], root);
+    }
+    if let Some(any_of) = schema["anyOf"].as_array().or(schema["oneOf"].as_array()) {
+      return match any_of.iter().any(|schema| conforms(value, schema, root).is_ok()) {
+        true => Ok(()),
+        false => Err(format!("{} matches none of {}", value, Value::Array(any_of.clone()))),
+      };
+    }
+    let type_matches = |t: &Value| match t.as_str().unwrap() {
+      "object" => value.is_object(),
+      "array" => value.is_array(),
+      "string" => value.is_string(),
+      "integer" => value.is_u64() || value.is_i64(),
+      "boolean" => value.is_boolean(),
+      "null" => value.is_null(),
+      _ => true,
+    };
+    let type_ok = match &schema["type"] {
+      Value::Array(types) => types.iter().any(type_matches),
+      Value::Null => true,
+      t => type_matches(t),
+    };
+    if !type_ok || schema.get("const").is_some_and(|c| c != value) {
+      return Err(format!("{} does not match {}", value, schema));
+    }
+    match value {
+      Value::Object(object) => {
+        for field in schema["required"].as_array().into_iter().flatten() {
+          if !object.contains_key(field.as_str().unwrap()) {
+            return Err(format!("missing {}", field));
+          }
+        }
+        for (field, value) in object {
+          match &schema["properties"][field] {
+            Value::Null if schema["additionalProperties"] == false => return Err(format!("unexpected {}", field)),
+            Value::Null => {
+              if let Value::Object(_) = &schema["additionalProperties"] {
+                conforms(value, &schema["additionalProperties"], root)?;
+              }
+            },
+            field_schema => conforms(value, field_schema, root).map_err(|e| format!("{}: {}", field, e))?,
+          }
+        }
+      },
+      Value::Array(items) if !schema["items"].is_null() => {
+        for item in items {
+          conforms(item, &schema["items"], root)?;
+        }
+      },
+      _ => {},
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_schema() {
+    for (args, published) in [
+      (vec!["schema"], "schema/lnk.schema.json"),
+      (vec!["schema", "--normalize"], "schema/normalized.schema.json"),
+    ] {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser")).args(&args).output().unwrap();
+      assert!(output.status.success());
+      let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+      let published: serde_json::Value = serde_json::from_str(&fs::read_to_string(published).unwrap()).unwrap();
+      assert_eq!(schema, published, "regenerate {} with `lnk_parser {}`", args.join(" "), args.join(" "));
+
+      let mut parse_args = vec![
+        "-p",
+        "samples/*/*/*.lnk",
+        "--output-format",
+        "jsonl",
+        "--spans",
+        "--rules",
+        "rules",
+      ];
+      if args.len() == 2 {
+        parse_args[4] = "--normalize";
+      }
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(&parse_args)
+        .output()
+        .unwrap();
+      let records = String::from_utf8(output.stdout).unwrap();
+      assert!(records.lines().count() > 1);
+      for record in records.lines() {
+        let record: serde_json::Value = serde_json::from_str(record).unwrap();
+        assert_eq!(record["schema_version"], lnk::schema::SCHEMA_VERSION);
+        if let Err(e) = conforms(&record, &schema, &schema) {
+          panic!("{} does not conform to its schema: {}", record["lnk_file_metadata"]["full_path"], e);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_rules() {
+    use lnk::rules::{RuleSet, Severity};
+
+    let rule = |conditions: &str| format!("id: r\ntitle: t\nseverity: low\nconditions:\n{}", conditions);
+    let data = fs::read(SAMPLE).unwrap();
+    let lnk = Lnk::from_buffer(&data).unwrap();
+    let matches = |conditions: &str| !RuleSet::parse(&rule(conditions)).unwrap().evaluate(&lnk, &data).is_empty();
+    assert!(matches("  - field: target_hostname\n    equals: WIN10\n    ignore_case: true"));
+    assert!(!matches("  - field: target_hostname\n    equals: WIN10"));
+    assert!(matches("  - field: target_full_path\n    contains: '\\Desktop\\'"));
+    assert!(matches("  - field: target_full_path\n    regex: '\\.TXT$'\n    ignore_case: true"));
+    assert!(matches("  - field: target_size\n    min: 1\n    max: 4"));
+    assert!(!matches("  - field: target_size\n    min: 5"));
+    assert!(matches("  - extra_data: 0xA0000003"));
+    assert!(!matches("  - extra_data: Tracker\n    not: true"));
+    assert!(matches("  - field: drive_type\n    equals: DRIVE_FIXED\n  - extra_data: TrackerDataBlock"));
+
+    // Invalid rules are reported with the rule they are in.
+    for (yaml, message) in [
+      (rule("  - field: nope\n    equals: x"), "unknown field 'nope'"),
+      (rule("  - field: user\n    equals: x\n    contains: x"), "exactly one of"),
+      (rule("  - field: user\n    regex: '('"), "invalid regex"),
+      (rule("  - extra_data: Unknown"), "unknown ExtraData block"),
+      (rule("  - field: user\n    equal: x"), "unknown field `equal`"),
+      (
+        format!("{}\n---\n{}", rule("  - field: user\n    equals: x"), rule("  - field: user\n    equals: y")),
+        "duplicate rule id 'r'",
+      ),
+    ] {
+      let error = RuleSet::parse(&yaml).unwrap_err().to_string();
+      assert!(error.contains(message), "{}", error);
+    }
+
+    // The sample rules pass their tests and are added to the records.
+    let rules = RuleSet::load("rules").unwrap();
+    let results = rules.run_tests();
+    assert!(results.iter().all(|result| result.passed()));
+    // Every sample rule is tested against a file it matches, not only against files it does not match.
+    for rule in rules.rules() {
+      assert!(
+        results.iter().any(|result| result.rule_id == rule.id && result.should_match),
+        "{} has no should_match test",
+        rule.id
+      );
+    }
+    let lnk_parser = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_lnk_parser")).args(args).output().unwrap();
+    assert!(lnk_parser(&["rules", "rules", "--test"]).status.success());
+    let output = lnk_parser(&[
+      "-p",
+      "samples/WIN10/*/network_share.lnk",
+      "--output-format",
+      "jsonl",
+      "--rules",
+      "rules",
+    ]);
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["rule_matches"][0]["id"], "lnk-target-on-network-share");
+    assert_eq!(record["rule_matches"][0]["severity"], serde_json::json!(Severity::Low));
+    let output = lnk_parser(&["-p", SAMPLE, "--rules", "rules", "--filter", "rule_matches == \"\""]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+    // The test files are parsed like the inputs of a run, with the metadata and the user of their path.
+    let dir = temp_dir("rules");
+    let recent = dir.join("Users/bob/AppData/Roaming/Microsoft/Windows/Recent");
+    fs::create_dir_all(&recent).unwrap();
+    fs::copy(SAMPLE, recent.join("a.lnk")).unwrap();
+    let conditions = "  - field: user\n    equals: bob\n  - field: lnk_full_path\n    contains: Recent\n  - field: lnk_modification_time\n    regex: '^\\d{4}-'";
+    let yaml = format!(
+      "{}\ntests:\n  should_match: ['Users/bob/AppData/Roaming/Microsoft/Windows/Recent/a.lnk']",
+      rule(conditions)
+    );
+    fs::write(dir.join("user.yml"), yaml).unwrap();
+    let results = RuleSet::load(&dir).unwrap().run_tests();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].passed(), "{:?}", results[0].outcome);
+    fs::remove_dir_all(&dir).unwrap();
+
+    // A rule that does not give the expected result fails the harness.
+    let dir = temp_dir("rules");
+    let sample = fs::canonicalize(SAMPLE).unwrap();
+    fs::write(
+      dir.join("wrong.yaml"),
+      format!("{}\ntests:\n  should_match: ['{}']", rule("  - field: user\n    equals: nobody"), sample.display()),
+    )
+    .unwrap();
+    let output = lnk_parser(&["rules", &dir.to_string_lossy(), "--test"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("FAIL r "));
+    // A symbolic link looping back to the rules directory is not followed.
+    #[cfg(unix)]
+    {
+      std::os::unix::fs::symlink(&dir, dir.join("loop")).unwrap();
+      assert_eq!(RuleSet::load(&dir).unwrap().rules().len(), 1);
+    }
+    fs::write(dir.join("broken.yml"), "id: [").unwrap();
+    assert_eq!(lnk_parser(&["rules", &dir.to_string_lossy()]).status.code(), Some(2));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_stix() {
+    let stix = |paths: &[&str]| {
+      let mut args = vec!["--output-format", "stix"];
+      for path in paths {
+        args.extend(["-p", path]);
+      }
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser")).args(&args).output().unwrap();
+      assert!(output.status.success());
+      serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let network_share = "samples/WIN10/1607_14393/network_share.lnk";
+    let bundle = stix(&[SAMPLE, network_share]);
+    assert_eq!(bundle["type"], "bundle");
+    let objects = bundle["objects"].as_array().unwrap();
+    let ids: Vec<&str> = objects.iter().map(|o| o["id"].as_str().unwrap()).collect();
+    for object in objects {
+      assert_eq!(object["spec_version"], "2.1");
+      let (object_type, uuid) = object["id"].as_str().unwrap().split_once("--").unwrap();
+      assert_eq!(object_type, object["type"]);
+      assert_eq!(&uuid[14..15], "5", "{} is not a UUIDv5", uuid);
+      for reference in ["source_ref", "target_ref", "image_ref", "parent_directory_ref"] {
+        if let Some(id) = object[reference].as_str() {
+          assert!(ids.contains(&id), "{} refers to a missing object", object["id"]);
+        }
+      }
+    }
+    let find = |object_type: &str, property: &str, value: &str| {
+      objects
+        .iter()
+        .find(|o| o["type"] == object_type && o[property] == value)
+        .unwrap_or_else(|| panic!("no {} with {} {}", object_type, property, value))
+    };
+    let lnk_file = find("file", "name", "windows_generated.lnk");
+    let target = find("file", "name", "test.txt");
+    assert_eq!(target["parent_directory_ref"], find("directory", "path", "C:\\Users\\u0041\\Desktop\\test")["id"]);
+    let process = find("process", "command_line", "C:\\Users\\u0041\\Desktop\\test\\test.txt");
+    assert_eq!(process["image_ref"], target["id"]);
+    let relationship = find("relationship", "target_ref", target["id"].as_str().unwrap());
+    assert_eq!(relationship["source_ref"], lnk_file["id"]);
+    assert_eq!(relationship["relationship_type"], "refers-to");
+    let share = find("directory", "path", "\\\\127.0.0.1\\TEST");
+    assert_eq!(find("relationship", "target_ref", share["id"].as_str().unwrap())["relationship_type"], "refers-to");
+
+    // The IDs only depend on the data, the same file given twice is written once.
+    assert_eq!(stix(&[SAMPLE, network_share]), bundle);
+    let once = stix(&[SAMPLE]);
+    assert_eq!(stix(&[SAMPLE, SAMPLE]), once);
+    assert_ne!(once["id"], bundle["id"]);
+  }
+
+  #[test]
+  fn test_find() {
+    let dir = temp_dir("find");
+    let index = dir.join("samples.idx").to_string_lossy().to_string();
+    let find = |args: &[&str]| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .arg("find")
+        .args(args)
+        .output()
+        .unwrap();
+      assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+      String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(find(&["-p", "samples/*/*/*.lnk", "--index", &index]), "");
+    let lnk_names = |args: &[&str]| {
+      let mut args = args.to_vec();
+      args.extend(["--index", &index]);
+      let mut names: Vec<String> = find(&args)
+        .lines()
+        .map(|line| {
+          line
+            .split(" -> ")
+            .next()
+            .unwrap()
+            .trim_start_matches(env!("CARGO_MANIFEST_DIR"))
+            .to_string()
+        })
+        .collect();
+      names.sort();
+      names
+    };
+
+    assert_eq!(lnk_names(&[]).len(), 20);
+    assert_eq!(lnk_names(&["--target", "//127.0.0.1/test"]).len(), 8);
+    assert_eq!(lnk_names(&["--target", "//127.0.0.1/tes"]).len(), 0);
+    assert_eq!(
+      lnk_names(&[
+        "--target-prefix",
+        "C:/USERS/u0041/desktop/test/t",
+        "--machine-id",
+        "WIN10"
+      ]),
+      [
+        "/samples/WIN10/1607_14393/manual.lnk",
+        "/samples/WIN10/1607_14393/windows_generated.lnk"
+      ]
+    );
+    // The target of the WS12R2 shortcuts is also on a share.
+    assert_eq!(lnk_names(&["--target-prefix", "\\\\ws12r2\\TEST\\"]).len(), 4);
+    assert_eq!(lnk_names(&["--volume-serial", "e02e8a93"]).len(), 4);
+    // The shortcuts to the share were created on the same volume, they have the same volume droid.
+    assert_eq!(lnk_names(&["--droid", "{00d2581c-4749-44bd-9381-9bdfadf8a9de}"]).len(), 6);
+    assert_eq!(lnk_names(&["--mft", "90070-3"]), lnk_names(&["--mft", "90070", "--machine-id", "win10"]));
+    assert_eq!(lnk_names(&["--mft", "90070-4"]).len(), 0);
+
+    let entry: serde_json::Value = serde_json::from_str(
+      find(&["--index", &index, "--mft", "90070", "--output-format", "jsonl"])
+        .lines()
+        .next()
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(entry["mft_sequence_number"], 3);
+
+    // An invalid index or query is a usage error.
+    fs::write(&index, "[]").unwrap();
+    let status = |args: &[&str]| {
+      Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(args)
+        .status()
+        .unwrap()
+        .code()
+    };
+    assert_eq!(status(&["find", "--index", &index]), Some(2));
+    assert_eq!(status(&["find", "--index", &index, "--mft", "x-1"]), Some(2));
+    assert_eq!(status(&["find", "--target", "x"]), Some(2));
+    // An index that can not be read is an I/O error.
+    let missing = dir.join("missing.idx").to_string_lossy().to_string();
+    assert_eq!(status(&["find", "--index", &missing]), Some(5));
+    assert_eq!(status(&["find", "--index", &dir.to_string_lossy()]), Some(5));
+    // So is an input that can not be read, a file the parser panics on is a parse failure.
+    let inputs = dir.join("inputs");
+    fs::create_dir(&inputs).unwrap();
+    let mut data = fs::read("samples/WIN10/1607_14393/network_share.lnk").unwrap();
+    data[0x68..0x6C].copy_from_slice(&[0; 4]);
+    fs::write(inputs.join("a.lnk"), data).unwrap();
+    let pattern = inputs.join("*.lnk").to_string_lossy().to_string();
+    assert_eq!(status(&["find", "-p", &pattern]), Some(4));
+    fs::create_dir(inputs.join("b.lnk")).unwrap();
+    assert_eq!(status(&["find", "-p", &pattern]), Some(5));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_desktop() {
+    use lnk::{
+      desktop::{DesktopEntry, EntryType, MountTable},
+      writer::LnkSpec,
+    };
+
+    let mounts = MountTable::from_toml("C = \"/mnt/c/\"\n'\\\\127.0.0.1\\TEST' = \"/mnt/test\"\n").unwrap();
+    let entry = DesktopEntry::from_lnk(&Lnk::from_path(SAMPLE).unwrap(), &mounts).unwrap();
+    assert_eq!(entry.entry_type, EntryType::Link);
+    assert_eq!(entry.name, "windows_generated");
+    assert_eq!(entry.url.as_deref(), Some("file:///mnt/c/Users/u0041/Desktop/test/test.txt"));
+    let entry = DesktopEntry::parse(&entry.to_string()).unwrap();
+    let spec = entry.to_lnk_spec(&mounts).unwrap();
+    assert_eq!(spec.target_full_path, r"C:\Users\u0041\Desktop\test\test.txt");
+    assert_eq!(spec.working_dir.as_deref(), Some(r"C:\Users\u0041\Desktop\test"));
+    let share = Lnk::from_path("samples/WIN10/1607_14393/network_share.lnk").unwrap();
+    let entry = DesktopEntry::from_lnk(&share, &mounts).unwrap();
+    assert_eq!(entry.url.as_deref(), Some("file:///mnt/test"));
+    assert_eq!(entry.to_lnk_spec(&mounts).unwrap().target_full_path, r"\\127.0.0.1\TEST");
+    assert!(DesktopEntry::from_lnk(&share, &MountTable::new().drive('C', "/mnt/c")).is_err());
+
+    // Arguments are quoted for the Exec key and for CommandLineToArgvW on the way back.
+    let spec = LnkSpec {
+      target_full_path: String::from(r"C:\Program Files\App\app.exe"),
+      command_line_arguments: Some(String::from(r#"--open "C:\Users\bob\My Docs\a.txt" 100% D:\x "q\"" "e\\""#)),
+      working_dir: Some(String::from(r"C:\Users\bob")),
+      icon_location: Some(String::from(r"%SystemRoot%\system32\shell32.dll")),
+      name_string: Some(String::from("My app")),
+      ..LnkSpec::default()
+    };
+    let lnk = Lnk::from_buffer(&spec.to_bytes().unwrap()).unwrap();
+    let entry = DesktopEntry::from_lnk(&lnk, &mounts).unwrap();
+    let desktop = entry.to_string();
+    assert!(desktop
+      .contains(r#"Exec="/mnt/c/Program Files/App/app.exe" --open "/mnt/c/Users/bob/My Docs/a.txt" 100%% "D:\\\\x""#));
+    assert!(desktop.contains("Icon=/mnt/c/Windows/system32/shell32.dll\n"));
+    let entry = DesktopEntry::parse(&format!("# Converted\n{}[Desktop Action x]\nExec=y\n", desktop)).unwrap();
+    assert_eq!(entry.exec.len(), 7);
+    let round_trip = Lnk::from_buffer(&entry.to_lnk_spec(&mounts).unwrap().to_bytes().unwrap()).unwrap();
+    // The arguments may be quoted differently but give the same argv.
+    let argv = |lnk: &Lnk| {
+      lnk
+        .command_line(&lnk::command_line::Profile::windows_defaults(None))
+        .unwrap()
+        .argv
+    };
+    assert_eq!(argv(&round_trip), argv(&lnk));
+    assert_eq!(round_trip.name_string.unwrap().string, "My app");
+  }
+
+  #[test]
+  fn test_tui() {
+    use lnk::spans::{spans, tree, Node};
+
+    // Every span is in the tree once, inside the span of its parent.
+    fn check(nodes: &[Node], parent: Option<&Node>, count: &mut usize) {
+      for node in nodes {
+        assert!(parent.is_none_or(|parent| parent.span.contains(&node.span)), "{}", node.path);
+        *count += 1;
+        check(&node.children, Some(node), count);
+      }
+    }
+    for path in glob("samples/*/*/*.lnk").unwrap().flatten() {
+      let spans = spans(&fs::read(&path).unwrap());
+      let sections = tree(&spans);
+      let mut count = 0;
+      check(&sections, None, &mut count);
+      assert_eq!(count, spans.len());
+      assert!(sections
+        .windows(2)
+        .all(|s| s[0].span.offset + s[0].span.length <= s[1].span.offset));
+    }
+    let sections = tree(&spans(&fs::read(SAMPLE).unwrap()));
+    let paths: Vec<&str> = sections.iter().map(|node| node.path.as_str()).collect();
+    assert_eq!(
+      paths,
+      [
+        "shell_link_header",
+        "link_target_id_list",
+        "link_info",
+        "relative_path",
+        "working_dir",
+        "extra_data.blocks.0",
+        "extra_data.blocks.1",
+        "extra_data.terminal_block"
+      ]
+    );
+    let tracker: Vec<&str> = sections[5].children.iter().map(|node| node.path.as_str()).collect();
+    assert_eq!(
+      tracker[..3],
+      [
+        "extra_data.blocks.0.size",
+        "extra_data.blocks.0.signature",
+        "extra_data.extra_data_blocks.0.Tracker.size"
+      ]
+    );
+    assert_eq!(
+      sections[2]
+        .children
+        .iter()
+        .find(|n| n.path == "link_info.volume_id")
+        .unwrap()
+        .children
+        .len(),
+      5
+    );
+
+    // The UI needs a terminal.
+    let status = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+      .args(["tui", SAMPLE])
+      .output()
+      .unwrap()
+      .status;
+    assert_eq!(status.code(), Some(2));
+  }
+
+  /// Build an uncompressed prefetch file of `version` for APP.EXE, with `filenames` extra file names so the file can
+  /// span several compression blocks.
+  fn prefetch_file(version: u32, metrics_offset: u32, filenames: usize) -> Vec<u8> {
+    fn put_u32(buf: &mut Vec<u8>, offset: usize, value: u32) {
+      if buf.len() < offset + 4 {
+        buf.resize(offset + 4, 0);
+      }
+      buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    fn put_u64(buf: &mut Vec<u8>, offset: usize, value: u64) {
+      put_u32(buf, offset, value as u32);
+      put_u32(buf, offset + 4, (value >> 32) as u32);
+    }
+    fn put_utf16(buf: &mut Vec<u8>, offset: usize, value: &str) -> usize {
+      let bytes: Vec<u8> = value.encode_utf16().chain(Some(0)).flat_map(u16::to_le_bytes).collect();
+      if buf.len() < offset + bytes.len() {
+        buf.resize(offset + bytes.len(), 0);
+      }
+      buf[offset..offset + bytes.len()].copy_from_slice(&bytes);
+      bytes.len()
+    }
+    let mut buf = vec![0; 0x200];
+    put_u32(&mut buf, 0, version);
+    buf[4..8].copy_from_slice(b"SCCA");
+    put_utf16(&mut buf, 16, "APP.EXE");
+    put_u32(&mut buf, 76, 0x1234ABCD);
+    let info = 84;
+    put_u32(&mut buf, info, metrics_offset);
+    let (run_times_offset, run_times, run_count_offset, volume_size) = match version {
+      17 => (36, 1, 60, 40),
+      23 => (44, 1, 68, 104),
+      30 if metrics_offset != 304 => (44, 8, 116, 96),
+      30 => (44, 8, 124, 96),
+      _ => (44, 8, 124, 104),
+    };
+    for i in 0..run_times {
+      // The second slot is empty, as if the executable only ran the other times.
+      if i != 1 {
+        put_u64(&mut buf, info + run_times_offset + i * 8, 132000000000000000 + i as u64 * 36000000000);
+      }
+    }
+    put_u32(&mut buf, info + run_count_offset, 42);
+
+    let mut names: Vec<String> = (0..filenames)
+      .map(|i| format!("\\VOLUME{{01D5-TEST}}\\WINDOWS\\SYSTEM32\\FILE{:05}.DLL", i))
+      .collect();
+    names.push(String::from("\\VOLUME{01D5-TEST}\\PROGRAM FILES\\APP\\APP.EXE"));
+    let mut offset = 0x200;
+    for name in &names {
+      offset += put_utf16(&mut buf, offset, name);
+    }
+    put_u32(&mut buf, info + 16, 0x200);
+    put_u32(&mut buf, info + 20, (offset - 0x200) as u32);
+
+    let volumes = offset;
+    put_u32(&mut buf, info + 24, volumes as u32);
+    put_u32(&mut buf, info + 28, 1);
+    put_u32(&mut buf, volumes, volume_size as u32);
+    put_u32(&mut buf, volumes + 4, 18);
+    put_u64(&mut buf, volumes + 8, 132000000000000000);
+    put_u32(&mut buf, volumes + 16, 0x12345678);
+    put_utf16(&mut buf, volumes + volume_size, "\\VOLUME{01D5-TEST}");
+    let references = volume_size + 0x40;
+    put_u32(&mut buf, volumes + 20, references as u32);
+    put_u32(&mut buf, volumes + 24, 40);
+    put_u32(&mut buf, volumes + references + 4, 3);
+    let first = volumes + references + if version == 17 { 8 } else { 16 };
+    put_u64(&mut buf, first, 5 | 5 << 48);
+    put_u64(&mut buf, first + 16, 0x1234 | 2 << 48);
+    let directories = references + 0x40;
+    put_u32(&mut buf, volumes + 28, directories as u32);
+    put_u32(&mut buf, volumes + 32, 2);
+    let mut offset = volumes + directories;
+    for directory in [
+      "\\VOLUME{01D5-TEST}\\WINDOWS",
+      "\\VOLUME{01D5-TEST}\\PROGRAM FILES\\APP",
+    ] {
+      buf.resize(offset + 2, 0);
+      buf[offset..offset + 2].copy_from_slice(&(directory.len() as u16).to_le_bytes());
+      offset += 2 + put_utf16(&mut buf, offset + 2, directory);
+    }
+    let size = buf.len() as u32;
+    put_u32(&mut buf, 12, size);
+    buf
+  }
+
+  /// Compress `data` in a MAM container. Every symbol gets a 9-bit code, equal to the symbol, runs of a repeated
+  /// byte are written as matches at offset 1.
+  fn mam_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = b"MAM\x04".to_vec();
+    out.extend((data.len() as u32).to_le_bytes());
+    for block in data.chunks(65536) {
+      out.extend([0x99; 256]);
+      let mut symbols = vec![];
+      let mut i = 0;
+      while i < block.len() {
+        let run = block[i..].iter().skip(1).take(17).take_while(|b| **b == block[i]).count();
+        symbols.push(block[i] as u32);
+        if run >= 3 {
+          // Offset 1 needs no offset bits, the low nibble is the length minus 3.
+          symbols.push(256 + (run as u32 - 3));
+          i += run;
+        }
+        i += 1;
+      }
+      let mut bits: Vec<bool> = symbols
+        .iter()
+        .flat_map(|s| (0..9).rev().map(move |b| s >> b & 1 == 1))
+        .collect();
+      bits.resize(bits.len().div_ceil(16) * 16 + 16, false);
+      for word in bits.chunks(16) {
+        let word = word.iter().fold(0u16, |w, b| w << 1 | *b as u16);
+        out.extend(word.to_le_bytes());
+      }
+    }
+    out
+  }
+
+  #[test]
+  fn test_prefetch() {
+    use lnk::prefetch::Prefetch;
+
+    for (version, metrics_offset, run_times) in [(17, 152, 1), (23, 240, 1), (26, 304, 7), (30, 304, 7), (30, 296, 7)] {
+      let pf = Prefetch::from_buffer(&prefetch_file(version, metrics_offset, 2)).unwrap();
+      assert_eq!(pf.version, version);
+      assert_eq!(pf.executable_name, "APP.EXE");
+      assert_eq!(pf.prefetch_hash, "1234ABCD");
+      assert_eq!(pf.run_count, 42, "version {} metrics at {}", version, metrics_offset);
+      assert_eq!(pf.last_run_times.len(), run_times);
+      assert_eq!(pf.last_run_times[0].to_string(), "2019-04-17T18:40:00Z");
+      assert_eq!(pf.filenames.len(), 3);
+      assert_eq!(pf.executable_path.as_deref(), Some("\\VOLUME{01D5-TEST}\\PROGRAM FILES\\APP\\APP.EXE"));
+      let volume = &pf.volumes[0];
+      assert_eq!(volume.device_path, "\\VOLUME{01D5-TEST}");
+      assert_eq!(volume.serial_number, "1234-5678");
+      assert_eq!(volume.creation_time.to_string(), "2019-04-17T18:40:00Z");
+      assert_eq!(volume.directories[1], "\\VOLUME{01D5-TEST}\\PROGRAM FILES\\APP");
+      let references: Vec<String> = volume.file_references.iter().map(|r| r.to_string()).collect();
+      assert_eq!(references, ["5-5", "4660-2"]);
+    }
+    let normalized = Prefetch::from_buffer(&prefetch_file(30, 304, 0)).unwrap().normalize();
+    assert_eq!(normalized["last_run_times"].split('|').nth(1), Some("2019-04-17T20:40:00Z"));
+    assert_eq!(normalized["volume_serial_numbers"], "1234-5678");
+
+    // Windows 10 files are compressed, this one spans several blocks and has matches.
+    let data = prefetch_file(30, 304, 3000);
+    assert!(data.len() > 3 * 65536);
+    let compressed = mam_compress(&data);
+    assert_eq!(Prefetch::decompress(&compressed).unwrap(), data);
+    let mut truncated = compressed.clone();
+    truncated.truncate(compressed.len() / 2);
+    assert!(Prefetch::from_buffer(&truncated).is_err());
+    // The size in the header can not be larger than what the data can hold.
+    let mut oversized = compressed.clone();
+    oversized[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Prefetch::decompress(&oversized)
+      .unwrap_err()
+      .to_string()
+      .contains("larger than the data"));
+    // A header with a checksum has 12 bytes.
+    assert!(Prefetch::from_buffer(b"MAM\x84\x10\0\0\0\0\0").is_err());
+    let mut unsupported = compressed.clone();
+    unsupported[3] = 0x03;
+    assert!(Prefetch::from_buffer(&unsupported).is_err());
+    assert!(Prefetch::from_buffer(&prefetch_file(31, 304, 0)).is_err());
+
+    let dir = temp_dir("prefetch");
+    let prefetch_dir = dir.join("Windows").join("Prefetch");
+    fs::create_dir_all(&prefetch_dir).unwrap();
+    fs::write(prefetch_dir.join("APP.EXE-1234ABCD.pf"), &compressed).unwrap();
+    fs::write(prefetch_dir.join("OLD.EXE-1234ABCD.pf"), prefetch_file(23, 240, 0)).unwrap();
+    let run = |format: &str| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["prefetch", "--root", dir.to_str().unwrap(), "--output-format", format])
+        .output()
+        .unwrap();
+      assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+      String::from_utf8(output.stdout).unwrap()
+    };
+    let csv = run("csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("\"pf_full_path\",\"version\",\"executable_name\""));
+    assert!(lines[1].contains("\"30\",\"APP.EXE\",\"\\VOLUME{01D5-TEST}\\PROGRAM FILES\\APP\\APP.EXE\""));
+    let json: serde_json::Value = serde_json::from_str(&run("json")).unwrap();
+    assert_eq!(json[1]["version"], 23);
+    assert_eq!(json[0]["volumes"][0]["file_references"][1]["mft_entry"], 0x1234);
+    let ecs = run("ecs");
+    let events: Vec<serde_json::Value> = ecs.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    // Seven run times for the version 30 file, one for the version 23 file.
+    assert_eq!(events.len(), 2 * 8);
+    assert_eq!(events[0]["index"]["_index"], "prefetch");
+    assert_eq!(events[1]["event"]["action"], "prefetch-executed");
+    assert_eq!(events[1]["process"]["name"], "APP.EXE");
+    assert_eq!(events[1]["@timestamp"], "2019-04-17T18:40:00Z");
+
+    fs::write(prefetch_dir.join("BAD.EXE-1234ABCD.pf"), &truncated).unwrap();
+    let status = || {
+      Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["prefetch", "--root", dir.to_str().unwrap()])
+        .output()
+        .unwrap()
+        .status
+        .code()
+    };
+    assert_eq!(status(), Some(4));
+    // A header too short for its checksum is a parse failure, a file that can not be read is an I/O failure.
+    fs::write(prefetch_dir.join("BAD.EXE-1234ABCD.pf"), b"MAM\x84\x10\0\0\0\0\0").unwrap();
+    assert_eq!(status(), Some(4));
+    fs::create_dir(prefetch_dir.join("DIR.EXE-1234ABCD.pf")).unwrap();
+    assert_eq!(status(), Some(5));
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  /// A key of a test registry hive.
+  struct TestKey {
+    name: String,
+    last_written: u64,
+    values: Vec<(String, Vec<u8>)>,
+    subkeys: Vec<TestKey>,
+  }
+
+  /// Build a registry hive with a single hive bin holding `root` and its subkeys.
+  fn registry_hive(root: &TestKey, dirty: bool) -> Vec<u8> {
+    fn cell(bins: &mut Vec<u8>, data: &[u8]) -> u32 {
+      let offset = bins.len() as u32;
+      let size = (4 + data.len()).div_ceil(8) * 8;
+      bins.extend((-(size as i32)).to_le_bytes());
+      bins.extend(data);
+      bins.resize(offset as usize + size, 0);
+      offset
+    }
+    fn write_key(bins: &mut Vec<u8>, key: &TestKey) -> u32 {
+      let subkeys: Vec<u32> = key.subkeys.iter().map(|subkey| write_key(bins, subkey)).collect();
+      let mut list = b"lf".to_vec();
+      list.extend((subkeys.len() as u16).to_le_bytes());
+      for offset in &subkeys {
+        list.extend(offset.to_le_bytes());
+        list.extend([0; 4]);
+      }
+      let list = cell(bins, &list);
+      let mut values = vec![];
+      for (name, data) in &key.values {
+        let (size, data_offset) = match data.len() {
+          0..=4 => {
+            let mut inline = [0; 4];
+            inline[..data.len()].copy_from_slice(data);
+            (data.len() as u32 | 0x80000000, u32::from_le_bytes(inline))
+          },
+          _ => (data.len() as u32, cell(bins, data)),
+        };
+        let mut vk = b"vk".to_vec();
+        vk.extend((name.len() as u16).to_le_bytes());
+        vk.extend(size.to_le_bytes());
+        vk.extend(data_offset.to_le_bytes());
+        vk.extend(3u32.to_le_bytes());
+        vk.extend([1, 0, 0, 0]);
+        vk.extend(name.as_bytes());
+        values.extend(cell(bins, &vk).to_le_bytes());
+      }
+      let values = cell(bins, &values);
+      let mut nk = vec![0; 76];
+      nk[..4].copy_from_slice(b"nk\x20\x00");
+      nk[4..12].copy_from_slice(&key.last_written.to_le_bytes());
+      nk[20..24].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+      nk[28..32].copy_from_slice(&list.to_le_bytes());
+      nk[36..40].copy_from_slice(&(key.values.len() as u32).to_le_bytes());
+      nk[40..44].copy_from_slice(&values.to_le_bytes());
+      nk[72..74].copy_from_slice(&(key.name.len() as u16).to_le_bytes());
+      nk.extend(key.name.as_bytes());
+      cell(bins, &nk)
+    }
+    let mut bins = vec![0; 32];
+    let root = write_key(&mut bins, root);
+    bins.resize(bins.len().div_ceil(4096) * 4096, 0);
+    bins[..4].copy_from_slice(b"hbin");
+    let size = bins.len() as u32;
+    bins[8..12].copy_from_slice(&size.to_le_bytes());
+    let mut hive = vec![0; 4096];
+    hive[..4].copy_from_slice(b"regf");
+    hive[4..8].copy_from_slice(&1u32.to_le_bytes());
+    hive[8..12].copy_from_slice(&(if dirty { 2u32 } else { 1 }).to_le_bytes());
+    hive[20..24].copy_from_slice(&1u32.to_le_bytes());
+    hive[24..28].copy_from_slice(&5u32.to_le_bytes());
+    hive[36..40].copy_from_slice(&root.to_le_bytes());
+    hive[40..44].copy_from_slice(&(bins.len() as u32).to_le_bytes());
+    hive.extend(bins);
+    hive
+  }
+
+  #[test]
+  fn test_shellbags() {
+    use lnk::shellbags::{regf::Hive, ShellBags};
+
+    // My Computer, C:\ and the first shell item of the sample IDList (the test folder), with an unparsable sibling of
+    // the test folder that is the most recently used. A value holds the item followed by an empty item.
+    let data = fs::read(SAMPLE).unwrap();
+    let test_size = u16::from_le_bytes([data[0x4E], data[0x4F]]) as usize;
+    let mut root = vec![0x14, 0x00, 0x1F, 0x50];
+    root.extend([
+      0xE0, 0x4F, 0xD0, 0x20, 0xEA, 0x3A, 0x69, 0x10, 0xA2, 0xD8, 0x08, 0x00, 0x2B, 0x30, 0x30, 0x9D,
+    ]);
+    let mut volume = vec![0x19, 0x00, 0x2F];
+    volume.extend(b"C:\\");
+    volume.resize(0x19, 0);
+    let items = [root, volume, data[0x4E..0x4E + test_size].to_vec()];
+    let item = |i: usize| [items[i].as_slice(), &[0, 0]].concat();
+    let test = TestKey {
+      name: String::from("0"),
+      last_written: 132000000000000000,
+      values: vec![(String::from("NodeSlot"), 12u32.to_le_bytes().to_vec())],
+      subkeys: vec![],
+    };
+    let volume = TestKey {
+      name: String::from("0"),
+      last_written: 132000000000000000 + 36000000000,
+      values: vec![(String::from("0"), item(2))],
+      subkeys: vec![test],
+    };
+    let my_computer = TestKey {
+      name: String::from("0"),
+      last_written: 132000000000000000 + 2 * 36000000000,
+      values: vec![
+        (String::from("0"), item(1)),
+        (String::from("1"), vec![0x40, 0x00, 0x1F, 0x50, 0x00, 0x00]),
+        (String::from("MRUListEx"), [1u32, 0, u32::MAX].iter().flat_map(|s| s.to_le_bytes()).collect()),
+      ],
+      subkeys: vec![volume],
+    };
+    let mut key = TestKey {
+      name: String::from("BagMRU"),
+      last_written: 131000000000000000,
+      values: vec![(String::from("0"), item(0))],
+      subkeys: vec![my_computer],
+    };
+    for name in ["Shell", "Windows", "Microsoft", "Software", "ROOT"] {
+      key = TestKey {
+        name: String::from(name),
+        last_written: 0,
+        values: vec![],
+        subkeys: vec![key],
+      };
+    }
+    let hive = registry_hive(&key, false);
+
+    let shell_bags = ShellBags::from_hive(&Hive::from_buffer(hive.clone()).unwrap()).unwrap();
+    assert!(!shell_bags.dirty);
+    let paths: Vec<(&str, &str)> = shell_bags
+      .entries
+      .iter()
+      .map(|entry| (entry.key_path.as_str(), entry.path.as_str()))
+      .collect();
+    assert_eq!(
+      paths,
+      [
+        ("Software\\Microsoft\\Windows\\Shell\\BagMRU\\0", "MY_COMPUTER"),
+        ("Software\\Microsoft\\Windows\\Shell\\BagMRU\\0\\0", "MY_COMPUTER\\C:\\"),
+        ("Software\\Microsoft\\Windows\\Shell\\BagMRU\\0\\0\\0", "MY_COMPUTER\\C:\\test"),
+        ("Software\\Microsoft\\Windows\\Shell\\BagMRU\\0\\1", "MY_COMPUTER\\{UNPARSED}"),
+      ]
+    );
+    // The items are decoded by the same shell item parser as the LNK IDList.
+    let test = &shell_bags.entries[2];
+    assert_eq!(test.item_type, "FileEntry");
+    assert_eq!(test.node_slot, Some(12));
+    assert_eq!(test.mru_position, None);
+    let fields = test.normalize();
+    assert_eq!(fields["key_last_written"], "2019-04-17T18:40:00Z");
+    assert_eq!(fields["item_modification_time"], "2021-02-08T12:46:24Z");
+    let volume = &shell_bags.entries[1];
+    assert_eq!(volume.item_type, "Volume");
+    assert_eq!(volume.mru_position, Some(1));
+    assert!(volume.last_interacted.is_none());
+    assert_eq!(shell_bags.entries[3].item_type, "Unparsed");
+    assert_eq!(shell_bags.entries[3].mru_position, Some(0));
+    assert_eq!(
+      shell_bags.entries[3].last_interacted.as_ref().map(|t| t.to_string()).as_deref(),
+      Some("2019-04-17T20:40:00Z")
+    );
+    assert!(
+      ShellBags::from_hive(&Hive::from_buffer(registry_hive(&key, true)).unwrap())
+        .unwrap()
+        .dirty
+    );
+    assert!(Hive::from_buffer(data).is_err());
+
+    // A slot key whose subkey list points back to the key itself is walked once.
+    let mut looped = TestKey {
+      name: String::from("BagMRU"),
+      last_written: 0,
+      values: vec![(String::from("0"), item(0))],
+      subkeys: vec![TestKey {
+        name: String::from("0"),
+        last_written: 1,
+        values: vec![(String::from("0"), item(1))],
+        subkeys: vec![TestKey {
+          name: String::from("0"),
+          last_written: 0,
+          values: vec![],
+          subkeys: vec![],
+        }],
+      }],
+    };
+    for name in ["Shell", "Windows", "Microsoft", "Software", "ROOT"] {
+      looped = TestKey {
+        name: String::from(name),
+        last_written: 0,
+        values: vec![],
+        subkeys: vec![looped],
+      };
+    }
+    let mut looped = registry_hive(&looped, false);
+    let nk = [b"nk\x20\x00".as_slice(), &1u64.to_le_bytes()].concat();
+    let slot = looped.windows(nk.len()).position(|window| window == nk).unwrap();
+    let list = u32::from_le_bytes(looped[slot + 28..slot + 32].try_into().unwrap()) as usize;
+    let slot_offset = (slot - 4 - 4096) as u32;
+    looped[4096 + list + 8..4096 + list + 12].copy_from_slice(&slot_offset.to_le_bytes());
+    let entries = ShellBags::from_hive(&Hive::from_buffer(looped).unwrap()).unwrap().entries;
+    let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+    assert_eq!(paths, ["MY_COMPUTER", "MY_COMPUTER\\C:\\"]);
+
+    let dir = temp_dir("shellbags");
+    let profile = dir.join("Users").join("u0041");
+    fs::create_dir_all(&profile).unwrap();
+    fs::write(profile.join("NTUSER.DAT"), &hive).unwrap();
+    let run = |format: &str| {
+      let output = Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["shellbags", "--root", dir.to_str().unwrap(), "--output-format", format])
+        .output()
+        .unwrap();
+      assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+      String::from_utf8(output.stdout).unwrap()
+    };
+    let csv = run("csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("\"hive_path\",\"user\",\"key_path\",\"path\""));
+    assert!(lines[3].contains("\"u0041\",\"Software\\Microsoft\\Windows\\Shell\\BagMRU\\0\\0\\0\""));
+    let json: serde_json::Value = serde_json::from_str(&run("json")).unwrap();
+    assert_eq!(json[2]["shell_item"]["shell_item_data"]["FileEntry"]["name"], "test");
+    let events: Vec<serde_json::Value> = run("ecs").lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    let actions: Vec<&str> = events.iter().filter_map(|e| e["event"]["action"].as_str()).collect();
+    assert!(actions.contains(&"shellbag-folder-interacted"));
+    assert!(actions.contains(&"shellbag-folder-created"));
+    assert_eq!(events[1]["event"]["dataset"], "shellbags");
+    assert_eq!(events[1]["user"]["name"], "u0041");
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn test_evidence() {
+    use lnk::evidence::{Hashes, Manifest};
+    use std::io::Cursor;
+
+    // The hashes are those of the whole file or buffer, from_reader hashes the bytes it read.
+    let data = fs::read(SAMPLE).unwrap();
+    let lnk = Lnk::from_path(SAMPLE).unwrap();
+    assert_eq!(lnk.hashes, Hashes::of(&data));
+    assert_eq!(lnk.normalize()["lnk_sha256"], lnk.hashes.sha256);
+    assert_eq!(lnk.hashes.md5.len(), 32);
+    assert_eq!(lnk.hashes.sha1.len(), 40);
+    let mut padded = data.clone();
+    padded.extend([0xCC; 16]);
+    assert_eq!(Lnk::from_buffer(&padded).unwrap().hashes, Hashes::of(&padded));
+    let mut reader = Cursor::new(padded.as_slice());
+    assert_eq!(Lnk::from_reader(&mut reader).unwrap().hashes, Hashes::of(&data));
+    assert_eq!(reader.position() as usize, data.len());
+
+    let dir = temp_dir("evidence");
+    fs::copy(SAMPLE, dir.join("a.lnk")).unwrap();
+    fs::write(dir.join("b.lnk"), b"not a shortcut").unwrap();
+    fs::write(dir.join("key"), "secret\n").unwrap();
+    let (output, manifest) = (dir.join("out.jsonl"), dir.join("manifest.json"));
+    let run = |args: &[&str]| {
+      Command::new(env!("CARGO_BIN_EXE_lnk_parser"))
+        .args(["-p", &dir.join("*.lnk").to_string_lossy(), "--output-format", "jsonl"])
+        .args(args)
+        .output()
+        .unwrap()
+    };
+    let result = run(&[
+      "-o",
+      &output.to_string_lossy(),
+      "--manifest",
+      &manifest.to_string_lossy(),
+      "--manifest-key",
+      &dir.join("key").to_string_lossy(),
+    ]);
+    // b.lnk can not be parsed, the manifest is still written.
+    assert_eq!(result.status.code(), Some(4));
+    let record: serde_json::Value = serde_json::from_str(fs::read_to_string(&output).unwrap().trim()).unwrap();
+    assert_eq!(record["hashes"]["sha1"], lnk.hashes.sha1);
+
+    let mut manifest: Manifest = serde_json::from_str(&fs::read_to_string(&manifest).unwrap()).unwrap();
+    assert_eq!(manifest.version, env!("CARGO_PKG_VERSION"));
+    assert!(manifest.arguments.iter().any(|a| a == "--manifest"));
+    assert!(!manifest.started.is_empty() && manifest.finished >= manifest.started);
+    assert_eq!(manifest.inputs.len(), 2);
+    assert_eq!(manifest.inputs[0].hashes.as_ref(), Some(&lnk.hashes));
+    assert_eq!((manifest.inputs[0].records, manifest.inputs[0].size), (1, Some(data.len() as u64)));
+    assert_eq!(manifest.inputs[1].records, 0);
+    assert!(manifest.inputs[1].error.is_some());
+    let output_hashes = Hashes::of(&fs::read(&output).unwrap());
+    assert_eq!(manifest.output.as_ref().unwrap().hashes, output_hashes);
+    assert_eq!(manifest.output.as_ref().unwrap().hmac_sha256.len(), 64);
+    assert_eq!(manifest.signature.len(), 64);
+    assert!(manifest.verify(b"secret").unwrap());
+    assert!(!manifest.verify(b"other").unwrap());
+    // The manifest itself is signed, editing an input or the recorded output HMAC is detected.
+    let mut edited = manifest.clone();
+    edited.inputs[0].hashes.as_mut().unwrap().sha256 = Hashes::of(b"other").sha256;
+    assert!(!edited.verify(b"secret").unwrap());
+    let mut edited = manifest.clone();
+    edited.output.as_mut().unwrap().hmac_sha256 = "0".repeat(64);
+    assert!(!edited.verify(b"secret").unwrap());
+    fs::write(&output, fs::read_to_string(&output).unwrap().replace("test.txt", "TEST.txt")).unwrap();
+    assert!(!manifest.verify(b"secret").unwrap());
+    manifest.output = None;
+    assert!(!manifest.verify(b"secret").unwrap());
+
+    // The manifest authenticates an output file, and needs a key.
+    let other_manifest = dir.join("other.json").to_string_lossy().into_owned();
+    let result = run(&[
+      "--manifest",
+      &other_manifest,
+      "--manifest-key",
+      &dir.join("key").to_string_lossy(),
+    ]);
+    assert_eq!(result.status.code(), Some(2));
+    assert_eq!(
+      run(&["-o", &output.to_string_lossy(), "--manifest", &other_manifest])
+        .status
+        .code(),
+      Some(2)
+    );
+  }
 }